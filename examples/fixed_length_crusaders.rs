@@ -6,10 +6,10 @@ use tracing_subscriber::filter::LevelFilter;
 
 use pea2pea::{
     protocols::{Handshake, Reading, Writing},
-    Config, Connection, ConnectionSide, Node, Pea2Pea,
+    Config, Connection, ConnectionSide, Node, Pea2Pea, PeerAddr,
 };
 
-use std::{io, net::SocketAddr, time::Duration};
+use std::{io, time::Duration};
 
 #[derive(Clone)]
 struct JoJoNode(Node);
@@ -65,7 +65,7 @@ impl Reading for JoJoNode {
 
     fn read_message<R: io::Read>(
         &self,
-        _source: SocketAddr,
+        _source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>> {
         let mut arr = [0u8; 1];
@@ -75,17 +75,13 @@ impl Reading for JoJoNode {
         Ok(Some(battle_cry))
     }
 
-    async fn process_message(
-        &self,
-        source: SocketAddr,
-        battle_cry: Self::Message,
-    ) -> io::Result<()> {
+    async fn process_message(&self, source: PeerAddr, battle_cry: Self::Message) -> io::Result<()> {
         let reply = match battle_cry {
             BattleCry::Ora => BattleCry::Muda,
             BattleCry::Muda => BattleCry::Ora,
         };
 
-        self.send_direct_message(source, reply)
+        self.send_direct_message(&source, reply)
             .unwrap()
             .await
             .unwrap();
@@ -99,7 +95,7 @@ impl Writing for JoJoNode {
 
     fn write_message<W: io::Write>(
         &self,
-        _: SocketAddr,
+        _: &PeerAddr,
         payload: &Self::Message,
         writer: &mut W,
     ) -> io::Result<()> {
@@ -140,12 +136,12 @@ async fn main() {
         node.enable_writing().await;
     }
 
-    jotaro.node().connect(dio_addr).await.unwrap();
+    jotaro.node().connect(&dio_addr).await.unwrap();
 
     sleep(Duration::from_secs(3)).await;
 
     jotaro
-        .send_direct_message(dio_addr, BattleCry::Ora)
+        .send_direct_message(&dio_addr, BattleCry::Ora)
         .unwrap()
         .await
         .unwrap();
@@ -6,10 +6,10 @@ use tracing_subscriber::filter::LevelFilter;
 
 use pea2pea::{
     protocols::{Disconnect, Handshake, Reading, Writing},
-    Config, Connection, Node, Pea2Pea,
+    Config, Connection, DisconnectReason, Node, Pea2Pea, PeerAddr,
 };
 
-use std::{io, net::SocketAddr, time::Duration};
+use std::{io, time::Duration};
 
 #[derive(Clone)]
 struct NakedNode(Node);
@@ -50,7 +50,7 @@ impl Reading for NakedNode {
 
     fn read_message<R: io::Read>(
         &self,
-        _source: SocketAddr,
+        _source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>> {
         let vec = common::read_len_prefixed_message::<R, 2>(reader)?;
@@ -59,7 +59,7 @@ impl Reading for NakedNode {
             .transpose()
     }
 
-    async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()> {
+    async fn process_message(&self, source: PeerAddr, message: Self::Message) -> io::Result<()> {
         let reply = if self.node().name() == "Drebin" {
             if message == "..." {
                 return Ok(());
@@ -74,7 +74,7 @@ impl Reading for NakedNode {
 
         info!(parent: self.node().span(), "{}", reply);
 
-        self.send_direct_message(source, reply.to_string())
+        self.send_direct_message(&source, reply.to_string())
             .unwrap()
             .await
             .unwrap();
@@ -88,7 +88,7 @@ impl Writing for NakedNode {
 
     fn write_message<W: io::Write>(
         &self,
-        _: SocketAddr,
+        _: &PeerAddr,
         payload: &Self::Message,
         buffer: &mut W,
     ) -> io::Result<()> {
@@ -99,7 +99,7 @@ impl Writing for NakedNode {
 
 #[async_trait::async_trait]
 impl Disconnect for NakedNode {
-    async fn handle_disconnect(&self, _addr: SocketAddr) {
+    async fn handle_disconnect(&self, _addr: PeerAddr, _reason: DisconnectReason) {
         if self.node().name() == "Drebin" {
             info!(parent: self.node().span(), "All right. Who else is almost dead?");
         } else {
@@ -133,12 +133,12 @@ async fn main() {
         hapsburgs_thug.enable_disconnect().await;
 
         // Habsburg's thugs alert Drebin of their presence
-        hapsburgs_thug.node().connect(drebin_addr).await.unwrap();
+        hapsburgs_thug.node().connect(&drebin_addr).await.unwrap();
         sleep(Duration::from_millis(50)).await;
-        let thug_addr = drebin.node().connected_addrs()[0];
+        let thug_addr = drebin.node().connected_addrs()[0].clone();
 
         drebin
-            .send_direct_message(thug_addr, "Talk!".to_string())
+            .send_direct_message(&thug_addr, "Talk!".to_string())
             .unwrap();
 
         sleep(Duration::from_millis(50)).await;
@@ -14,13 +14,12 @@ use tracing_subscriber::filter::LevelFilter;
 use pea2pea::{
     connect_nodes,
     protocols::{Handshake, Reading, Writing},
-    Connection, ConnectionSide, Node, Pea2Pea, Topology,
+    Connection, ConnectionSide, Node, Pea2Pea, PeerAddr, Topology,
 };
 
 use std::{
     collections::HashMap,
     io,
-    net::SocketAddr,
     sync::{
         atomic::{AtomicUsize, Ordering::Relaxed},
         Arc,
@@ -34,7 +33,7 @@ type PlayerName = String;
 
 #[derive(Debug)]
 struct PlayerInfo {
-    addr: SocketAddr,
+    addr: PeerAddr,
     is_carrier: bool,
 }
 
@@ -62,13 +61,13 @@ impl Player {
             .other_players
             .lock()
             .iter()
-            .map(|(name, player)| (name.clone(), player.addr))
+            .map(|(name, player)| (name.clone(), player.addr.clone()))
             .choose(&mut *RNG.lock())
             .unwrap();
 
         info!(parent: self.node().span(), "throwing the potato to player {}!", new_carrier_name);
 
-        self.send_direct_message(new_carrier_addr, Message::HotPotato)
+        self.send_direct_message(&new_carrier_addr, Message::HotPotato)
             .unwrap()
             .await
             .unwrap();
@@ -111,7 +110,7 @@ impl Handshake for Player {
         };
 
         let player = PlayerInfo {
-            addr: conn.addr,
+            addr: conn.addr.clone(),
             is_carrier: false,
         };
         self.other_players.lock().insert(peer_name, player);
@@ -132,7 +131,7 @@ impl Reading for Player {
 
     fn read_message<R: io::Read>(
         &self,
-        _source: SocketAddr,
+        _source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>> {
         // expecting inbound messages to be prefixed with their length encoded as a LE u16
@@ -142,7 +141,7 @@ impl Reading for Player {
             .transpose()
     }
 
-    async fn process_message(&self, _source: SocketAddr, message: Self::Message) -> io::Result<()> {
+    async fn process_message(&self, _source: PeerAddr, message: Self::Message) -> io::Result<()> {
         match message {
             Message::HotPotato => {
                 info!(parent: self.node().span(), "I have the potato!");
@@ -179,7 +178,7 @@ impl Writing for Player {
 
     fn write_message<W: io::Write>(
         &self,
-        _: SocketAddr,
+        _: &PeerAddr,
         payload: &Self::Message,
         writer: &mut W,
     ) -> io::Result<()> {
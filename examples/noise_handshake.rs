@@ -1,7 +1,7 @@
 mod common;
 use common::{prefix_with_len, read_len_prefixed_message};
 
-use parking_lot::{Mutex, RwLock};
+use parking_lot::Mutex;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     time::sleep,
@@ -11,10 +11,10 @@ use tracing_subscriber::filter::LevelFilter;
 
 use pea2pea::{
     protocols::{Handshake, Reading, Writing},
-    Config, Connection, ConnectionSide, Node, Pea2Pea,
+    Config, Connection, ConnectionSide, Node, Pea2Pea, PeerAddr,
 };
 
-use std::{collections::HashMap, io, net::SocketAddr, str, sync::Arc, time::Duration};
+use std::{io, str, time::Duration};
 
 // maximum noise message size, as specified by its protocol
 const NOISE_BUF_LEN: usize = 65535;
@@ -27,7 +27,6 @@ struct NoiseState {
 #[derive(Clone)]
 struct SecureNode {
     node: Node,
-    noise_states: Arc<RwLock<HashMap<SocketAddr, Arc<Mutex<NoiseState>>>>>,
 }
 
 impl Pea2Pea for SecureNode {
@@ -46,10 +45,7 @@ impl SecureNode {
         };
         let node = Node::new(Some(config)).await?;
 
-        Ok(Self {
-            node,
-            noise_states: Default::default(),
-        })
+        Ok(Self { node })
     }
 }
 
@@ -80,7 +76,7 @@ impl Handshake for SecureNode {
                 debug!(parent: self.node().span(), "sent e (XX handshake part 1/3)");
 
                 // <- e, ee, s, es
-                conn.reader().read(&mut buf).await?;
+                let _ = conn.reader().read(&mut buf).await?;
                 let message =
                     read_len_prefixed_message::<_, 2>(&mut io::Cursor::new(buf))?.unwrap();
                 noise.read_message(&message, &mut buffer).unwrap();
@@ -99,7 +95,7 @@ impl Handshake for SecureNode {
                 let mut noise = noise_builder.build_responder().unwrap();
 
                 // <- e
-                conn.reader().read(&mut buf).await?;
+                let _ = conn.reader().read(&mut buf).await?;
                 let message =
                     read_len_prefixed_message::<_, 2>(&mut io::Cursor::new(buf))?.unwrap();
                 noise.read_message(&message, &mut buffer).unwrap();
@@ -113,7 +109,7 @@ impl Handshake for SecureNode {
                 debug!(parent: self.node().span(), "sent e, ee, s, es (XX handshake part 2/3)");
 
                 // <- s, se, psk
-                conn.reader().read(&mut buf).await?;
+                let _ = conn.reader().read(&mut buf).await?;
                 let message =
                     read_len_prefixed_message::<_, 2>(&mut io::Cursor::new(buf))?.unwrap();
                 noise.read_message(&message, &mut buffer).unwrap();
@@ -125,11 +121,9 @@ impl Handshake for SecureNode {
 
         debug!(parent: self.node().span(), "XX handshake complete");
 
+        // the state is dropped along with the connection
         let noise_state = NoiseState { state, buffer };
-
-        self.noise_states
-            .write()
-            .insert(conn.addr, Arc::new(Mutex::new(noise_state)));
+        conn.extensions.insert(Mutex::new(noise_state));
 
         Ok(conn)
     }
@@ -141,13 +135,16 @@ impl Reading for SecureNode {
 
     fn read_message<R: io::Read>(
         &self,
-        source: SocketAddr,
+        source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>> {
         let bytes = read_len_prefixed_message::<_, 2>(reader)?;
 
         if let Some(bytes) = bytes {
-            let noise = Arc::clone(self.noise_states.read().get(&source).unwrap());
+            let noise = self
+                .node()
+                .connection_extension::<Mutex<NoiseState>>(source)
+                .unwrap();
             let NoiseState { state, buffer } = &mut *noise.lock();
 
             let len = state.read_message(&bytes, buffer).ok().unwrap();
@@ -159,7 +156,7 @@ impl Reading for SecureNode {
         }
     }
 
-    async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()> {
+    async fn process_message(&self, source: PeerAddr, message: Self::Message) -> io::Result<()> {
         info!(parent: self.node().span(), "decrypted a message from {}: \"{}\"", source, message);
 
         Ok(())
@@ -171,13 +168,16 @@ impl Writing for SecureNode {
 
     fn write_message<W: io::Write>(
         &self,
-        target: SocketAddr,
+        target: &PeerAddr,
         payload: &Self::Message,
         writer: &mut W,
     ) -> io::Result<()> {
         info!(parent: self.node.span(), "sending an encrypted message to {}: \"{}\"", target, payload);
 
-        let noise = Arc::clone(self.noise_states.read().get(&target).unwrap());
+        let noise = self
+            .node()
+            .connection_extension::<Mutex<NoiseState>>(target)
+            .unwrap();
 
         let NoiseState { state, buffer } = &mut *noise.lock();
         let len = state.write_message(payload.as_bytes(), buffer).unwrap();
@@ -204,7 +204,7 @@ async fn main() {
     // connect the initiator to the responder
     initiator
         .node()
-        .connect(responder.node().listening_addr().unwrap())
+        .connect(&responder.node().listening_addr().unwrap())
         .await
         .unwrap();
 
@@ -214,16 +214,16 @@ async fn main() {
     // send a message from initiator to responder
     let msg = "why hello there, fellow noise protocol user; I'm the initiator";
     initiator
-        .send_direct_message(responder.node().listening_addr().unwrap(), msg.to_string())
+        .send_direct_message(&responder.node().listening_addr().unwrap(), msg.to_string())
         .unwrap()
         .await
         .unwrap();
 
     // send a message from responder to initiator; determine the latter's address first
-    let initiator_addr = responder.node().connected_addrs()[0];
+    let initiator_addr = responder.node().connected_addrs()[0].clone();
     let msg = "why hello there, fellow noise protocol user; I'm the responder";
     responder
-        .send_direct_message(initiator_addr, msg.to_string())
+        .send_direct_message(&initiator_addr, msg.to_string())
         .unwrap()
         .await
         .unwrap();
@@ -7,12 +7,11 @@ use tracing_subscriber::filter::LevelFilter;
 use pea2pea::{
     connect_nodes,
     protocols::{Reading, Writing},
-    Node, Pea2Pea, Topology,
+    Node, Pea2Pea, PeerAddr, Topology,
 };
 
 use std::{
     io::{self, Read},
-    net::SocketAddr,
     time::Duration,
 };
 
@@ -33,7 +32,7 @@ impl Reading for Player {
 
     fn read_message<R: io::Read>(
         &self,
-        _src: SocketAddr,
+        _src: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<String>> {
         let mut len_arr = [0u8; 2];
@@ -59,7 +58,7 @@ impl Reading for Player {
         }
     }
 
-    async fn process_message(&self, source: SocketAddr, message: String) -> io::Result<()> {
+    async fn process_message(&self, source: PeerAddr, message: String) -> io::Result<()> {
         let own_id = self.node().name().parse::<usize>().unwrap();
 
         info!(
@@ -74,7 +73,7 @@ impl Reading for Player {
 
         // there are just a maximum of 2 connections, so this is sufficient
         if let Some(addr) = connected_addrs.into_iter().find(|addr| *addr != source) {
-            self.send_direct_message(addr, message)?.await.unwrap();
+            self.send_direct_message(&addr, message)?.await.unwrap();
         }
 
         Ok(())
@@ -86,7 +85,7 @@ impl Writing for Player {
 
     fn write_message<W: io::Write>(
         &self,
-        _: SocketAddr,
+        _: &PeerAddr,
         payload: &Self::Message,
         writer: &mut W,
     ) -> io::Result<()> {
@@ -117,7 +116,7 @@ async fn main() {
     info!(parent: players[0].node().span(), "psst, player {}; \"{}\", pass it on!", players[1].node().name(), message);
     players[0]
         .send_direct_message(
-            players[1].node().listening_addr().unwrap(),
+            &players[1].node().listening_addr().unwrap(),
             message.to_string(),
         )
         .unwrap()
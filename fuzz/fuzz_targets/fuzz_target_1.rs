@@ -12,14 +12,13 @@
 use libfuzzer_sys::fuzz_target;
 use pea2pea::{
     protocols::{Reading, Writing},
-    Config, Node, Pea2Pea,
+    Config, Node, Pea2Pea, PeerAddr,
 };
 use tokio::time::sleep;
 
 use std::{
     convert::TryInto,
     io::{self, ErrorKind, Read, Write},
-    net::SocketAddr,
     time::Duration,
 };
 
@@ -43,7 +42,7 @@ impl Reading for FuzzNode {
 
     fn read_message<R: Read>(
         &self,
-        _source: SocketAddr,
+        _source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>> {
         // expect a prefix with a u16 LE length of the actual message
@@ -82,11 +81,7 @@ impl Reading for FuzzNode {
         }
     }
 
-    async fn process_message(
-        &self,
-        _source: SocketAddr,
-        _message: Self::Message,
-    ) -> io::Result<()> {
+    async fn process_message(&self, _source: PeerAddr, _message: Self::Message) -> io::Result<()> {
         Ok(())
     }
 }
@@ -96,7 +91,7 @@ impl Writing for FuzzNode {
 
     fn write_message<W: Write>(
         &self,
-        _target: SocketAddr,
+        _target: &PeerAddr,
         payload: &Self::Message,
         writer: &mut W,
     ) -> io::Result<()> {
@@ -125,7 +120,7 @@ fuzz_target!(|messages: Vec<Vec<u8>>| {
         sender.enable_writing().await;
 
         let receiver_addr = receiver.node().listening_addr().unwrap();
-        sender.node().connect(receiver_addr).await.unwrap();
+        sender.node().connect(&receiver_addr).await.unwrap();
 
         // a small delay to ensure that both nodes are fully connected
         sleep(Duration::from_millis(10)).await;
@@ -134,7 +129,7 @@ fuzz_target!(|messages: Vec<Vec<u8>>| {
             // we await the `oneshot::Receiver` returned by `Writing::send_direct_message` every time
             // in order to not "clog" the inbound queue of the `receiver` node
             assert!(sender
-                .send_direct_message(receiver_addr, msg.into())
+                .send_direct_message(&receiver_addr, msg.into())
                 .unwrap()
                 .await
                 .unwrap());
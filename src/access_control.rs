@@ -0,0 +1,144 @@
+use parking_lot::RwLock;
+
+#[cfg(doc)]
+use crate::{KnownPeers, Node};
+
+use std::{fmt, io, net::IpAddr, str::FromStr};
+
+/// A range of IP addresses in the CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    /// The address of the network.
+    addr: IpAddr,
+    /// The number of leading bits of the address that identify the network.
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Creates a new address range; returns an [`io::ErrorKind::InvalidInput`] error if the prefix
+    /// length exceeds the length of the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> io::Result<Self> {
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_prefix_len {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Checks whether the given IP address belongs to the range.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<IpAddr> for Cidr {
+    fn from(addr: IpAddr) -> Self {
+        let prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        Self { addr, prefix_len }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        fn invalid<E>(_: E) -> io::Error {
+            io::ErrorKind::InvalidInput.into()
+        }
+
+        if let Some((addr, prefix_len)) = s.split_once('/') {
+            Self::new(
+                addr.parse().map_err(invalid)?,
+                prefix_len.parse().map_err(invalid)?,
+            )
+        } else {
+            s.parse::<IpAddr>().map(Self::from).map_err(invalid)
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Contains the rules determining which IP addresses the node may be connected with; it is consulted before
+/// accepting inbound connections and before dialing peers in [`Node::connect`]. An address is permitted if it
+/// doesn't match any of the deny rules and - unless there are no allow rules - it matches one of the allow rules.
+///
+/// note: Bans of specific peers are registered in [`KnownPeers`] instead (see [`Node::ban`]).
+#[derive(Default)]
+pub struct AccessControl(RwLock<AccessRules>);
+
+#[derive(Default)]
+struct AccessRules {
+    /// The ranges of addresses that are allowed; if empty, all of them are.
+    allow: Vec<Cidr>,
+    /// The ranges of addresses that are denied.
+    deny: Vec<Cidr>,
+}
+
+impl AccessControl {
+    /// Adds a rule allowing the given range of addresses; once there are any allow rules, the addresses
+    /// not matching any of them are no longer permitted.
+    pub fn allow(&self, cidr: Cidr) {
+        self.0.write().allow.push(cidr);
+    }
+
+    /// Adds a rule denying the given range of addresses; deny rules take precedence over allow rules.
+    pub fn deny(&self, cidr: Cidr) {
+        self.0.write().deny.push(cidr);
+    }
+
+    /// Removes all the rules concerning the given range of addresses; returns `false` if there were none.
+    pub fn remove(&self, cidr: Cidr) -> bool {
+        let mut rules = self.0.write();
+        let num_rules = rules.allow.len() + rules.deny.len();
+        rules.allow.retain(|c| *c != cidr);
+        rules.deny.retain(|c| *c != cidr);
+
+        num_rules != rules.allow.len() + rules.deny.len()
+    }
+
+    /// Removes all the rules.
+    pub fn clear(&self) {
+        let mut rules = self.0.write();
+        rules.allow.clear();
+        rules.deny.clear();
+    }
+
+    /// Returns the allow rules.
+    pub fn allowed(&self) -> Vec<Cidr> {
+        self.0.read().allow.clone()
+    }
+
+    /// Returns the deny rules.
+    pub fn denied(&self) -> Vec<Cidr> {
+        self.0.read().deny.clone()
+    }
+
+    /// Checks whether the node may be connected with the given IP address.
+    pub fn is_permitted(&self, ip: IpAddr) -> bool {
+        let rules = self.0.read();
+
+        !rules.deny.iter().any(|cidr| cidr.contains(ip))
+            && (rules.allow.is_empty() || rules.allow.iter().any(|cidr| cidr.contains(ip)))
+    }
+}
@@ -1,11 +1,20 @@
 #[cfg(doc)]
-use crate::protocols::{self, Handshake, Reading, Writing};
+use crate::protocols::{self, Disconnect, Handshake, Reading, Writing};
+use crate::{FailurePolicy, PeerId, ReputationPolicy};
+
+#[cfg(doc)]
+use crate::{KnownPeers, Node, NodeEvent};
 
 use std::{
     io::{self, ErrorKind::*},
     net::{IpAddr, Ipv4Addr},
 };
 
+#[cfg(feature = "metrics")]
+use std::net::SocketAddr;
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
+
 /// The node's configuration. See the source of [`Config::default`] for the defaults.
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -44,6 +53,73 @@ pub struct Config {
     /// note: This number can very briefly be breached by 1 in case of inbound connection attempts. It can never be
     /// breached by outbound connection attempts, though.
     pub max_connections: u16,
+    /// The maximum number of active inbound connections, i.e. ones initiated by the peers; it allows some of
+    /// the [`Config::max_connections`] to be reserved for outbound connections.
+    ///
+    /// note: If set to `None`, only [`Config::max_connections`] applies.
+    pub max_inbound: Option<u16>,
+    /// The maximum number of active outbound connections, i.e. ones initiated by the node.
+    ///
+    /// note: If set to `None`, only [`Config::max_connections`] applies.
+    pub max_outbound: Option<u16>,
+    /// The maximum number of active connections with a single IP address.
+    ///
+    /// note: If set to `None`, only [`Config::max_connections`] applies; it doesn't apply to peers whose
+    /// [`PeerAddr`](crate::PeerAddr) has no IP.
+    pub max_connections_per_ip: Option<u16>,
+    /// The maximum number of active connections with a single peer, as identified by its [`PeerId`]. Once it is
+    /// reached, a new connection with the peer either replaces one of the existing ones in line with the tie-breaking
+    /// rule (see [`Config::local_peer_id`]), or is rejected.
+    ///
+    /// note: If it is set to `Some(1)` (the default), the node also refuses to connect to an address it is already
+    /// connected or connecting to; otherwise, it can open multiple connections with a single address, which are told
+    /// apart by their [`ConnectionId`]s (see [`Node::connection_ids`]). If set to `None`, any number of connections
+    /// with a single peer is allowed.
+    ///
+    /// [`ConnectionId`]: crate::ConnectionId
+    /// [`Node::connection_ids`]: crate::Node::connection_ids
+    pub max_connections_per_peer: Option<u16>,
+    /// The node's own [`PeerId`]; it determines how the duplicate connections between the node and a peer (e.g. ones
+    /// caused by both sides dialing each other at the same time) are resolved. If it is set, both sides prefer the
+    /// connection initiated by the side with the lower [`PeerId`] over one initiated by the other side; otherwise,
+    /// they prefer the connection whose ends have the lower pair of addresses.
+    ///
+    /// note: The connections initiated by the same side are resolved in favor of the existing one. The rule based on
+    /// addresses requires both sides to see the same addresses of the connections' ends, which is not the case e.g.
+    /// behind a NAT or with a [`UnixTransport`](crate::transport::UnixTransport) (whose dialing sides are unnamed);
+    /// if they don't, the existing connection is preferred, so if both sides dial each other at the same time, each
+    /// of them can keep the connection it initiated and reject the other one, losing both connections. Setting it
+    /// avoids that, as long as the peers use it as their [`PeerId`] too.
+    pub local_peer_id: Option<PeerId>,
+    /// Determines when a peer that keeps failing (e.g. sending invalid messages) is disconnected from,
+    /// and optionally banned.
+    ///
+    /// note: If set to `None`, the failures are only counted in [`Node::known_peers`].
+    pub failure_policy: Option<FailurePolicy>,
+    /// Determines how the reputation scores of the peers are adjusted automatically, and what happens to
+    /// the peers whose scores drop too low.
+    ///
+    /// note: If set to `None`, the scores are only adjusted via [`Node::adjust_score`], and they don't decay.
+    pub reputation_policy: Option<ReputationPolicy>,
+    /// The file the node's [`KnownPeers`] are loaded from when it is created (if the file exists), and saved
+    /// to when it is shut down (see [`KnownPeers::save`]).
+    ///
+    /// note: The persistent peers saved in the file are only redialed once [`Node::restore_persistent_peers`]
+    /// is called, so that the protocols can be enabled first.
+    #[cfg(feature = "serde")]
+    pub known_peers_file: Option<PathBuf>,
+    /// If set (along with [`Config::known_peers_file`]), the [`KnownPeers`] are also saved periodically, at
+    /// this interval.
+    #[cfg(feature = "serde")]
+    pub known_peers_save_interval_ms: Option<u64>,
+    /// If set, the node serves its metrics (see [`metrics::render`]) over HTTP at this address, under the
+    /// [`metrics::METRICS_PATH`]; the port can be set to `0` in order to pick any available one (see
+    /// [`Node::metrics_addr`]).
+    ///
+    /// [`metrics::render`]: crate::metrics::render
+    /// [`metrics::METRICS_PATH`]: crate::metrics::METRICS_PATH
+    #[cfg(feature = "metrics")]
+    pub metrics_addr: Option<SocketAddr>,
 
     /// The size of a per-connection buffer for reading inbound messages. It should be at least as large as the
     /// maximum message size permitted by the network. Any inbound message larger than this value will be rejected
@@ -57,16 +133,42 @@ pub struct Config {
     ///
     /// note: The node needs to implement the [`Reading`] protocol in order for it to have any effect.
     pub inbound_queue_depth: usize,
+    /// Determines what happens to inbound messages that don't fit in a full inbound queue.
+    ///
+    /// note: The node needs to implement the [`Reading`] protocol in order for it to have any effect.
+    pub inbound_queue_policy: QueuePolicy,
     /// The depth of per-connection queues used to send outbound messages; the greater it is, the more outbound
     /// messages the node can enqueue. Setting it to a large value is not recommended, as doing it might
     /// obscure potential issues with your implementation (like slow serialization) or network.
     ///
     /// note: The node needs to implement the [`Writing`] protocol in order for it to have any effect.
     pub outbound_queue_depth: usize,
+    /// Determines what happens to outbound messages that don't fit in a full outbound queue; it can't be
+    /// [`QueuePolicy::Wait`], as [`Writing::send_direct_message`] doesn't block.
+    ///
+    /// note: The node needs to implement the [`Writing`] protocol in order for it to have any effect.
+    pub outbound_queue_policy: QueuePolicy,
+    /// The maximum time allowed for establishing an outbound connection (before it is upgraded and the
+    /// handshake is performed).
+    pub connect_timeout_ms: u64,
+    /// The delay between the consecutive connection attempts in [`Node::connect_any`], unless the
+    /// previous attempt fails sooner.
+    pub connection_attempt_delay_ms: u64,
     /// The maximum time allowed for a connection to perform a handshake before it is rejected.
     ///
     /// note: The node needs to implement the [`Handshake`] protocol in order for it to have any effect.
     pub max_handshake_time_ms: u64,
+    /// If set, [`Node::disconnect`] and [`Node::shut_down`] drain the affected connections before closing
+    /// them: no new outbound messages are accepted, the queued ones are sent and the stream is shut down,
+    /// and the messages that are already being processed are allowed to finish, all within this time limit.
+    /// Only then is the [`Disconnect`] protocol triggered, so it can no longer send messages to the peer.
+    ///
+    /// note: The node needs to implement the [`Reading`] and/or [`Writing`] protocol in order for it to have
+    /// any effect.
+    pub drain_timeout_ms: Option<u64>,
+    /// The number of [`NodeEvent`]s buffered for each subscriber; subscribers that fall behind miss the
+    /// oldest events (see [`Node::subscribe_events`]).
+    pub event_queue_depth: usize,
 }
 
 impl Default for Config {
@@ -96,11 +198,47 @@ impl Default for Config {
                 UnexpectedEof,
             ],
             max_connections: 100,
+            max_inbound: None,
+            max_outbound: None,
+            max_connections_per_ip: None,
+            max_connections_per_peer: Some(1),
+            local_peer_id: None,
+            failure_policy: None,
+            reputation_policy: None,
+            #[cfg(feature = "serde")]
+            known_peers_file: None,
+            #[cfg(feature = "serde")]
+            known_peers_save_interval_ms: None,
+            #[cfg(feature = "metrics")]
+            metrics_addr: None,
 
             read_buffer_size: 64 * 1024,
             inbound_queue_depth: 64,
+            inbound_queue_policy: QueuePolicy::Wait,
             outbound_queue_depth: 64,
+            outbound_queue_policy: QueuePolicy::DropNewest,
+            connect_timeout_ms: 10_000,
+            connection_attempt_delay_ms: 250,
             max_handshake_time_ms: 3_000,
+            drain_timeout_ms: None,
+            event_queue_depth: 256,
         }
     }
 }
+
+/// Specifies what happens when a message is to be added to a full per-connection message queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Wait until there is room in the queue; this means that no further reads from the connection are
+    /// performed, which applies backpressure to the peer.
+    ///
+    /// note: It only applies to inbound messages; since [`Writing::send_direct_message`] doesn't block,
+    /// [`Node::new`] rejects it as [`Config::outbound_queue_policy`] with [`io::ErrorKind::InvalidInput`].
+    Wait,
+    /// Drop the message that was to be queued.
+    DropNewest,
+    /// Drop the oldest queued message in order to make room for the new one.
+    DropOldest,
+    /// Drop the message that was to be queued and disconnect from the related peer (see [`Node::disconnect`]).
+    Disconnect,
+}
@@ -1,43 +1,400 @@
 //! Objects associated with connection handling.
 
+#[cfg(feature = "tls")]
+use crate::transport::rustls::pki_types::CertificateDer;
+use crate::{
+    transport::{ReadHalf, TransportStream, WriteHalf},
+    PeerAddr, PeerId,
+};
+
 use parking_lot::RwLock;
-use tokio::{
-    net::{
-        tcp::{OwnedReadHalf, OwnedWriteHalf},
-        TcpStream,
-    },
-    task::JoinHandle,
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::Span;
+
+#[cfg(doc)]
+use crate::{protocols::Handshake, Config, Node, QueuePolicy};
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    future::Future,
+    io,
+    ops::Not,
+    sync::Arc,
 };
 
-use std::{collections::HashMap, net::SocketAddr, ops::Not};
+/// A connection with an identified peer; it counts towards [`Config::max_connections_per_peer`] from the moment
+/// the handshake concludes, i.e. before it is established.
+pub(crate) struct PeerSlot {
+    pub(crate) id: ConnectionId,
+    pub(crate) addr: PeerAddr,
+    pub(crate) local_addr: Option<PeerAddr>,
+    pub(crate) side: ConnectionSide,
+    established: bool,
+}
+
+impl PeerSlot {
+    pub(crate) fn new(conn: &Connection) -> Self {
+        Self {
+            id: conn.id,
+            addr: conn.addr.clone(),
+            local_addr: conn.local_addr.clone(),
+            side: conn.side,
+            established: false,
+        }
+    }
+
+    /// Returns the addresses of both ends of the connection, the lower one first; unlike the sides, they
+    /// are the same for both parties of the connection, unless e.g. a NAT is involved.
+    pub(crate) fn endpoints(&self) -> Option<(&PeerAddr, &PeerAddr)> {
+        let local_addr = self.local_addr.as_ref()?;
 
+        Some(if local_addr < &self.addr {
+            (local_addr, &self.addr)
+        } else {
+            (&self.addr, local_addr)
+        })
+    }
+}
+
+/// The active connections, grouped by address; there can be several ones with a single address (see
+/// [`Config::max_connections_per_peer`]), in which case they are listed from the oldest to the newest one.
 #[derive(Default)]
-pub(crate) struct Connections(RwLock<HashMap<SocketAddr, Connection>>);
+pub(crate) struct Connections {
+    conns: RwLock<HashMap<PeerAddr, Vec<Connection>>>,
+    peer_ids: RwLock<HashMap<PeerId, Vec<PeerSlot>>>,
+}
 
 impl Connections {
-    pub(crate) fn add(&self, conn: Connection) {
-        self.0.write().insert(conn.addr, conn);
+    /// Registers the connection; it is handed back if it was identified, but its reserved slot has been taken
+    /// over by another connection with the same peer in the meantime (see [`Connections::reserve`]).
+    pub(crate) fn add(&self, conn: Connection) -> Option<Connection> {
+        let mut conns = self.conns.write();
+        if let Some(ref peer_id) = conn.peer_id {
+            let mut peer_ids = self.peer_ids.write();
+            let slot = peer_ids
+                .get_mut(peer_id)
+                .and_then(|slots| slots.iter_mut().find(|slot| slot.id == conn.id));
+            if let Some(slot) = slot {
+                slot.established = true;
+            } else {
+                return Some(conn);
+            }
+        }
+        // the lock is still held, so the tasks waiting for the signal will find the connection
+        let _ = conn.ready.send(true);
+        conns.entry(conn.addr.clone()).or_default().push(conn);
+
+        None
+    }
+
+    /// Reserves a slot for a connection with the given peer, so that connections set up concurrently can't
+    /// exceed the limit. If it has already been reached, the most recent slot that the new connection is
+    /// preferred over is taken over; its identifier is returned if it belongs to an established connection,
+    /// which then needs to be closed.
+    pub(crate) fn reserve<F: Fn(&PeerSlot, &PeerSlot) -> bool>(
+        &self,
+        peer_id: &PeerId,
+        slot: PeerSlot,
+        limit: Option<u16>,
+        is_preferred: F,
+    ) -> io::Result<Option<ConnectionId>> {
+        let mut peer_ids = self.peer_ids.write();
+        let slots = peer_ids.entry(peer_id.clone()).or_default();
+
+        let mut replaced = None;
+        if let Some(limit) = limit {
+            if slots.len() >= limit as usize {
+                let idx = slots
+                    .iter()
+                    .rposition(|existing| is_preferred(&slot, existing))
+                    .ok_or(io::ErrorKind::AlreadyExists)?;
+                let existing = slots.remove(idx);
+                if existing.established {
+                    replaced = Some(existing.id);
+                }
+            }
+        }
+
+        slots.push(slot);
+
+        Ok(replaced)
+    }
+
+    /// Releases the slot reserved for a connection that couldn't be established.
+    pub(crate) fn release(&self, peer_id: &PeerId, id: ConnectionId) {
+        let mut peer_ids = self.peer_ids.write();
+        if let Some(slots) = peer_ids.get_mut(peer_id) {
+            slots.retain(|slot| slot.established || slot.id != id);
+            if slots.is_empty() {
+                peer_ids.remove(peer_id);
+            }
+        }
+    }
+
+    pub(crate) fn is_connected(&self, addr: &PeerAddr) -> bool {
+        self.conns.read().contains_key(addr)
     }
 
-    pub(crate) fn is_connected(&self, addr: SocketAddr) -> bool {
-        self.0.read().contains_key(&addr)
+    pub(crate) fn contains(&self, id: ConnectionId) -> bool {
+        self.conns
+            .read()
+            .values()
+            .flatten()
+            .any(|conn| conn.id == id)
     }
 
-    pub(crate) fn remove(&self, addr: SocketAddr) -> Option<Connection> {
-        self.0.write().remove(&addr)
+    pub(crate) fn remove(&self, id: ConnectionId) -> Option<Connection> {
+        let mut conns = self.conns.write();
+        let (addr, idx) = conns.iter().find_map(|(addr, conns)| {
+            let idx = conns.iter().position(|conn| conn.id == id)?;
+            Some((addr.clone(), idx))
+        })?;
+        let addr_conns = conns.get_mut(&addr).unwrap(); // safe; it was just found
+        let conn = addr_conns.remove(idx);
+        if addr_conns.is_empty() {
+            conns.remove(&addr);
+        }
+
+        if let Some(ref peer_id) = conn.peer_id {
+            let mut peer_ids = self.peer_ids.write();
+            if let Some(slots) = peer_ids.get_mut(peer_id) {
+                slots.retain(|slot| slot.id != id);
+                if slots.is_empty() {
+                    peer_ids.remove(peer_id);
+                }
+            }
+        }
+
+        Some(conn)
     }
 
     pub(crate) fn num_connected(&self) -> usize {
-        self.0.read().len()
+        self.conns.read().values().map(Vec::len).sum()
+    }
+
+    pub(crate) fn count<F: Fn(&PeerAddr, ConnectionSide) -> bool>(&self, filter: F) -> usize {
+        self.conns
+            .read()
+            .values()
+            .flatten()
+            .filter(|conn| filter(&conn.addr, conn.side))
+            .count()
+    }
+
+    pub(crate) fn side(&self, addr: &PeerAddr) -> Option<ConnectionSide> {
+        self.conns.read().get(addr)?.first().map(|conn| conn.side)
+    }
+
+    pub(crate) fn addr(&self, id: ConnectionId) -> Option<PeerAddr> {
+        self.conns
+            .read()
+            .values()
+            .flatten()
+            .find(|conn| conn.id == id)
+            .map(|conn| conn.addr.clone())
+    }
+
+    pub(crate) fn id(&self, addr: &PeerAddr) -> Option<ConnectionId> {
+        self.conns.read().get(addr)?.first().map(|conn| conn.id)
+    }
+
+    pub(crate) fn ids(&self, addr: &PeerAddr) -> Vec<ConnectionId> {
+        self.conns
+            .read()
+            .get(addr)
+            .map(|conns| conns.iter().map(|conn| conn.id).collect())
+            .unwrap_or_default()
     }
 
-    pub(crate) fn addrs(&self) -> Vec<SocketAddr> {
-        self.0.read().keys().copied().collect()
+    pub(crate) fn all_ids(&self) -> Vec<ConnectionId> {
+        self.conns
+            .read()
+            .values()
+            .flatten()
+            .map(|conn| conn.id)
+            .collect()
+    }
+
+    pub(crate) fn info(&self, addr: &PeerAddr) -> Option<ConnectionInfo> {
+        self.conns
+            .read()
+            .get(addr)?
+            .first()
+            .map(|conn| ConnectionInfo {
+                addr: conn.addr.clone(),
+                id: conn.id,
+                side: conn.side,
+                peer_id: conn.peer_id.clone(),
+                extensions: conn.extensions.clone(),
+                #[cfg(feature = "tls")]
+                peer_certificates: conn.peer_certificates.clone(),
+                #[cfg(feature = "noise")]
+                remote_static_key: conn.remote_static_key.clone(),
+            })
+    }
+
+    pub(crate) fn span(&self, addr: &PeerAddr) -> Option<Span> {
+        self.conns
+            .read()
+            .get(addr)?
+            .first()
+            .map(|conn| conn.span.clone())
+    }
+
+    pub(crate) fn extension<T: Send + Sync + 'static>(&self, addr: &PeerAddr) -> Option<Arc<T>> {
+        self.conns
+            .read()
+            .get(addr)?
+            .first()?
+            .extensions
+            .get_shared()
+    }
+
+    pub(crate) fn addrs(&self) -> Vec<PeerAddr> {
+        self.conns.read().keys().cloned().collect()
+    }
+
+    pub(crate) fn peer_id(&self, addr: &PeerAddr) -> Option<PeerId> {
+        self.conns.read().get(addr)?.first()?.peer_id.clone()
+    }
+
+    pub(crate) fn peer_addrs(&self, peer_id: &PeerId) -> Vec<PeerAddr> {
+        let mut addrs = Vec::new();
+        for (addr, _) in self.established_slots(peer_id) {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+
+        addrs
+    }
+
+    pub(crate) fn peer_connection_ids(&self, peer_id: &PeerId) -> Vec<ConnectionId> {
+        self.established_slots(peer_id)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect()
+    }
+
+    /// Returns the addresses and identifiers of the established connections with the given peer, from the
+    /// oldest to the newest one.
+    fn established_slots(&self, peer_id: &PeerId) -> Vec<(PeerAddr, ConnectionId)> {
+        self.peer_ids
+            .read()
+            .get(peer_id)
+            .map(|slots| {
+                slots
+                    .iter()
+                    .filter(|slot| slot.established)
+                    .map(|slot| (slot.addr.clone(), slot.id))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// A sequential identifier of a [`Connection`], unique within the [`Node`]; the identifiers are assigned
+/// in the order in which the node starts setting the connections up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId(pub(crate) u64);
+
+impl ConnectionId {
+    /// Returns the numeric value of the identifier.
+    pub fn as_u64(self) -> u64 {
+        self.0
     }
 }
 
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A type map containing arbitrary data associated with a [`Connection`], e.g. the state of an encryption layer
+/// established during the [`Handshake`]; it can hold a single value of any given type.
+///
+/// note: The values are shared between the [`Connection`], the [`ConnectionInfo`] objects obtained via
+/// [`Node::connection_info`] and the handles obtained via [`Node::connection_extension`], so mutable state
+/// needs to be wrapped in a synchronization primitive (e.g. a `Mutex`).
+#[derive(Clone, Default)]
+pub struct Extensions(HashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+impl Extensions {
+    /// Inserts a value, returning the previous value of the same type, if there was one.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<Arc<T>> {
+        self.0
+            .insert(TypeId::of::<T>(), Arc::new(value))
+            .and_then(|prev| prev.downcast().ok())
+    }
+
+    /// Returns a reference to the value of the given type, if there is one.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Returns a shared handle to the value of the given type, if there is one; unlike [`Extensions::get`],
+    /// it allows the value to outlive the [`Extensions`].
+    pub fn get_shared<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        Arc::clone(self.0.get(&TypeId::of::<T>())?).downcast().ok()
+    }
+
+    /// Removes the value of the given type, returning it if there was one.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
+        self.0.remove(&TypeId::of::<T>())?.downcast().ok()
+    }
+
+    /// Checks whether there is a value of the given type.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.0.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the number of stored values.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks whether there are no stored values.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Information about an active connection, obtained via [`Node::connection_info`]; it is a snapshot, except
+/// for the values of the [`Extensions`], which are shared with the [`Connection`].
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// The address of the connection.
+    pub addr: PeerAddr,
+    /// The identifier of the connection.
+    pub id: ConnectionId,
+    /// The connection's side in relation to the node.
+    pub side: ConnectionSide,
+    /// The stable identifier of the peer, if it has one.
+    pub peer_id: Option<PeerId>,
+    /// The data associated with the connection.
+    pub extensions: Extensions,
+    /// The certificates presented by the peer if the connection is secured with TLS (see
+    /// [`Connection::peer_certificates`]).
+    #[cfg(feature = "tls")]
+    pub peer_certificates: Option<Vec<CertificateDer<'static>>>,
+    /// The peer's static public key if the connection is secured with Noise (see [`Connection::remote_static_key`]).
+    #[cfg(feature = "noise")]
+    pub remote_static_key: Option<Vec<u8>>,
+}
+
 /// Indicates who was the initiator and who was the responder when the connection was established.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ConnectionSide {
     /// The side that initiated the connection.
     Initiator,
@@ -45,6 +402,33 @@ pub enum ConnectionSide {
     Responder,
 }
 
+/// The reason why the node has disconnected from a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DisconnectReason {
+    /// The disconnect was requested via [`Node::disconnect`].
+    Requested,
+    /// The peer has closed the connection.
+    PeerClosed,
+    /// A fatal error (see [`Config::fatal_io_errors`]) was encountered while reading from the connection.
+    ReadError(io::ErrorKind),
+    /// A fatal error (see [`Config::fatal_io_errors`]) was encountered while writing to the connection.
+    WriteError(io::ErrorKind),
+    /// A message queue was full and its policy is [`QueuePolicy::Disconnect`].
+    QueueOverflow,
+    /// The node is shutting down (see [`Node::shut_down`]).
+    ShutDown,
+    /// The peer was banned (see [`Node::ban`]).
+    Banned,
+    /// The peer has exceeded the number of failures allowed by [`Config::failure_policy`].
+    TooManyFailures,
+    /// The peer's reputation score has dropped below a threshold set by [`Config::reputation_policy`].
+    LowReputation,
+    /// Another connection with the same peer was preferred over this one (see
+    /// [`Config::max_connections_per_peer`]).
+    Duplicate,
+}
+
 impl Not for ConnectionSide {
     type Output = Self;
 
@@ -61,40 +445,136 @@ impl Not for ConnectionSide {
 /// of the connection.
 pub struct Connection {
     /// The address of the connection.
-    pub addr: SocketAddr,
+    pub addr: PeerAddr,
+    /// The address of the node's own end of the connection, if the transport can provide it.
+    pub(crate) local_addr: Option<PeerAddr>,
     /// Kept only until the protocols are enabled (the reading protocol should take it).
-    pub reader: Option<OwnedReadHalf>,
+    pub reader: Option<ReadHalf>,
     /// Kept only until the protocols are enabled (the writing protocol should take it).
-    pub writer: Option<OwnedWriteHalf>,
+    pub writer: Option<WriteHalf>,
+    /// The writer left over if the writing protocol isn't enabled; it is kept so that the node doesn't close
+    /// its side of the stream until the connection is dropped.
+    idle_writer: Option<WriteHalf>,
     /// Handles to tasks spawned for the connection.
     pub tasks: Vec<JoinHandle<()>>,
     /// The connection's side in relation to the node.
     pub side: ConnectionSide,
+    /// The stable identifier of the peer; it can be assigned during the handshake.
+    pub peer_id: Option<PeerId>,
+    /// Arbitrary data associated with the connection; it can be populated during the handshake, is available
+    /// via [`Node::connection_info`] afterwards, and is dropped once the node disconnects from the peer.
+    pub extensions: Extensions,
+    /// The identifier of the connection.
+    id: ConnectionId,
+    /// The connection's tracing span; a child of the node's span.
+    span: Span,
+    /// Signals that the connection has been fully established.
+    ready: watch::Sender<bool>,
+    /// Allows the connection's tasks to wait for the connection to be fully established.
+    ready_receiver: watch::Receiver<bool>,
+    /// The certificates presented by the peer if the connection is secured with TLS.
+    #[cfg(feature = "tls")]
+    peer_certificates: Option<Vec<CertificateDer<'static>>>,
+    /// The peer's static public key if the connection is secured with Noise.
+    #[cfg(feature = "noise")]
+    pub(crate) remote_static_key: Option<Vec<u8>>,
 }
 
 impl Connection {
     /// Creates a [`Connection`] with placeholders for protocol-related objects.
-    pub(crate) fn new(addr: SocketAddr, stream: TcpStream, side: ConnectionSide) -> Self {
-        let (reader, writer) = stream.into_split();
+    pub(crate) fn new(
+        addr: PeerAddr,
+        local_addr: Option<PeerAddr>,
+        stream: Box<dyn TransportStream>,
+        side: ConnectionSide,
+        id: ConnectionId,
+        span: Span,
+    ) -> Self {
+        #[cfg(feature = "tls")]
+        let peer_certificates = stream.peer_certificates();
+        let (reader, writer) = stream.split();
+        let (ready, ready_receiver) = watch::channel(false);
 
         Self {
             addr,
+            local_addr,
             reader: Some(reader),
             writer: Some(writer),
+            idle_writer: None,
             side,
+            peer_id: None,
+            extensions: Default::default(),
+            id,
+            span,
+            ready,
+            ready_receiver,
             tasks: Default::default(),
+            #[cfg(feature = "tls")]
+            peer_certificates,
+            #[cfg(feature = "noise")]
+            remote_static_key: None,
+        }
+    }
+
+    /// Returns the identifier of the connection.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Returns the connection's tracing span; it contains the peer's address, the connection's side and
+    /// identifier, and - once it is known - the peer's [`PeerId`], and it is the parent span of the logs
+    /// related to the connection.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Returns a future that resolves once the connection is fully established, i.e. once all the protocols
+    /// have been enabled and the node has registered it; it resolves to `false` if the connection is dropped
+    /// before that happens. It is used to postpone the reads and writes performed by the protocols' tasks.
+    pub(crate) fn readiness(&self) -> impl Future<Output = bool> + Send + 'static {
+        let mut receiver = self.ready_receiver.clone();
+
+        async move {
+            while !*receiver.borrow() {
+                if receiver.changed().await.is_err() {
+                    return false;
+                }
+            }
+
+            true
         }
     }
 
+    /// Returns the certificates presented by the peer if the connection is secured with TLS
+    /// (see [`TlsTransport`](crate::transport::TlsTransport)); once the connection is established, they are
+    /// available via [`Node::connection_info`].
+    #[cfg(feature = "tls")]
+    pub fn peer_certificates(&self) -> Option<&[CertificateDer<'static>]> {
+        self.peer_certificates.as_deref()
+    }
+
+    /// Returns the peer's static public key if the connection is secured with Noise and the handshake
+    /// pattern transmits or requires it (see [`noise::perform_handshake`](crate::noise::perform_handshake));
+    /// once the connection is established, it is available via [`Node::connection_info`].
+    #[cfg(feature = "noise")]
+    pub fn remote_static_key(&self) -> Option<&[u8]> {
+        self.remote_static_key.as_deref()
+    }
+
+    /// Makes the writer unavailable without closing the node's side of the stream.
+    pub(crate) fn disable_writer(&mut self) {
+        self.idle_writer = self.writer.take();
+    }
+
     /// Provides mutable access to the underlying reader; it should only be used in protocol definitions.
-    pub fn reader(&mut self) -> &mut OwnedReadHalf {
+    pub fn reader(&mut self) -> &mut ReadHalf {
         self.reader
             .as_mut()
             .expect("Connection's reader is not available!")
     }
 
     /// Provides mutable access to the underlying writer; it should only be used in protocol definitions.
-    pub fn writer(&mut self) -> &mut OwnedWriteHalf {
+    pub fn writer(&mut self) -> &mut WriteHalf {
         self.writer
             .as_mut()
             .expect("Connection's writer is not available!")
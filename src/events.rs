@@ -0,0 +1,66 @@
+use crate::{ConnectionSide, DisconnectReason, PeerAddr};
+
+#[cfg(doc)]
+use crate::{protocols::Handshake, Config, Node};
+
+use std::io;
+
+/// An event related to the node's connections; the events can be received via [`Node::subscribe_events`].
+///
+/// note: The `side` fields follow the convention of [`Connection::side`](crate::Connection::side), i.e. they
+/// indicate the side of the peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeEvent {
+    /// A connection is being set up, either because it was accepted or because the node is connecting to a peer.
+    Connecting {
+        /// The address of the peer.
+        addr: PeerAddr,
+        /// The side of the peer.
+        side: ConnectionSide,
+    },
+    /// The [`Handshake`] with a peer has started.
+    HandshakeStarted {
+        /// The address of the peer.
+        addr: PeerAddr,
+    },
+    /// The [`Handshake`] with a peer has failed or timed out.
+    HandshakeFailed {
+        /// The address of the peer.
+        addr: PeerAddr,
+        /// The kind of the error that caused the failure.
+        error: io::ErrorKind,
+    },
+    /// A connection couldn't be set up; it follows [`NodeEvent::HandshakeFailed`] if that was the cause.
+    ConnectionFailed {
+        /// The address of the peer.
+        addr: PeerAddr,
+        /// The kind of the error that caused the failure.
+        error: io::ErrorKind,
+    },
+    /// A connection has been fully established, i.e. all the protocols have been enabled for it.
+    Connected {
+        /// The address of the peer.
+        addr: PeerAddr,
+        /// The side of the peer.
+        side: ConnectionSide,
+    },
+    /// A connection was rejected because the node has reached one of its connection limits, e.g.
+    /// [`Config::max_connections`].
+    Rejected {
+        /// The address of the peer.
+        addr: PeerAddr,
+    },
+    /// The node has disconnected from a peer.
+    Disconnected {
+        /// The address of the peer.
+        addr: PeerAddr,
+        /// The reason for the disconnect.
+        reason: DisconnectReason,
+    },
+    /// The node's listener has failed to accept a connection.
+    ListenerError {
+        /// The kind of the listener's error.
+        error: io::ErrorKind,
+    },
+}
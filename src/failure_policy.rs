@@ -0,0 +1,85 @@
+use crate::BanTarget;
+
+use parking_lot::Mutex;
+
+#[cfg(doc)]
+use crate::{Config, ConnectionSide, KnownPeers, Node};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Determines when a peer has failed too many times and needs to be disconnected from (see
+/// [`Config::failure_policy`]); the failures are registered via [`Node::register_failure`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailurePolicy {
+    /// The number of failures within the window that causes the peer to be disconnected from.
+    pub max_failures: u32,
+    /// The length of the sliding window; only the failures registered within it are counted.
+    pub window_ms: u64,
+    /// If set, every period of this length that elapses since the peer's most recent failure forgives
+    /// one of its earlier failures, allowing it to recover quicker than the window would.
+    pub decay_ms: Option<u64>,
+    /// If set, the offending peer is also banned for this long; if the node initiated the connection, the
+    /// ban applies to the peer's address, otherwise it applies to its IP (see [`Node::ban`]).
+    ///
+    /// note: The failures are counted the same way, i.e. the failures of inbound connections from a single
+    /// IP add up, regardless of their port.
+    pub ban_duration_ms: Option<u64>,
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 10,
+            window_ms: 60_000,
+            decay_ms: None,
+            ban_duration_ms: None,
+        }
+    }
+}
+
+/// Keeps track of the recent failures of the node's peers.
+#[derive(Default)]
+pub(crate) struct FailureLog(Mutex<HashMap<BanTarget, VecDeque<Instant>>>);
+
+impl FailureLog {
+    /// Registers a failure of the given peer; returns `true` if it exceeds the limit set by the policy.
+    pub(crate) fn register(&self, target: &BanTarget, policy: &FailurePolicy) -> bool {
+        let now = Instant::now();
+        let window = Duration::from_millis(policy.window_ms);
+        let mut log = self.0.lock();
+
+        // forget the failures that fell out of the window (along with the related peers)
+        log.retain(|_, failures| {
+            while let Some(failure) = failures.front() {
+                if now.duration_since(*failure) > window {
+                    failures.pop_front();
+                } else {
+                    break;
+                }
+            }
+            !failures.is_empty()
+        });
+
+        let failures = log.entry(target.clone()).or_default();
+
+        // forgive some of the failures if the peer hasn't failed in a while
+        if let (Some(decay_ms), Some(last_failure)) = (policy.decay_ms, failures.back()) {
+            let forgiven = now.duration_since(*last_failure).as_millis() / decay_ms.max(1) as u128;
+            for _ in 0..forgiven.min(failures.len() as u128) {
+                failures.pop_front();
+            }
+        }
+
+        failures.push_back(now);
+
+        if failures.len() >= policy.max_failures as usize {
+            log.remove(target);
+            true
+        } else {
+            false
+        }
+    }
+}
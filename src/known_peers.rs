@@ -1,52 +1,567 @@
+#[cfg(feature = "serde")]
+use parking_lot::Mutex;
 use parking_lot::RwLock;
 
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+#[cfg(feature = "serde")]
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use crate::Stats;
+#[cfg(feature = "serde")]
+use crate::stats::{unix_millis, StatsRecord};
+use crate::{PeerAddr, PeerId, ReconnectPolicy, Stats, StatsSnapshot};
+
+/// The version of the format of the files containing saved [`KnownPeers`].
+#[cfg(feature = "serde")]
+const FILE_VERSION: u32 = 1;
+
+#[cfg(doc)]
+use crate::{Node, ReputationPolicy};
+
+/// Contains statistics related to node's peers, currently connected or not; peers with a known
+/// [`PeerId`] share the same [`Stats`] across all the addresses they connect from. It also keeps
+/// track of the peers' reputation scores (see [`ReputationPolicy`]).
+#[derive(Default)]
+pub struct KnownPeers(
+    RwLock<KnownPeersInner>,
+    /// Serializes the saves to files, which use a common temporary file.
+    #[cfg(feature = "serde")]
+    Mutex<()>,
+);
 
-/// Contains statistics related to node's peers, currently connected or not.
 #[derive(Default)]
-pub struct KnownPeers(RwLock<HashMap<SocketAddr, Arc<Stats>>>);
+struct KnownPeersInner {
+    /// The stats of peers, keyed by their addresses.
+    addrs: HashMap<PeerAddr, Arc<Stats>>,
+    /// The stats of peers with a known [`PeerId`].
+    peer_ids: HashMap<PeerId, Arc<Stats>>,
+    /// The banned peers, along with the expiry times of their bans.
+    bans: HashMap<BanTarget, Option<Instant>>,
+    /// The persistent peers, along with their reconnect policies.
+    persistent_peers: HashMap<PeerAddr, ReconnectPolicy>,
+    /// The half-life of the reputation scores; if `None`, they don't decay.
+    score_half_life: Option<Duration>,
+}
+
+impl KnownPeersInner {
+    /// Removes the bans that have expired by the given time.
+    fn remove_expired_bans(&mut self, now: Instant) {
+        self.bans
+            .retain(|_, expiry| expiry.map(|expiry| expiry > now) != Some(false));
+    }
+}
+
+/// A point-in-time copy of the [`KnownPeers`]' stats and scores (see [`KnownPeers::stats_snapshot`]).
+///
+/// note: Just like in [`KnownPeers`], the addresses associated with the same [`PeerId`] share their stats.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KnownPeersSnapshot {
+    /// The stats of the known peers, keyed by their addresses.
+    pub addrs: HashMap<PeerAddr, StatsSnapshot>,
+    /// The stats of the known peers with a [`PeerId`].
+    pub peer_ids: HashMap<PeerId, StatsSnapshot>,
+    /// The reputation scores of the known peers, keyed by their addresses.
+    pub scores: HashMap<PeerAddr, f64>,
+}
+
+impl KnownPeersSnapshot {
+    /// Returns the changes that have occurred since the given earlier snapshot (see [`StatsSnapshot::diff`]);
+    /// the peers that weren't known back then are compared against empty stats, and the scores are taken
+    /// from `self`.
+    pub fn diff(&self, earlier: &Self) -> Self {
+        fn diff_map<K: Clone + Eq + std::hash::Hash>(
+            later: &HashMap<K, StatsSnapshot>,
+            earlier: &HashMap<K, StatsSnapshot>,
+        ) -> HashMap<K, StatsSnapshot> {
+            let empty = StatsSnapshot::default();
+            later
+                .iter()
+                .map(|(key, stats)| (key.clone(), stats.diff(earlier.get(key).unwrap_or(&empty))))
+                .collect()
+        }
+
+        Self {
+            addrs: diff_map(&self.addrs, &earlier.addrs),
+            peer_ids: diff_map(&self.peer_ids, &earlier.peer_ids),
+            scores: self.scores.clone(),
+        }
+    }
+}
+
+/// The contents of the files containing saved [`KnownPeers`] (see [`KnownPeers::save_to`]).
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedKnownPeers {
+    /// The version of the format.
+    version: u32,
+    /// The stats shared by the addresses and [`PeerId`]s referring to their indices.
+    #[serde(default)]
+    stats: Vec<StatsRecord>,
+    /// The known addresses, along with the indices of their stats.
+    #[serde(default)]
+    addrs: Vec<(PeerAddr, usize)>,
+    /// The known [`PeerId`]s, along with the indices of their stats.
+    #[serde(default)]
+    peer_ids: Vec<(PeerId, usize)>,
+    /// The active bans, along with their expiry times in milliseconds since the Unix epoch (`None` if permanent).
+    #[serde(default)]
+    bans: Vec<(BanTarget, Option<u64>)>,
+    /// The persistent peers, along with their reconnect policies.
+    #[serde(default)]
+    persistent_peers: Vec<(PeerAddr, ReconnectPolicy)>,
+}
+
+/// The subject of a ban: either a single address, or all the addresses with the given IP.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BanTarget {
+    /// All the addresses with the given IP.
+    Ip(IpAddr),
+    /// A single address.
+    Addr(PeerAddr),
+}
+
+impl BanTarget {
+    /// Checks whether the given address is subject to the ban.
+    pub fn matches(&self, addr: &PeerAddr) -> bool {
+        match self {
+            Self::Ip(ip) => addr.ip() == Some(*ip),
+            Self::Addr(a) => addr == a,
+        }
+    }
+}
+
+impl From<IpAddr> for BanTarget {
+    fn from(ip: IpAddr) -> Self {
+        Self::Ip(ip)
+    }
+}
+
+impl From<PeerAddr> for BanTarget {
+    fn from(addr: PeerAddr) -> Self {
+        Self::Addr(addr)
+    }
+}
+
+impl From<&PeerAddr> for BanTarget {
+    fn from(addr: &PeerAddr) -> Self {
+        Self::Addr(addr.clone())
+    }
+}
+
+impl From<SocketAddr> for BanTarget {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Addr(addr.into())
+    }
+}
 
 impl KnownPeers {
+    /// Creates a collection of known peers whose reputation scores decay with the given half-life.
+    pub(crate) fn new(score_half_life: Option<Duration>) -> Self {
+        let known_peers = Self::default();
+        known_peers.0.write().score_half_life = score_half_life;
+
+        known_peers
+    }
+
     /// Adds an address to the list of known peers.
-    pub fn add(&self, addr: SocketAddr) {
-        self.0.write().entry(addr).or_default();
+    pub fn add(&self, addr: &PeerAddr) {
+        let mut inner = self.0.write();
+        if !inner.addrs.contains_key(addr) {
+            inner.addrs.insert(addr.clone(), Default::default());
+        }
+    }
+
+    /// Associates the given address with the given [`PeerId`]; if the peer is already known by its
+    /// identifier, its existing stats become available under the new address too.
+    pub fn add_peer_id(&self, addr: &PeerAddr, peer_id: PeerId) {
+        let mut inner = self.0.write();
+        let stats = if let Some(stats) = inner.peer_ids.get(&peer_id) {
+            Arc::clone(stats)
+        } else {
+            let stats = inner.addrs.get(addr).cloned().unwrap_or_default();
+            inner.peer_ids.insert(peer_id, Arc::clone(&stats));
+            stats
+        };
+        inner.addrs.insert(addr.clone(), stats);
     }
 
     /// Returns the stats for the given peer.
-    pub fn get(&self, addr: SocketAddr) -> Option<Arc<Stats>> {
-        self.0.read().get(&addr).map(Arc::clone)
+    pub fn get(&self, addr: &PeerAddr) -> Option<Arc<Stats>> {
+        self.0.read().addrs.get(addr).map(Arc::clone)
+    }
+
+    /// Returns the stats for the peer with the given [`PeerId`].
+    pub fn get_by_id(&self, peer_id: &PeerId) -> Option<Arc<Stats>> {
+        self.0.read().peer_ids.get(peer_id).map(Arc::clone)
+    }
+
+    /// Removes an address to the list of known peers; the stats remain available via the
+    /// associated [`PeerId`], if there is one.
+    pub fn remove(&self, addr: &PeerAddr) -> Option<Arc<Stats>> {
+        self.0.write().addrs.remove(addr)
     }
 
-    /// Removes an address to the list of known peers.
-    pub fn remove(&self, addr: SocketAddr) -> Option<Arc<Stats>> {
-        self.0.write().remove(&addr)
+    /// Removes a [`PeerId`] from the list of known peers, along with all the addresses associated
+    /// with it.
+    pub fn remove_by_id(&self, peer_id: &PeerId) -> Option<Arc<Stats>> {
+        let mut inner = self.0.write();
+        let stats = inner.peer_ids.remove(peer_id)?;
+        inner.addrs.retain(|_, s| !Arc::ptr_eq(s, &stats));
+
+        Some(stats)
     }
 
     /// Returns the list of all known peers and their stats.
-    pub fn snapshot(&self) -> HashMap<SocketAddr, Arc<Stats>> {
-        self.0.read().clone()
+    pub fn snapshot(&self) -> HashMap<PeerAddr, Arc<Stats>> {
+        self.0.read().addrs.clone()
+    }
+
+    /// Returns the addresses of the known peers that haven't been seen for at least the given duration
+    /// (including the ones that were never seen), e.g. in order to evict them.
+    pub fn idle(&self, max_idle: Duration) -> Vec<PeerAddr> {
+        self.0
+            .read()
+            .addrs
+            .iter()
+            .filter(|(_, stats)| stats.idle_for().map(|idle| idle >= max_idle) != Some(false))
+            .map(|(addr, _)| addr.clone())
+            .collect()
+    }
+
+    /// Returns a point-in-time copy of the stats and scores of all known peers; unlike [`KnownPeers::snapshot`],
+    /// its contents don't change afterwards.
+    pub fn stats_snapshot(&self) -> KnownPeersSnapshot {
+        let inner = self.0.read();
+
+        KnownPeersSnapshot {
+            addrs: inner
+                .addrs
+                .iter()
+                .map(|(addr, stats)| (addr.clone(), stats.snapshot()))
+                .collect(),
+            peer_ids: inner
+                .peer_ids
+                .iter()
+                .map(|(peer_id, stats)| (peer_id.clone(), stats.snapshot()))
+                .collect(),
+            scores: inner
+                .addrs
+                .iter()
+                .map(|(addr, stats)| (addr.clone(), stats.score(inner.score_half_life)))
+                .collect(),
+        }
+    }
+
+    /// Returns the list of all known peers with a [`PeerId`] and their stats.
+    pub fn snapshot_by_id(&self) -> HashMap<PeerId, Arc<Stats>> {
+        self.0.read().peer_ids.clone()
     }
 
     /// Registers a submission of a message to the given address.
-    pub fn register_sent_message(&self, to: SocketAddr, size: usize) {
-        if let Some(stats) = self.0.read().get(&to) {
+    pub fn register_sent_message(&self, to: &PeerAddr, size: usize) {
+        if let Some(stats) = self.0.read().addrs.get(to) {
             stats.register_sent_message(size);
         }
     }
 
     /// Registers a receipt of a message to the given address.
-    pub fn register_received_message(&self, from: SocketAddr, size: usize) {
-        if let Some(stats) = self.0.read().get(&from) {
+    pub fn register_received_message(&self, from: &PeerAddr, size: usize) {
+        if let Some(stats) = self.0.read().addrs.get(from) {
             stats.register_received_message(size);
         }
     }
 
+    /// Registers the time between queueing a message for the given address and writing it to the stream.
+    pub fn register_write_latency(&self, to: &PeerAddr, latency: Duration) {
+        if let Some(stats) = self.0.read().addrs.get(to) {
+            stats.register_write_latency(latency);
+        }
+    }
+
     /// Registers a failure associated with the given address.
-    pub fn register_failure(&self, addr: SocketAddr) {
-        if let Some(stats) = self.0.read().get(&addr) {
+    pub fn register_failure(&self, addr: &PeerAddr) {
+        if let Some(stats) = self.0.read().addrs.get(addr) {
             stats.register_failure();
         }
     }
+
+    /// Registers that the peer with the given address was seen just now.
+    pub fn register_seen(&self, addr: &PeerAddr) {
+        if let Some(stats) = self.0.read().addrs.get(addr) {
+            stats.register_seen();
+        }
+    }
+
+    /// Registers an established connection with the given address.
+    pub fn register_connection(&self, addr: &PeerAddr) {
+        if let Some(stats) = self.0.read().addrs.get(addr) {
+            stats.register_connection();
+        }
+    }
+
+    /// Registers the end of the connection with the given address.
+    pub fn register_disconnection(&self, addr: &PeerAddr) {
+        if let Some(stats) = self.0.read().addrs.get(addr) {
+            stats.register_disconnection();
+        }
+    }
+
+    /// Registers an attempt to connect to the given address.
+    pub fn register_connection_attempt(&self, addr: &PeerAddr) {
+        if let Some(stats) = self.0.read().addrs.get(addr) {
+            stats.register_connection_attempt();
+        }
+    }
+
+    /// Adjusts the reputation score of the given address by the given amount; returns the updated score,
+    /// or `None` if the address is unknown.
+    ///
+    /// note: It doesn't enforce the thresholds of the [`ReputationPolicy`]; [`Node::adjust_score`] does.
+    pub fn adjust_score(&self, addr: &PeerAddr, delta: f64) -> Option<f64> {
+        let inner = self.0.read();
+        let stats = inner.addrs.get(addr)?;
+
+        Some(stats.adjust_score(delta, inner.score_half_life))
+    }
+
+    /// Returns the reputation score of the given address.
+    pub fn score(&self, addr: &PeerAddr) -> Option<f64> {
+        let inner = self.0.read();
+        let stats = inner.addrs.get(addr)?;
+
+        Some(stats.score(inner.score_half_life))
+    }
+
+    /// Returns the reputation score of the peer with the given [`PeerId`].
+    pub fn score_by_id(&self, peer_id: &PeerId) -> Option<f64> {
+        let inner = self.0.read();
+        let stats = inner.peer_ids.get(peer_id)?;
+
+        Some(stats.score(inner.score_half_life))
+    }
+
+    /// Returns the addresses of all known peers along with their reputation scores, sorted from the
+    /// highest score to the lowest one.
+    pub fn scores(&self) -> Vec<(PeerAddr, f64)> {
+        let inner = self.0.read();
+        let mut scores = inner
+            .addrs
+            .iter()
+            .map(|(addr, stats)| (addr.clone(), stats.score(inner.score_half_life)))
+            .collect::<Vec<_>>();
+        scores.sort_unstable_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+
+        scores
+    }
+
+    /// Records the given address as a persistent peer (see [`Node::add_persistent_peer`]).
+    pub(crate) fn add_persistent_peer(&self, addr: &PeerAddr, policy: ReconnectPolicy) {
+        self.0.write().persistent_peers.insert(addr.clone(), policy);
+    }
+
+    /// Stops recording the given address as a persistent peer (see [`Node::remove_persistent_peer`]); returns
+    /// `false` if it wasn't recorded as one.
+    pub(crate) fn remove_persistent_peer(&self, addr: &PeerAddr) -> bool {
+        self.0.write().persistent_peers.remove(addr).is_some()
+    }
+
+    /// Returns the persistent peers along with their [`ReconnectPolicy`]s, including the ones loaded from a file;
+    /// the latter can be redialed via [`Node::restore_persistent_peers`].
+    pub fn persistent_peers(&self) -> HashMap<PeerAddr, ReconnectPolicy> {
+        self.0.read().persistent_peers.clone()
+    }
+
+    /// Bans the given address or IP for the given duration; banned peers can't connect to the node, and the
+    /// node doesn't connect to them. Banning an already banned target overrides the previous duration, and
+    /// the expired bans are removed whenever a new one is inserted.
+    ///
+    /// note: It doesn't disconnect from the matching peers; [`Node::ban`] does.
+    pub fn ban<T: Into<BanTarget>>(&self, target: T, duration: Duration) {
+        let now = Instant::now();
+        // an expiry time that can't be represented means that the ban is permanent
+        let expiry = now.checked_add(duration);
+        let mut inner = self.0.write();
+        // clean up the expired bans, so that they don't accumulate
+        inner.remove_expired_bans(now);
+        inner.bans.insert(target.into(), expiry);
+    }
+
+    /// Lifts the ban on the given address or IP; returns `false` if it wasn't banned.
+    pub fn unban<T: Into<BanTarget>>(&self, target: T) -> bool {
+        self.0.write().bans.remove(&target.into()).is_some()
+    }
+
+    /// Checks whether the given address is banned, either directly or via its IP.
+    pub fn is_banned(&self, addr: &PeerAddr) -> bool {
+        let now = Instant::now();
+        self.0.read().bans.iter().any(|(target, expiry)| {
+            target.matches(addr) && expiry.map(|expiry| expiry > now) != Some(false)
+        })
+    }
+
+    /// Returns the list of active bans along with their expiry times; `None` means that the ban is permanent.
+    pub fn bans(&self) -> HashMap<BanTarget, Option<Instant>> {
+        let mut inner = self.0.write();
+        // clean up the expired bans
+        inner.remove_expired_bans(Instant::now());
+
+        inner.bans.clone()
+    }
+
+    /// Saves the known peers (their addresses, [`PeerId`]s, stats, the active bans, and the persistent peers) to
+    /// the given file in JSON; the file is replaced atomically, and concurrent saves are performed one at a time.
+    /// See [`KnownPeers::save_to`] for details.
+    #[cfg(feature = "serde")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let _saving = self.1.lock();
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        let mut file = BufWriter::new(File::create(&tmp_path)?);
+        self.save_to(&mut file)?;
+        file.into_inner()?.sync_all()?;
+
+        fs::rename(tmp_path, path)
+    }
+
+    /// Loads the known peers from the given file (see [`KnownPeers::save`] and [`KnownPeers::load_from`]).
+    #[cfg(feature = "serde")]
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.load_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the known peers to the given writer as a versioned JSON object. The stats shared by the addresses
+    /// and [`PeerId`]s of a peer are saved once and referred to by their index; the times are saved as wall-clock
+    /// time, and the reputation scores are saved with any decay applied.
+    #[cfg(feature = "serde")]
+    pub fn save_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        // prepare the contents under the lock, but write them outside of it
+        let saved = {
+            let inner = self.0.read();
+            let mut saved = SavedKnownPeers {
+                version: FILE_VERSION,
+                stats: Vec::new(),
+                addrs: Vec::with_capacity(inner.addrs.len()),
+                peer_ids: Vec::with_capacity(inner.peer_ids.len()),
+                bans: Vec::with_capacity(inner.bans.len()),
+                persistent_peers: inner
+                    .persistent_peers
+                    .iter()
+                    .map(|(addr, policy)| (addr.clone(), *policy))
+                    .collect(),
+            };
+            let mut indices: HashMap<*const Stats, usize> = HashMap::new();
+            let mut index_of = |stats: &Arc<Stats>, saved_stats: &mut Vec<StatsRecord>| -> usize {
+                *indices.entry(Arc::as_ptr(stats)).or_insert_with(|| {
+                    saved_stats.push(stats.to_record(inner.score_half_life));
+                    saved_stats.len() - 1
+                })
+            };
+
+            for (addr, stats) in &inner.addrs {
+                let index = index_of(stats, &mut saved.stats);
+                saved.addrs.push((addr.clone(), index));
+            }
+
+            for (peer_id, stats) in &inner.peer_ids {
+                let index = index_of(stats, &mut saved.stats);
+                saved.peer_ids.push((peer_id.clone(), index));
+            }
+
+            let now = Instant::now();
+            let wall_now = SystemTime::now();
+            for (target, expiry) in &inner.bans {
+                let expiry = match expiry {
+                    Some(expiry) => match expiry.checked_duration_since(now) {
+                        Some(remaining) => Some(unix_millis(wall_now + remaining)),
+                        None => continue, // already expired
+                    },
+                    None => None,
+                };
+                saved.bans.push((target.clone(), expiry));
+            }
+
+            saved
+        };
+
+        serde_json::to_writer(&mut writer, &saved)?;
+        writer.flush()
+    }
+
+    /// Reads the known peers written by [`KnownPeers::save_to`] from the given reader and adds them to the
+    /// existing ones; the entries that are already present are replaced, and the expired bans are skipped.
+    /// Returns an [`io::ErrorKind::InvalidData`] error describing the problem (without adding anything) if the
+    /// contents are malformed or saved in an unsupported version of the format.
+    #[cfg(feature = "serde")]
+    pub fn load_from<R: Read>(&self, reader: R) -> io::Result<()> {
+        fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, error)
+        }
+
+        let saved: SavedKnownPeers = serde_json::from_reader(reader).map_err(invalid)?;
+        if saved.version != FILE_VERSION {
+            return Err(invalid(format!(
+                "unsupported known peers file version: {}",
+                saved.version
+            )));
+        }
+
+        let stats = saved
+            .stats
+            .into_iter()
+            .map(|record| Arc::new(Stats::from_record(record)))
+            .collect::<Vec<_>>();
+        let get_stats = |index: usize| {
+            stats
+                .get(index)
+                .cloned()
+                .ok_or_else(|| invalid(format!("missing stats with index {}", index)))
+        };
+        let addrs = saved
+            .addrs
+            .into_iter()
+            .map(|(addr, index)| Ok((addr, get_stats(index)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let peer_ids = saved
+            .peer_ids
+            .into_iter()
+            .map(|(peer_id, index)| Ok((peer_id, get_stats(index)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+        let bans = saved
+            .bans
+            .into_iter()
+            .filter_map(|(target, expiry)| match expiry {
+                None => Some((target, None)),
+                Some(expiry) => {
+                    match (UNIX_EPOCH + Duration::from_millis(expiry)).duration_since(wall_now) {
+                        Ok(remaining) => Some((target, now.checked_add(remaining))),
+                        Err(_) => None, // already expired
+                    }
+                }
+            });
+
+        let mut inner = self.0.write();
+        inner.addrs.extend(addrs);
+        inner.peer_ids.extend(peer_ids);
+        inner.bans.extend(bans);
+        inner.persistent_peers.extend(saved.persistent_peers);
+
+        Ok(())
+    }
 }
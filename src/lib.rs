@@ -7,21 +7,43 @@
 //! - benchmarking and stress-testing P2P nodes (or other network entities)
 //! - substituting other, "heavier" nodes in local network tests
 
+mod access_control;
 mod config;
+mod events;
+mod failure_policy;
 mod known_peers;
 mod node;
+mod peer_addr;
+mod peer_id;
+mod reconnect;
+mod reputation;
 mod stats;
 mod topology;
 
 pub mod connections;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "noise")]
+pub mod noise;
 pub mod protocols;
+pub mod transport;
 
-pub use config::Config;
-pub use connections::{Connection, ConnectionSide};
-pub use known_peers::KnownPeers;
+pub use access_control::{AccessControl, Cidr};
+pub use config::{Config, QueuePolicy};
+pub use connections::{
+    Connection, ConnectionId, ConnectionInfo, ConnectionSide, DisconnectReason, Extensions,
+};
+pub use events::NodeEvent;
+pub use failure_policy::FailurePolicy;
+pub use known_peers::{BanTarget, KnownPeers, KnownPeersSnapshot};
 pub use node::Node;
-pub use stats::Stats;
+pub use peer_addr::PeerAddr;
+pub use peer_id::PeerId;
+pub use reconnect::ReconnectPolicy;
+pub use reputation::ReputationPolicy;
+pub use stats::{Histogram, Rates, Stats, StatsSnapshot};
 pub use topology::{connect_nodes, Topology};
+pub use transport::Transport;
 
 /// A trait for objects containing a [`Node`]; it is required to implement protocols.
 pub trait Pea2Pea {
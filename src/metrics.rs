@@ -0,0 +1,391 @@
+//! Rendering of the node's statistics in the [OpenMetrics](https://openmetrics.io) text format, along
+//! with an optional HTTP endpoint serving them (see [`Config::metrics_addr`]).
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tracing::*;
+
+use crate::{Histogram, Node, PeerAddr, Stats};
+
+#[cfg(doc)]
+use crate::Config;
+
+use std::{
+    fmt::{self, Write},
+    io,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// The content type of the rendered metrics.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// The path the metrics are served at by the HTTP endpoint.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Renders the node's [`Stats`], the stats of its known peers, its connection counts and the lengths
+/// of its connections' message queues in the OpenMetrics text format. All the metrics are prefixed with
+/// `pea2pea_`, and labeled with the name of the node; the per-peer ones are also labeled with the peers'
+/// addresses.
+pub fn render(node: &Node) -> String {
+    let mut enc = Encoder::default();
+    let node_name = node.name();
+    let node_labels = [("node", node_name)];
+
+    enc.family(
+        "pea2pea_connected",
+        "gauge",
+        "The number of active connections.",
+    );
+    enc.sample("pea2pea_connected", &node_labels, node.num_connected());
+    enc.family(
+        "pea2pea_connecting",
+        "gauge",
+        "The number of connections that are being set up.",
+    );
+    enc.sample("pea2pea_connecting", &node_labels, node.num_connecting());
+
+    // node-level stats
+    enc.stats("pea2pea_node", &[(&node_labels[..], node.stats())]);
+
+    // per-peer stats
+    let peers = node
+        .known_peers()
+        .snapshot()
+        .into_iter()
+        .map(|(addr, stats)| {
+            let addr_str = addr.to_string();
+            (addr, addr_str, stats)
+        })
+        .collect::<Vec<_>>();
+    let peer_labels = peers
+        .iter()
+        .map(|(_, addr, _)| [("node", node_name), ("peer", addr.as_str())])
+        .collect::<Vec<_>>();
+    let peer_stats = peer_labels
+        .iter()
+        .zip(&peers)
+        .map(|(labels, (_, _, stats))| (&labels[..], &**stats))
+        .collect::<Vec<_>>();
+    enc.stats("pea2pea_peer", &peer_stats);
+
+    // the scores and last-seen times are only meaningful for peers
+    enc.family(
+        "pea2pea_peer_score",
+        "gauge",
+        "The reputation score of the peer.",
+    );
+    for (labels, (addr, _, _)) in peer_labels.iter().zip(&peers) {
+        if let Some(score) = node.known_peers().score(addr) {
+            enc.sample("pea2pea_peer_score", labels, Float(score));
+        }
+    }
+    enc.family(
+        "pea2pea_peer_last_seen_timestamp_seconds",
+        "gauge",
+        "The Unix time the peer was last seen at.",
+    );
+    for (labels, (_, _, stats)) in peer_labels.iter().zip(&peers) {
+        if let Some(last_seen) = stats.last_seen() {
+            let secs = last_seen
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            enc.sample(
+                "pea2pea_peer_last_seen_timestamp_seconds",
+                labels,
+                Float(secs),
+            );
+        }
+    }
+
+    // queue lengths of the connected peers
+    let connected = node
+        .connected_addrs()
+        .into_iter()
+        .map(|addr| {
+            let addr_str = addr.to_string();
+            (addr, addr_str)
+        })
+        .collect::<Vec<_>>();
+    for (name, help, get_len) in [
+        (
+            "pea2pea_inbound_queue_length",
+            "The number of inbound messages waiting to be processed.",
+            Node::inbound_queue_len as fn(&Node, &PeerAddr) -> Option<usize>,
+        ),
+        (
+            "pea2pea_outbound_queue_length",
+            "The number of outbound messages waiting to be written.",
+            Node::outbound_queue_len,
+        ),
+    ] {
+        enc.family(name, "gauge", help);
+        for (addr, addr_str) in &connected {
+            if let Some(len) = get_len(node, addr) {
+                enc.sample(name, &[("node", node_name), ("peer", addr_str)], len);
+            }
+        }
+    }
+
+    enc.finish()
+}
+
+/// Builds the text representation of the metrics.
+#[derive(Default)]
+struct Encoder {
+    out: String,
+}
+
+/// A wrapper ensuring that floats are rendered in accordance with the OpenMetrics spec.
+struct Float(f64);
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_nan() {
+            write!(f, "NaN")
+        } else if self.0.is_infinite() {
+            write!(f, "{}Inf", if self.0 > 0.0 { "+" } else { "-" })
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+// note: writing to a String can't fail, so the results are ignored below
+impl Encoder {
+    /// Writes the metadata of a metric family.
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+    }
+
+    /// Writes a single sample.
+    fn sample<V: fmt::Display>(&mut self, name: &str, labels: &[(&str, &str)], value: V) {
+        let _ = write!(self.out, "{}", name);
+        if !labels.is_empty() {
+            self.out.push('{');
+            for (i, (key, value)) in labels.iter().enumerate() {
+                if i != 0 {
+                    self.out.push(',');
+                }
+                let _ = write!(self.out, "{}=\"", key);
+                for c in value.chars() {
+                    match c {
+                        '\\' => self.out.push_str("\\\\"),
+                        '"' => self.out.push_str("\\\""),
+                        '\n' => self.out.push_str("\\n"),
+                        c => self.out.push(c),
+                    }
+                }
+                self.out.push('"');
+            }
+            self.out.push('}');
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+
+    /// Writes the metric families representing the given [`Stats`] objects, each with its own labels.
+    fn stats(&mut self, prefix: &str, stats: &[(&[(&str, &str)], &Stats)]) {
+        let snapshots = stats
+            .iter()
+            .map(|(_, stats)| stats.snapshot())
+            .collect::<Vec<_>>();
+
+        type Counter = fn(&Stats) -> u64;
+        let counters: [(&str, &str, Counter); 8] = [
+            ("messages_sent", "The number of sent messages.", |s| {
+                s.sent().0
+            }),
+            ("bytes_sent", "The number of sent bytes.", |s| s.sent().1),
+            (
+                "messages_received",
+                "The number of received messages.",
+                |s| s.received().0,
+            ),
+            ("bytes_received", "The number of received bytes.", |s| {
+                s.received().1
+            }),
+            ("failures", "The number of failures.", Stats::failures),
+            (
+                "connection_attempts",
+                "The number of connection attempts.",
+                Stats::connection_attempts,
+            ),
+            (
+                "rejections",
+                "The number of rejected connections.",
+                Stats::rejections,
+            ),
+            (
+                "connections",
+                "The number of established connections.",
+                Stats::connections,
+            ),
+        ];
+        for (name, help, get) in counters {
+            let name = format!("{}_{}", prefix, name);
+            self.family(&name, "counter", help);
+            let sample_name = format!("{}_total", name);
+            for (labels, stats) in stats {
+                self.sample(&sample_name, labels, get(stats));
+            }
+        }
+
+        for (kind, help) in [
+            ("message", "The moving average of messages per second."),
+            ("byte", "The moving average of bytes per second."),
+        ] {
+            let name = format!("{}_{}_rate", prefix, kind);
+            self.family(&name, "gauge", help);
+            for ((labels, _), snapshot) in stats.iter().zip(&snapshots) {
+                for (direction, rates) in [
+                    ("sent", &snapshot.sent_rates),
+                    ("received", &snapshot.received_rates),
+                ] {
+                    let (rate_1s, rate_1m) = if kind == "message" {
+                        (rates.msgs_1s, rates.msgs_1m)
+                    } else {
+                        (rates.bytes_1s, rates.bytes_1m)
+                    };
+                    for (window, rate) in [("1s", rate_1s), ("1m", rate_1m)] {
+                        let mut labels = labels.to_vec();
+                        labels.push(("direction", direction));
+                        labels.push(("window", window));
+                        self.sample(&name, &labels, Float(rate));
+                    }
+                }
+            }
+        }
+
+        let name = format!("{}_message_size_bytes", prefix);
+        self.family(&name, "histogram", "The sizes of messages.");
+        for ((labels, _), snapshot) in stats.iter().zip(&snapshots) {
+            for (direction, histogram) in [
+                ("sent", &snapshot.sent_sizes),
+                ("received", &snapshot.received_sizes),
+            ] {
+                let mut labels = labels.to_vec();
+                labels.push(("direction", direction));
+                self.histogram(&name, &labels, histogram);
+            }
+        }
+
+        let name = format!("{}_write_latency_microseconds", prefix);
+        self.family(
+            &name,
+            "histogram",
+            "The times between queueing messages and writing them.",
+        );
+        for ((labels, _), snapshot) in stats.iter().zip(&snapshots) {
+            self.histogram(&name, labels, &snapshot.write_latencies);
+        }
+    }
+
+    /// Writes the samples of the given histogram; only the buckets up to the highest non-empty one are
+    /// included, as the remaining ones would all have the same cumulative count.
+    fn histogram(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        let bucket_name = format!("{}_bucket", name);
+        let last = histogram.buckets.iter().rposition(|count| *count != 0);
+
+        let mut cumulative = 0;
+        for (i, count) in histogram.buckets.iter().enumerate() {
+            if Some(i) > last {
+                break;
+            }
+            cumulative += count;
+            let le = Histogram::bucket_upper_bound(i).to_string();
+            let mut labels = labels.to_vec();
+            labels.push(("le", &le));
+            self.sample(&bucket_name, &labels, cumulative);
+        }
+
+        let mut labels_inf = labels.to_vec();
+        labels_inf.push(("le", "+Inf"));
+        self.sample(&bucket_name, &labels_inf, histogram.count);
+        self.sample(&format!("{}_count", name), labels, histogram.count);
+        self.sample(&format!("{}_sum", name), labels, histogram.sum);
+    }
+
+    /// Concludes the exposition.
+    fn finish(mut self) -> String {
+        self.out.push_str("# EOF\n");
+        self.out
+    }
+}
+
+/// The maximum size of an HTTP request accepted by the metrics endpoint.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// The time the metrics endpoint waits for a complete request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the node's metrics over HTTP using the given listener, until the returned task is aborted.
+pub(crate) async fn serve(node: Node, listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                let node = node.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = respond(&node, stream).await {
+                        debug!(parent: node.span(), "couldn't serve the metrics to {}: {}", addr, e);
+                    }
+                });
+            }
+            Err(e) => {
+                error!(parent: node.span(), "couldn't accept a metrics request: {}", e);
+            }
+        }
+    }
+}
+
+/// Responds to a single HTTP request.
+async fn respond(node: &Node, mut stream: TcpStream) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    // read until the end of the headers; the body (if any) is irrelevant
+    timeout(REQUEST_TIMEOUT, async {
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let len = stream.read(&mut buf).await?;
+            if len == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            request.extend_from_slice(&buf[..len]);
+            if request.len() > MAX_REQUEST_SIZE {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+
+    let request_line = request.split(|b| *b == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|b| *b == b' ');
+    let (method, path) = (parts.next(), parts.next());
+
+    let (status, content_type, body) = match (method, path) {
+        (Some(b"GET"), Some(path)) if path == METRICS_PATH.as_bytes() => {
+            ("200 OK", CONTENT_TYPE, render(node))
+        }
+        (Some(b"GET"), _) => ("404 Not Found", "text/plain", "not found\n".to_owned()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_owned(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
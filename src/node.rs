@@ -1,26 +1,36 @@
 use crate::{
-    connections::{Connection, ConnectionSide, Connections},
+    connections::{
+        Connection, ConnectionId, ConnectionInfo, ConnectionSide, Connections, PeerSlot,
+    },
+    failure_policy::FailureLog,
     protocols::Protocols,
-    Config, KnownPeers, Stats,
+    reconnect::{maintain_connection, ReconnectPolicy},
+    transport::{TcpTransport, Transport, TransportStream},
+    AccessControl, BanTarget, Config, DisconnectReason, KnownPeers, NodeEvent, PeerAddr, PeerId,
+    QueuePolicy, ReputationPolicy, Stats,
 };
 
 use parking_lot::Mutex;
 use tokio::{
-    net::{TcpListener, TcpStream},
-    sync::oneshot,
+    sync::{broadcast, mpsc, oneshot, OwnedMutexGuard},
     task::{self, JoinHandle},
+    time::timeout,
 };
 use tracing::*;
 
+#[cfg(feature = "serde")]
+use tokio::time::Instant;
+
 use std::{
-    collections::HashSet,
+    collections::HashMap,
     io,
     net::SocketAddr,
     ops::Deref,
     sync::{
-        atomic::{AtomicUsize, Ordering::*},
+        atomic::{AtomicU64, AtomicUsize, Ordering::*},
         Arc,
     },
+    time::Duration,
 };
 
 macro_rules! enable_protocol {
@@ -41,6 +51,24 @@ macro_rules! enable_protocol {
     };
 }
 
+/// Creates a span at the most verbose level that is enabled, so that it can be the parent of the events
+/// logged at any level; the levels are checked first, so that only a single span is created.
+macro_rules! enabled_span {
+    ($($args:tt)+) => {
+        if span_enabled!(Level::TRACE) {
+            trace_span!($($args)+)
+        } else if span_enabled!(Level::DEBUG) {
+            debug_span!($($args)+)
+        } else if span_enabled!(Level::INFO) {
+            info_span!($($args)+)
+        } else if span_enabled!(Level::WARN) {
+            warn_span!($($args)+)
+        } else {
+            error_span!($($args)+)
+        }
+    };
+}
+
 // A seuential numeric identifier assigned to `Node`s that were not provided with a name.
 static SEQUENTIAL_NODE_ID: AtomicUsize = AtomicUsize::new(0);
 
@@ -63,17 +91,32 @@ pub struct InnerNode {
     /// The node's configuration.
     config: Config,
     /// The node's listening address.
-    listening_addr: Option<SocketAddr>,
+    listening_addr: Option<PeerAddr>,
+    /// The transport used to establish connections.
+    transport: Box<dyn Transport>,
     /// Contains objects used by the protocols implemented by the node.
     pub(crate) protocols: Protocols,
     /// A list of connections that have not been finalized yet.
-    connecting: Mutex<HashSet<SocketAddr>>,
+    connecting: Mutex<Vec<(PeerAddr, ConnectionSide)>>,
     /// Contains objects related to the node's active connections.
     connections: Connections,
+    /// The identifier to be assigned to the next connection.
+    next_connection_id: AtomicU64,
     /// Collects statistics related to the node's peers.
     known_peers: KnownPeers,
+    /// Determines which addresses the node may be connected with.
+    access_control: AccessControl,
+    /// Keeps track of the peers' recent failures if there is a failure policy.
+    failure_log: FailureLog,
     /// Collects statistics related to the node itself.
     stats: Stats,
+    /// Broadcasts the node's events to the subscribers.
+    events: broadcast::Sender<NodeEvent>,
+    /// The tasks maintaining connections with persistent peers.
+    pub(crate) persistent_peers: Mutex<HashMap<PeerAddr, JoinHandle<()>>>,
+    /// The address the node's metrics are served at.
+    #[cfg(feature = "metrics")]
+    metrics_addr: Option<SocketAddr>,
     /// The node's tasks.
     pub(crate) tasks: Mutex<Vec<JoinHandle<()>>>,
 }
@@ -81,6 +124,15 @@ pub struct InnerNode {
 impl Node {
     /// Creates a new [`Node`] optionally using the given [`Config`].
     pub async fn new(config: Option<Config>) -> io::Result<Self> {
+        Self::with_transport(config, TcpTransport).await
+    }
+
+    /// Creates a new [`Node`] optionally using the given [`Config`]; all of its connections
+    /// are established via the provided [`Transport`].
+    pub async fn with_transport<T: Transport>(
+        config: Option<Config>,
+        transport: T,
+    ) -> io::Result<Self> {
         let mut config = config.unwrap_or_default();
 
         // if there is no pre-configured name, assign a sequential numeric identifier
@@ -91,17 +143,23 @@ impl Node {
         // create a tracing span containing the node's name
         let span = create_span(config.name.as_deref().unwrap());
 
+        // outbound messages are queued without blocking, so they can't wait for room in the queue
+        if config.outbound_queue_policy == QueuePolicy::Wait {
+            error!(parent: span.clone(), "QueuePolicy::Wait is not supported for outbound messages");
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
         // procure a listening address
         let listener = if let Some(listener_ip) = config.listener_ip {
             let listener = if let Some(port) = config.desired_listening_port {
-                let desired_listening_addr = SocketAddr::new(listener_ip, port);
-                match TcpListener::bind(desired_listening_addr).await {
+                let desired_listening_addr = SocketAddr::new(listener_ip, port).into();
+                match transport.listen(&desired_listening_addr).await {
                     Ok(listener) => listener,
                     Err(e) => {
                         if config.allow_random_port {
                             warn!(parent: span.clone(), "trying any port, the desired one is unavailable: {}", e);
-                            let random_available_addr = SocketAddr::new(listener_ip, 0);
-                            TcpListener::bind(random_available_addr).await?
+                            let random_available_addr = SocketAddr::new(listener_ip, 0).into();
+                            transport.listen(&random_available_addr).await?
                         } else {
                             error!(parent: span.clone(), "the desired port is unavailable: {}", e);
                             return Err(e);
@@ -109,8 +167,8 @@ impl Node {
                     }
                 }
             } else if config.allow_random_port {
-                let random_available_addr = SocketAddr::new(listener_ip, 0);
-                TcpListener::bind(random_available_addr).await?
+                let random_available_addr = SocketAddr::new(listener_ip, 0).into();
+                transport.listen(&random_available_addr).await?
             } else {
                 panic!(
                     "you must either provide a desired port or allow a random port to be chosen"
@@ -123,25 +181,61 @@ impl Node {
         };
 
         let listening_addr = if let Some(ref listener) = listener {
-            let ip = config.listener_ip.unwrap(); // safe; listener.is_some() => config.listener_ip.is_some()
-            let port = listener.local_addr()?.port(); // discover the port if it was unspecified
-            Some((ip, port).into())
+            // discover the port if it was unspecified
+            Some(listener.local_addr()?)
+        } else {
+            None
+        };
+
+        #[cfg(feature = "metrics")]
+        let metrics_listener = if let Some(addr) = config.metrics_addr {
+            Some(tokio::net::TcpListener::bind(addr).await?)
         } else {
             None
         };
 
+        let (events, _) = broadcast::channel(config.event_queue_depth.max(1));
+        let score_half_life = config
+            .reputation_policy
+            .and_then(|policy| policy.half_life_ms)
+            .map(Duration::from_millis);
+
         let node = Node(Arc::new(InnerNode {
             span,
             config,
             listening_addr,
+            transport: Box::new(transport),
             protocols: Default::default(),
             connecting: Default::default(),
             connections: Default::default(),
-            known_peers: Default::default(),
+            next_connection_id: Default::default(),
+            known_peers: KnownPeers::new(score_half_life),
+            access_control: Default::default(),
+            failure_log: Default::default(),
             stats: Default::default(),
+            events,
+            persistent_peers: Default::default(),
+            #[cfg(feature = "metrics")]
+            metrics_addr: match metrics_listener {
+                Some(ref listener) => Some(listener.local_addr()?),
+                None => None,
+            },
             tasks: Default::default(),
         }));
 
+        #[cfg(feature = "serde")]
+        if let Some(ref path) = node.config.known_peers_file {
+            match node.known_peers.load(path) {
+                Ok(()) => {
+                    debug!(parent: node.span(), "loaded the known peers from {}", path.display())
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    warn!(parent: node.span(), "couldn't load the known peers from {}: {}", path.display(), e)
+                }
+            }
+        }
+
         if let Some(listener) = listener {
             // Use a channel to know when the listening task is ready.
             let (tx, rx) = oneshot::channel::<()>();
@@ -156,34 +250,80 @@ impl Node {
                         Ok((stream, addr)) => {
                             debug!(parent: node_clone.span(), "tentatively accepted a connection from {}", addr);
 
-                            if !node_clone.can_add_connection() {
+                            if !node_clone.is_permitted(&addr) {
+                                debug!(parent: node_clone.span(), "rejecting the connection from {}; it is not permitted", addr);
+                                node_clone.stats().register_rejection();
+                                node_clone.emit_event(NodeEvent::Rejected { addr });
+                                continue;
+                            }
+
+                            if !node_clone.can_add_connection(&addr, ConnectionSide::Initiator) {
                                 debug!(parent: node_clone.span(), "rejecting the connection from {}", addr);
+                                node_clone.stats().register_rejection();
+                                node_clone.emit_event(NodeEvent::Rejected { addr });
                                 continue;
                             }
 
-                            node_clone.connecting.lock().insert(addr);
+                            node_clone
+                                .connecting
+                                .lock()
+                                .push((addr.clone(), ConnectionSide::Initiator));
+                            node_clone.emit_event(NodeEvent::Connecting {
+                                addr: addr.clone(),
+                                side: ConnectionSide::Initiator,
+                            });
 
                             let node_clone2 = node_clone.clone();
                             task::spawn(async move {
                                 if let Err(e) = node_clone2
-                                    .adapt_stream(stream, addr, ConnectionSide::Responder)
+                                    .adapt_stream(stream, &addr, ConnectionSide::Responder)
                                     .await
                                 {
-                                    node_clone2.connecting.lock().remove(&addr);
-                                    node_clone2.known_peers().register_failure(addr);
+                                    // the failure needs to be registered while the side is still known
+                                    node_clone2.register_failure(&addr);
+                                    node_clone2.remove_connecting(&addr, ConnectionSide::Initiator);
+                                    node_clone2.emit_event(NodeEvent::ConnectionFailed {
+                                        addr,
+                                        error: e.kind(),
+                                    });
                                     error!(parent: node_clone2.span(), "couldn't accept a connection: {}", e);
                                 }
                             });
                         }
                         Err(e) => {
                             error!(parent: node_clone.span(), "couldn't accept a connection: {}", e);
+                            node_clone.emit_event(NodeEvent::ListenerError { error: e.kind() });
                         }
                     }
                 }
             });
             node.tasks.lock().push(listening_task);
             let _ = rx.await;
-            debug!(parent: node.span(), "listening on {}", node.listening_addr.unwrap());
+            debug!(parent: node.span(), "listening on {}", node.listening_addr.as_ref().unwrap());
+        }
+
+        #[cfg(feature = "serde")]
+        if let (Some(_), Some(interval_ms)) = (
+            &node.config.known_peers_file,
+            node.config.known_peers_save_interval_ms,
+        ) {
+            let node_clone = node.clone();
+            let saving_task = tokio::spawn(async move {
+                let period = Duration::from_millis(interval_ms.max(1));
+                let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+                loop {
+                    interval.tick().await;
+                    node_clone.save_known_peers().await;
+                }
+            });
+            node.tasks.lock().push(saving_task);
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(listener) = metrics_listener {
+            debug!(parent: node.span(), "serving metrics at {}", node.metrics_addr.unwrap());
+            let metrics_task = tokio::spawn(crate::metrics::serve(node.clone(), listener));
+            node.tasks.lock().push(metrics_task);
         }
 
         debug!(parent: node.span(), "the node is ready");
@@ -216,43 +356,167 @@ impl Node {
         &self.span
     }
 
+    /// Returns the tracing [`Span`] of the connection with the provided address, or the node's span if there
+    /// is no such connection; it can be used as the explicit parent of logs related to the connection.
+    ///
+    /// note: It involves a lookup, so on hot paths it should be passed directly as the `parent` of a log
+    /// (e.g. `trace!(parent: &node.connection_span(addr), ...)`), which only evaluates it if the log is enabled.
+    pub fn connection_span(&self, addr: &PeerAddr) -> Span {
+        self.connections
+            .span(addr)
+            .unwrap_or_else(|| self.span.clone())
+    }
+
     /// Returns the node's listening address; returns an error if the node was configured
     /// to not listen for inbound connections.
-    pub fn listening_addr(&self) -> io::Result<SocketAddr> {
+    pub fn listening_addr(&self) -> io::Result<PeerAddr> {
         self.listening_addr
+            .clone()
+            .ok_or_else(|| io::ErrorKind::AddrNotAvailable.into())
+    }
+
+    /// Returns the address the node's metrics are served at (see [`Config::metrics_addr`]); returns an error
+    /// if the node was configured to not serve them.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_addr(&self) -> io::Result<SocketAddr> {
+        self.metrics_addr
             .ok_or_else(|| io::ErrorKind::AddrNotAvailable.into())
     }
 
+    /// Subscribes to the node's [`NodeEvent`]s; only the events emitted after the subscription are received.
+    /// Up to [`Config::event_queue_depth`] events are buffered for each subscriber; if it falls behind, the
+    /// oldest events are lost (see [`broadcast::error::RecvError::Lagged`]).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<NodeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Sends the given event to all the subscribers, if there are any.
+    pub(crate) fn emit_event(&self, event: NodeEvent) {
+        let _ = self.events.send(event);
+    }
+
     async fn enable_protocols(&self, conn: Connection) -> io::Result<Connection> {
         let conn = enable_protocol!(handshake_handler, self, conn);
-        let conn = enable_protocol!(reading_handler, self, conn);
-        let conn = enable_protocol!(writing_handler, self, conn);
 
-        Ok(conn)
+        // the handshake might have identified the peer; the number of connections per peer can be limited
+        if let Some(ref peer_id) = conn.peer_id {
+            conn.span().record("peer_id", field::display(peer_id));
+
+            // the slot is reserved right away, so that the connections with the peer that are being set up
+            // concurrently (e.g. when both sides dial each other at the same time) are accounted for
+            let replaced = self
+                .connections
+                .reserve(
+                    peer_id,
+                    PeerSlot::new(&conn),
+                    self.config.max_connections_per_peer,
+                    |new, existing| self.is_preferred_connection(peer_id, new, existing),
+                )
+                .map_err(|e| {
+                    error!(parent: conn.span(), "{:?} is already connected", peer_id);
+                    e
+                })?;
+
+            if let Some(id) = replaced {
+                debug!(parent: conn.span(), "{:?} is already connected via connection {}; replacing it", peer_id, id);
+                self.close_connection(id, DisconnectReason::Duplicate).await;
+            }
+            self.known_peers.add_peer_id(&conn.addr, peer_id.clone());
+        }
+
+        let id = conn.id();
+        let peer_id = conn.peer_id.clone();
+        let ret = async {
+            let conn = enable_protocol!(reading_handler, self, conn);
+            let conn = enable_protocol!(writing_handler, self, conn);
+
+            Ok(conn)
+        }
+        .await;
+
+        if ret.is_err() {
+            self.release_connection(id, peer_id.as_ref());
+        }
+
+        ret
+    }
+
+    /// Releases the resources associated with a connection that couldn't be established.
+    fn release_connection(&self, id: ConnectionId, peer_id: Option<&PeerId>) {
+        if let Some(peer_id) = peer_id {
+            self.connections.release(peer_id, id);
+        }
+        self.remove_protocol_queues(id);
+    }
+
+    /// Drops the message queues associated with the given connection by the enabled protocols.
+    fn remove_protocol_queues(&self, id: ConnectionId) {
+        // drop the associated outbound message sender if Writing is enabled
+        if let Some(handler) = self.protocols.writing_handler.get() {
+            handler.senders.write().remove(&id);
+        }
+
+        // drop the associated inbound queue if Reading is enabled
+        if let Some(handler) = self.protocols.reading_handler.get() {
+            handler.queues.write().remove(&id);
+            handler.processing.write().remove(&id);
+        }
+    }
+
+    /// Removes a connection that is no longer being set up from the list of pending ones.
+    fn remove_connecting(&self, addr: &PeerAddr, side: ConnectionSide) {
+        let mut connecting = self.connecting.lock();
+        if let Some(idx) = connecting.iter().position(|(a, s)| a == addr && *s == side) {
+            connecting.swap_remove(idx);
+        }
     }
 
     /// Prepares the freshly acquired connection to handle the protocols the Node implements.
     async fn adapt_stream(
         &self,
-        stream: TcpStream,
-        peer_addr: SocketAddr,
+        stream: Box<dyn TransportStream>,
+        peer_addr: &PeerAddr,
         own_side: ConnectionSide,
     ) -> io::Result<()> {
         self.known_peers.add(peer_addr);
 
-        // register the port seen by the peer
+        // create a tracing span dedicated to the connection
+        let id = ConnectionId(self.next_connection_id.fetch_add(1, Relaxed));
+        let span = create_connection_span(self.span(), peer_addr, !own_side, id);
+
+        // register the address seen by the peer
+        let local_addr = stream.local_addr().ok();
         if let ConnectionSide::Initiator = own_side {
-            if let Ok(addr) = stream.local_addr() {
+            if let Some(ref addr) = local_addr {
                 debug!(
-                    parent: self.span(), "establishing connection with {}; the peer is connected on port {}",
-                    peer_addr, addr.port()
+                    parent: &span, "establishing connection with {}; the peer is connected from {}",
+                    peer_addr, addr
                 );
             } else {
-                warn!(parent: self.span(), "couldn't determine the peer's port");
+                debug!(parent: &span, "couldn't determine the address the peer is connected from");
             }
         }
 
-        let connection = Connection::new(peer_addr, stream, !own_side);
+        // prepare the stream for use (e.g. secure it), if the transport requires it
+        let stream = match timeout(
+            Duration::from_millis(self.config.max_handshake_time_ms),
+            self.transport.upgrade(stream, peer_addr, own_side),
+        )
+        .await
+        {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                error!(parent: &span, "couldn't upgrade the stream with {}: {}", peer_addr, e);
+                return Err(e);
+            }
+            Err(_) => {
+                error!(parent: &span, "upgrading the stream with {} timed out", peer_addr);
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+        };
+
+        let connection =
+            Connection::new(peer_addr.clone(), local_addr, stream, !own_side, id, span);
 
         // enact the enabled protocols
         let mut connection = self.enable_protocols(connection).await?;
@@ -260,42 +524,94 @@ impl Node {
         // the protocols are responsible for doing reads and writes; ensure that the Connection object
         // is not capable of performing them if the protocols haven't been enabled.
         connection.reader = None;
-        connection.writer = None;
+        connection.disable_writer();
 
-        self.connections.add(connection);
-        self.connecting.lock().remove(&peer_addr);
+        let side = connection.side;
+        let span = connection.span().clone();
+        if let Some(connection) = self.connections.add(connection) {
+            error!(parent: &span, "the connection with {} was replaced while being set up", peer_addr);
+            for task in &connection.tasks {
+                task.abort();
+            }
+            self.remove_protocol_queues(id);
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        self.known_peers.register_connection(peer_addr);
+        self.remove_connecting(peer_addr, side);
+        self.emit_event(NodeEvent::Connected {
+            addr: peer_addr.clone(),
+            side,
+        });
 
         Ok(())
     }
 
-    /// Connects to the provided `SocketAddr`.
-    pub async fn connect(&self, addr: SocketAddr) -> io::Result<()> {
-        if let Ok(listening_addr) = self.listening_addr() {
-            if addr == listening_addr
-                || addr.ip().is_loopback() && addr.port() == listening_addr.port()
-            {
+    /// Connects to the provided address.
+    pub async fn connect(&self, addr: &PeerAddr) -> io::Result<()> {
+        if let Some(ref listening_addr) = self.listening_addr {
+            let is_own_loopback = match (addr, listening_addr) {
+                (PeerAddr::Ip(addr), PeerAddr::Ip(own)) => {
+                    addr.ip().is_loopback() && addr.port() == own.port()
+                }
+                _ => false,
+            };
+            if addr == listening_addr || is_own_loopback {
                 error!(parent: self.span(), "can't connect to node's own listening address ({})", addr);
                 return Err(io::ErrorKind::AddrInUse.into());
             }
         }
 
-        if !self.can_add_connection() {
+        if !self.is_permitted(addr) {
+            error!(parent: self.span(), "refusing to connect to {}; it is not permitted", addr);
+            self.stats().register_rejection();
+            self.emit_event(NodeEvent::Rejected { addr: addr.clone() });
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+
+        if !self.can_add_connection(addr, ConnectionSide::Responder) {
             error!(parent: self.span(), "too many connections; refusing to connect to {}", addr);
+            self.stats().register_rejection();
+            self.emit_event(NodeEvent::Rejected { addr: addr.clone() });
             return Err(io::ErrorKind::PermissionDenied.into());
         }
 
-        if self.connections.is_connected(addr) {
+        // unless multiple connections per peer are allowed, an address is only connected to once
+        let single_connection = self.config.max_connections_per_peer == Some(1);
+        if single_connection && self.connections.is_connected(addr) {
             warn!(parent: self.span(), "already connected to {}", addr);
             return Err(io::ErrorKind::AlreadyExists.into());
         }
 
-        if !self.connecting.lock().insert(addr) {
-            warn!(parent: self.span(), "already connecting to {}", addr);
-            return Err(io::ErrorKind::AlreadyExists.into());
+        {
+            let mut connecting = self.connecting.lock();
+            if single_connection && connecting.iter().any(|(a, _)| a == addr) {
+                warn!(parent: self.span(), "already connecting to {}", addr);
+                return Err(io::ErrorKind::AlreadyExists.into());
+            }
+            connecting.push((addr.clone(), ConnectionSide::Responder));
         }
 
-        let stream = TcpStream::connect(addr).await.map_err(|e| {
-            self.connecting.lock().remove(&addr);
+        self.emit_event(NodeEvent::Connecting {
+            addr: addr.clone(),
+            side: ConnectionSide::Responder,
+        });
+
+        let stream = match timeout(
+            Duration::from_millis(self.config.connect_timeout_ms),
+            self.transport.dial(addr),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => Err(io::ErrorKind::TimedOut.into()),
+        }
+        .map_err(|e| {
+            self.register_failure(addr);
+            self.remove_connecting(addr, ConnectionSide::Responder);
+            self.emit_event(NodeEvent::ConnectionFailed {
+                addr: addr.clone(),
+                error: e.kind(),
+            });
             e
         })?;
 
@@ -304,48 +620,195 @@ impl Node {
             .await;
 
         if let Err(ref e) = ret {
-            self.connecting.lock().remove(&addr);
-            self.known_peers().register_failure(addr);
+            self.register_failure(addr);
+            self.remove_connecting(addr, ConnectionSide::Responder);
+            self.emit_event(NodeEvent::ConnectionFailed {
+                addr: addr.clone(),
+                error: e.kind(),
+            });
             error!(parent: self.span(), "couldn't initiate a connection with {}: {}", addr, e);
         }
 
         ret
     }
 
-    /// Disconnects from the provided `SocketAddr`.
-    pub async fn disconnect(&self, addr: SocketAddr) -> bool {
+    /// Connects to the first of the provided candidate addresses to become fully connected (i.e. after the
+    /// handshake, if enabled), and returns it. The attempts are staggered by [`Config::connection_attempt_delay_ms`]
+    /// (though a failure triggers the next attempt right away) and alternate between IPv6 and IPv4 addresses,
+    /// in line with the "Happy Eyeballs" algorithm (RFC 8305); the addresses without an IP are tried after
+    /// the IP-based ones.
+    ///
+    /// note: The attempts still in progress once a connection is established are not aborted; instead, any
+    /// of them that succeed are disconnected from.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`io::ErrorKind::InvalidInput`] if no addresses are provided; otherwise, if none of the
+    /// attempts succeed, the error of the last failed attempt is returned.
+    pub async fn connect_any(&self, addrs: &[PeerAddr]) -> io::Result<PeerAddr> {
+        if addrs.is_empty() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        let mut candidates = interleave_ip_families(addrs).into_iter();
+        let (result_sender, mut result_receiver) = mpsc::unbounded_channel();
+        let attempt_delay = Duration::from_millis(self.config.connection_attempt_delay_ms);
+        let mut pending = 0usize;
+        let mut last_error = None;
+
+        loop {
+            let has_more_candidates = if let Some(addr) = candidates.next() {
+                let node = self.clone();
+                let result_sender = result_sender.clone();
+                tokio::spawn(async move {
+                    let result = node.connect(&addr).await;
+                    let _ = result_sender.send((addr, result));
+                });
+                pending += 1;
+                candidates.len() != 0
+            } else {
+                false
+            };
+
+            // wait for a result; if there are more candidates, only until it's time for the next attempt
+            let result = if has_more_candidates {
+                timeout(attempt_delay, result_receiver.recv())
+                    .await
+                    .ok()
+                    .flatten()
+            } else if pending != 0 {
+                result_receiver.recv().await
+            } else {
+                break;
+            };
+
+            if let Some((addr, result)) = result {
+                pending -= 1;
+
+                match result {
+                    Ok(()) => {
+                        // disconnect from any other candidates that connect successfully later on
+                        if pending != 0 {
+                            let node = self.clone();
+                            tokio::spawn(async move {
+                                for _ in 0..pending {
+                                    if let Some((addr, Ok(()))) = result_receiver.recv().await {
+                                        node.disconnect(&addr).await;
+                                    }
+                                }
+                            });
+                        }
+
+                        return Ok(addr);
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+        }
+
+        Err(last_error.unwrap()) // safe; there was at least one candidate and all the attempts failed
+    }
+
+    /// Disconnects from the provided address, closing all the connections with it; the connections are
+    /// drained first if [`Config::drain_timeout_ms`] is set.
+    pub async fn disconnect(&self, addr: &PeerAddr) -> bool {
+        self.disconnect_with_reason(addr, DisconnectReason::Requested)
+            .await
+    }
+
+    /// Disconnects from the provided address for the given reason.
+    pub(crate) async fn disconnect_with_reason(
+        &self,
+        addr: &PeerAddr,
+        reason: DisconnectReason,
+    ) -> bool {
+        let ids = self.connections.ids(addr);
+        if ids.is_empty() {
+            warn!(parent: self.span(), "wasn't connected to {}", addr);
+            return false;
+        }
+
+        let mut disconnected = false;
+        for id in ids {
+            disconnected |= self.close_connection(id, reason).await;
+        }
+
+        disconnected
+    }
+
+    /// Closes the connection with the provided identifier (see [`Node::connection_ids`]), leaving any other
+    /// connections with the same address intact; it is drained first if [`Config::drain_timeout_ms`] is set.
+    pub async fn disconnect_connection(&self, id: ConnectionId) -> bool {
+        self.close_connection(id, DisconnectReason::Requested).await
+    }
+
+    /// Closes the connection with the provided identifier for the given reason.
+    pub(crate) async fn close_connection(
+        &self,
+        id: ConnectionId,
+        reason: DisconnectReason,
+    ) -> bool {
+        let addr = match self.connections.addr(id) {
+            Some(addr) => addr,
+            None => {
+                warn!(parent: self.span(), "there is no connection {}", id);
+                return false;
+            }
+        };
+
+        // the guard keeps any further inbound messages from being processed until the tasks are aborted
+        let mut _processing = None;
+        if let Some(drain_timeout_ms) = self.config.drain_timeout_ms {
+            if matches!(
+                reason,
+                DisconnectReason::Requested | DisconnectReason::ShutDown
+            ) && self.has_connection(id)
+            {
+                let drain_timeout = Duration::from_millis(drain_timeout_ms);
+                match timeout(drain_timeout, self.drain(id, &addr)).await {
+                    Ok(guard) => _processing = guard,
+                    Err(_) => {
+                        warn!(parent: self.span(), "couldn't drain the connection with {} in time", addr)
+                    }
+                }
+            }
+        }
+
+        // the Disconnect protocol is triggered once the connection is drained, so that any state that
+        // the pending writes and the messages being processed rely on is still available until then
         if let Some(handler) = self.protocols.disconnect_handler.get() {
-            if self.is_connected(addr) {
+            if self.has_connection(id) {
                 let (sender, receiver) = oneshot::channel();
 
-                handler.trigger((addr, sender));
+                handler.trigger(((addr.clone(), reason), sender));
                 let _ = receiver.await; // can't really fail
             }
         }
 
-        let conn = self.connections.remove(addr);
+        let conn = self.connections.remove(id);
 
         if let Some(ref conn) = conn {
-            debug!(parent: self.span(), "disconnecting from {}", conn.addr);
+            debug!(parent: conn.span(), "disconnecting from {} ({:?})", conn.addr, reason);
 
             // shut the associated tasks down
             for task in conn.tasks.iter().rev() {
                 task.abort();
             }
 
-            // drop the associated outbound message sender if Writing is enabled
-            if let Some(handler) = self.protocols.writing_handler.get() {
-                handler.senders.write().remove(&addr);
-            }
+            self.remove_protocol_queues(id);
+
+            self.known_peers.register_disconnection(&conn.addr);
 
             // if the (owning) node was not the initiator of the connection, it doesn't know the listening address
             // of the associated peer, so the related stats are unreliable; the next connection initiated by the
-            // peer could be bound to an entirely different port number
+            // peer could be bound to an entirely different port number; the stats of peers with a PeerId remain
+            // available via KnownPeers::get_by_id
             if matches!(conn.side, ConnectionSide::Initiator) {
-                self.known_peers().remove(conn.addr);
+                self.known_peers().remove(&conn.addr);
             }
 
             debug!(parent: self.span(), "disconnected from {}", addr);
+            self.emit_event(NodeEvent::Disconnected { addr, reason });
         } else {
             warn!(parent: self.span(), "wasn't connected to {}", addr);
         }
@@ -353,45 +816,414 @@ impl Node {
         conn.is_some()
     }
 
-    /// Returns a list containing addresses of active connections.
-    pub fn connected_addrs(&self) -> Vec<SocketAddr> {
+    /// Waits for the message from the given connection that is currently being processed (if any), and then
+    /// sends all the queued outbound messages and shuts the stream down.
+    async fn drain(&self, id: ConnectionId, addr: &PeerAddr) -> Option<OwnedMutexGuard<()>> {
+        let processing = if let Some(handler) = self.protocols.reading_handler.get() {
+            handler.wait_for_processing(id).await
+        } else {
+            None
+        };
+
+        // any responses to the last processed message are queued by now
+        if let Some(handler) = self.protocols.writing_handler.get() {
+            if !handler.drain(id).await {
+                debug!(parent: self.span(), "couldn't shut the stream to {} down cleanly", addr);
+            }
+        }
+
+        processing
+    }
+
+    /// Makes the node stay connected to the provided address: it is connected to right away (unless the
+    /// node is already connected to it), and redialed whenever the connection fails or is severed, in
+    /// accordance with the given [`ReconnectPolicy`]. The connection attempts are registered in
+    /// [`KnownPeers`], which also keep track of the persistent peers (see [`KnownPeers::persistent_peers`]).
+    /// Adding an address that is already a persistent peer replaces its policy.
+    ///
+    /// note: In order to disconnect from a persistent peer for good, [`Node::remove_persistent_peer`]
+    /// needs to be called before [`Node::disconnect`].
+    pub fn add_persistent_peer(&self, addr: &PeerAddr, policy: ReconnectPolicy) {
+        let mut persistent_peers = self.persistent_peers.lock();
+        self.known_peers.add_persistent_peer(addr, policy);
+        let task = tokio::spawn(maintain_connection(self.clone(), addr.clone(), policy));
+        if let Some(old_task) = persistent_peers.insert(addr.clone(), task) {
+            old_task.abort();
+        }
+    }
+
+    /// Makes the node stay connected to the persistent peers recorded in its [`KnownPeers`] that it isn't
+    /// maintaining connections with yet, e.g. ones loaded from the [`Config::known_peers_file`] (see
+    /// [`Node::add_persistent_peer`]); it should be called once the protocols are enabled. Returns the
+    /// number of the restored persistent peers.
+    pub fn restore_persistent_peers(&self) -> usize {
+        let mut num_restored = 0;
+        for (addr, policy) in self.known_peers.persistent_peers() {
+            if !self.persistent_peers.lock().contains_key(&addr) {
+                self.add_persistent_peer(&addr, policy);
+                num_restored += 1;
+            }
+        }
+
+        num_restored
+    }
+
+    /// Stops the node from maintaining a connection with the provided address; it doesn't disconnect
+    /// from it. Returns `false` if it wasn't a persistent peer.
+    pub fn remove_persistent_peer(&self, addr: &PeerAddr) -> bool {
+        let recorded = self.known_peers.remove_persistent_peer(addr);
+        if let Some(task) = self.persistent_peers.lock().remove(addr) {
+            task.abort();
+            true
+        } else {
+            recorded
+        }
+    }
+
+    /// Returns the list of persistent peers the node attempts to stay connected to.
+    pub fn persistent_peers(&self) -> Vec<PeerAddr> {
+        self.persistent_peers.lock().keys().cloned().collect()
+    }
+
+    /// Returns a list containing addresses of active connections; an address with multiple connections is
+    /// only listed once.
+    pub fn connected_addrs(&self) -> Vec<PeerAddr> {
         self.connections.addrs()
     }
 
+    /// Returns the [`PeerId`] of the peer connected via the provided address, if it has one.
+    pub fn peer_id(&self, addr: &PeerAddr) -> Option<PeerId> {
+        self.connections.peer_id(addr)
+    }
+
+    /// Returns the address of the connected peer with the provided [`PeerId`]; if there are multiple
+    /// connections with the peer (see [`Config::max_connections_per_peer`]), it's the address of the oldest one.
+    pub fn peer_addr(&self, peer_id: &PeerId) -> Option<PeerAddr> {
+        self.connections.peer_addrs(peer_id).into_iter().next()
+    }
+
+    /// Returns the addresses of all the connections with the peer with the provided [`PeerId`], from the
+    /// oldest to the newest one.
+    pub fn peer_addrs(&self, peer_id: &PeerId) -> Vec<PeerAddr> {
+        self.connections.peer_addrs(peer_id)
+    }
+
+    /// Returns the identifier of the connection with the provided address; if there are multiple connections
+    /// with it (see [`Config::max_connections_per_peer`]), it's the identifier of the oldest one.
+    pub fn connection_id(&self, addr: &PeerAddr) -> Option<ConnectionId> {
+        self.connections.id(addr)
+    }
+
+    /// Returns the identifiers of all the connections with the provided address, from the oldest to the newest one.
+    pub fn connection_ids(&self, addr: &PeerAddr) -> Vec<ConnectionId> {
+        self.connections.ids(addr)
+    }
+
+    /// Returns information about the connection with the provided address, including its
+    /// [`Connection::extensions`].
+    ///
+    /// note: It clones the whole map of extensions; in order to access a single value of it, e.g. on every
+    /// message, use [`Node::connection_extension`] instead. It returns `None` while the connection is still
+    /// being established, including during the [`Handshake`](crate::protocols::Handshake).
+    pub fn connection_info(&self, addr: &PeerAddr) -> Option<ConnectionInfo> {
+        self.connections.info(addr)
+    }
+
+    /// Returns a shared handle to the value of the given type among the [`Connection::extensions`] of the
+    /// connection with the provided address; it can be used e.g. in
+    /// [`Reading::process_message`](crate::protocols::Reading::process_message) or
+    /// [`Writing::write_message`](crate::protocols::Writing::write_message) in order to access the
+    /// data associated with the connection. Like [`Node::connection_info`], it returns `None` while the
+    /// connection is still being established.
+    pub fn connection_extension<T: Send + Sync + 'static>(
+        &self,
+        addr: &PeerAddr,
+    ) -> Option<Arc<T>> {
+        self.connections.extension(addr)
+    }
+
+    /// Disconnects from the peer with the provided [`PeerId`], closing all the connections with it.
+    pub async fn disconnect_peer(&self, peer_id: &PeerId) -> bool {
+        let ids = self.connections.peer_connection_ids(peer_id);
+        if ids.is_empty() {
+            warn!(parent: self.span(), "wasn't connected to {:?}", peer_id);
+            return false;
+        }
+
+        let mut disconnected = false;
+        for id in ids {
+            disconnected |= self.disconnect_connection(id).await;
+        }
+
+        disconnected
+    }
+
+    /// Applies the tie-breaking rule for duplicate connections with the given peer, determining whether a new
+    /// connection is preferred over an existing one; both sides of the connections arrive at the same result.
+    fn is_preferred_connection(
+        &self,
+        peer_id: &PeerId,
+        new: &PeerSlot,
+        existing: &PeerSlot,
+    ) -> bool {
+        // the connections initiated by the same side are resolved in favor of the existing one
+        if new.side == existing.side {
+            return false;
+        }
+
+        if let Some(ref own_id) = self.config.local_peer_id {
+            // prefer the connection initiated by the side with the lower PeerId
+            let initiated_by_peer = new.side == ConnectionSide::Initiator;
+            initiated_by_peer == (peer_id < own_id)
+        } else {
+            // prefer the connection whose ends have the lower addresses, which both sides see the same way
+            match (new.endpoints(), existing.endpoints()) {
+                (Some(new), Some(existing)) => new < existing,
+                _ => false,
+            }
+        }
+    }
+
     /// Returns a reference to the collection of statistics of node's known peers.
     #[inline]
     pub fn known_peers(&self) -> &KnownPeers {
         &self.known_peers
     }
 
+    /// Returns a reference to the node's access control rules.
+    #[inline]
+    pub fn access_control(&self) -> &AccessControl {
+        &self.access_control
+    }
+
+    /// Bans the given address or IP for the given duration (see [`KnownPeers::ban`]), and disconnects from
+    /// any matching peers.
+    pub async fn ban<T: Into<BanTarget>>(&self, target: T, duration: Duration) {
+        let target = target.into();
+        self.known_peers.ban(target.clone(), duration);
+        debug!(parent: self.span(), "banned {:?} for {:?}", target, duration);
+
+        for addr in self.connected_addrs() {
+            if target.matches(&addr) {
+                self.disconnect_with_reason(&addr, DisconnectReason::Banned)
+                    .await;
+            }
+        }
+    }
+
+    /// Registers a failure associated with the given address in [`KnownPeers`] and, if there is a
+    /// [`Config::failure_policy`], disconnects from the peer (and possibly bans it) once it has failed
+    /// too many times. It is called automatically whenever reading, writing or connecting fails, but it
+    /// can also be used to register other kinds of failures, e.g. in [`Reading::process_message`]. If there
+    /// is a [`Config::reputation_policy`], the peer's score is also lowered by its failure penalty.
+    ///
+    /// [`Reading::process_message`]: crate::protocols::Reading::process_message
+    pub fn register_failure(&self, addr: &PeerAddr) {
+        self.known_peers.register_failure(addr);
+        self.adjust_score_by_policy(addr, |policy| -policy.failure_penalty);
+
+        let policy = if let Some(ref policy) = self.config.failure_policy {
+            policy
+        } else {
+            return;
+        };
+
+        // the failures of peers that connected to the node are counted per IP, as they are likely to
+        // reconnect using a different port
+        let target = self.ban_target(addr);
+        if !self.failure_log.register(&target, policy) {
+            return;
+        }
+
+        warn!(parent: self.span(), "{} has failed too many times", addr);
+
+        if let Some(ban_duration_ms) = policy.ban_duration_ms {
+            self.known_peers
+                .ban(target, Duration::from_millis(ban_duration_ms));
+        }
+
+        self.spawn_disconnect(addr, DisconnectReason::TooManyFailures);
+    }
+
+    /// Adjusts the reputation score of the given address by the given amount (see [`KnownPeers::adjust_score`])
+    /// and, if there is a [`Config::reputation_policy`], disconnects from the peer (and possibly bans it) once
+    /// its score drops to one of the thresholds. It can be used to reward or penalize peers for application-specific
+    /// reasons, e.g. in [`Reading::process_message`]; returns the updated score, or `None` if the address is unknown.
+    ///
+    /// [`Reading::process_message`]: crate::protocols::Reading::process_message
+    pub fn adjust_score(&self, addr: &PeerAddr, delta: f64) -> Option<f64> {
+        let score = self.known_peers.adjust_score(addr, delta)?;
+
+        if let Some(ref policy) = self.config.reputation_policy {
+            let is_below = |threshold: Option<f64>| threshold.map(|t| score <= t) == Some(true);
+
+            if is_below(policy.ban_threshold) {
+                warn!(parent: self.span(), "the reputation of {} is too low ({}); banning it", addr, score);
+                self.known_peers.ban(
+                    self.ban_target(addr),
+                    Duration::from_millis(policy.ban_duration_ms),
+                );
+                self.spawn_disconnect(addr, DisconnectReason::LowReputation);
+            } else if is_below(policy.disconnect_threshold) {
+                warn!(parent: self.span(), "the reputation of {} is too low ({})", addr, score);
+                self.spawn_disconnect(addr, DisconnectReason::LowReputation);
+            }
+        }
+
+        Some(score)
+    }
+
+    /// Applies the score adjustment selected from the [`Config::reputation_policy`], if there is one.
+    pub(crate) fn adjust_score_by_policy(
+        &self,
+        addr: &PeerAddr,
+        select: fn(&ReputationPolicy) -> f64,
+    ) {
+        if let Some(ref policy) = self.config.reputation_policy {
+            let delta = select(policy);
+            if delta != 0.0 {
+                self.adjust_score(addr, delta);
+            }
+        }
+    }
+
+    /// Determines what a ban of the given peer should apply to: if the node initiated the connection, the
+    /// peer's address is known to be its listening address, otherwise only its IP is meaningful (if it has one).
+    fn ban_target(&self, addr: &PeerAddr) -> BanTarget {
+        let side = self.connections.side(addr).or_else(|| {
+            self.connecting
+                .lock()
+                .iter()
+                .find(|(a, _)| a == addr)
+                .map(|(_, side)| *side)
+        });
+
+        match addr.ip() {
+            Some(ip) if side == Some(ConnectionSide::Initiator) => BanTarget::Ip(ip),
+            _ => BanTarget::Addr(addr.clone()),
+        }
+    }
+
+    /// Disconnects from the given address in the background, if it is connected; it allows disconnects
+    /// to be triggered from within the connection's own tasks.
+    fn spawn_disconnect(&self, addr: &PeerAddr, reason: DisconnectReason) {
+        if self.is_connected(addr) {
+            let node = self.clone();
+            let addr = addr.clone();
+            tokio::spawn(async move {
+                node.disconnect_with_reason(&addr, reason).await;
+            });
+        }
+    }
+
+    /// Saves the node's [`KnownPeers`] to the [`Config::known_peers_file`], if there is one.
+    #[cfg(feature = "serde")]
+    async fn save_known_peers(&self) {
+        if let Some(path) = self.config.known_peers_file.clone() {
+            let node = self.clone();
+            match task::spawn_blocking(move || node.known_peers.save(path)).await {
+                Ok(Ok(())) => trace!(parent: self.span(), "saved the known peers"),
+                Ok(Err(e)) => error!(parent: self.span(), "couldn't save the known peers: {}", e),
+                Err(e) => error!(parent: self.span(), "couldn't save the known peers: {}", e),
+            }
+        }
+    }
+
     /// Checks whether the provided address is connected.
-    pub fn is_connected(&self, addr: SocketAddr) -> bool {
+    pub fn is_connected(&self, addr: &PeerAddr) -> bool {
         self.connections.is_connected(addr)
     }
 
+    /// Checks whether the connection with the provided identifier is still active.
+    pub(crate) fn has_connection(&self, id: ConnectionId) -> bool {
+        self.connections.contains(id)
+    }
+
     /// Returns the number of active connections.
     pub fn num_connected(&self) -> usize {
         self.connections.num_connected()
     }
 
+    /// Returns the number of inbound messages from the given address that are waiting to be processed,
+    /// or `None` if it's not connected or the node doesn't implement [`Reading`].
+    ///
+    /// [`Reading`]: crate::protocols::Reading
+    pub fn inbound_queue_len(&self, addr: &PeerAddr) -> Option<usize> {
+        let handler = self.protocols.reading_handler.get()?;
+        let id = self.connections.id(addr)?;
+        let queue = handler.queues.read().get(&id).cloned()?;
+
+        Some(queue.queue_len())
+    }
+
+    /// Returns the number of outbound messages to the given address that are waiting to be written, or
+    /// `None` if it's not connected or the node doesn't implement [`Writing`].
+    ///
+    /// [`Writing`]: crate::protocols::Writing
+    pub fn outbound_queue_len(&self, addr: &PeerAddr) -> Option<usize> {
+        let handler = self.protocols.writing_handler.get()?;
+        let id = self.connections.id(addr)?;
+        let (_, queue) = handler.senders.read().get(&id).cloned()?;
+
+        Some(queue.len())
+    }
+
     /// Returns the number of connections that are currently being set up.
     pub fn num_connecting(&self) -> usize {
         self.connecting.lock().len()
     }
 
-    /// Checks whether the `Node` can handle an additional connection.
-    fn can_add_connection(&self) -> bool {
+    /// Checks whether the node may be connected with the given address, based on its [`AccessControl`]
+    /// rules (which only apply to addresses with an IP) and the bans registered in [`KnownPeers`].
+    fn is_permitted(&self, addr: &PeerAddr) -> bool {
+        addr.ip().map(|ip| self.access_control.is_permitted(ip)) != Some(false)
+            && !self.known_peers.is_banned(addr)
+    }
+
+    /// Returns the number of active and pending connections matching the given predicate.
+    fn count_connections<F: Fn(&PeerAddr, ConnectionSide) -> bool>(&self, filter: F) -> usize {
+        let num_connecting = self
+            .connecting
+            .lock()
+            .iter()
+            .filter(|(addr, side)| filter(addr, *side))
+            .count();
+
+        self.connections.count(filter) + num_connecting
+    }
+
+    /// Checks whether the `Node` can handle an additional connection with the given address, where `side`
+    /// is the side of the peer.
+    fn can_add_connection(&self, addr: &PeerAddr, side: ConnectionSide) -> bool {
         let num_connected = self.num_connected();
         let limit = self.config.max_connections as usize;
         if num_connected >= limit || num_connected + self.num_connecting() >= limit {
             warn!(parent: self.span(), "maximum number of connections ({}) reached", limit);
-            false
-        } else {
-            true
+            return false;
+        }
+
+        let (side_limit, direction) = match side {
+            ConnectionSide::Initiator => (self.config.max_inbound, "inbound"),
+            ConnectionSide::Responder => (self.config.max_outbound, "outbound"),
+        };
+        if let Some(limit) = side_limit {
+            if self.count_connections(|_, s| s == side) >= limit as usize {
+                warn!(parent: self.span(), "maximum number of {} connections ({}) reached", direction, limit);
+                return false;
+            }
         }
+
+        if let (Some(limit), Some(ip)) = (self.config.max_connections_per_ip, addr.ip()) {
+            if self.count_connections(|a, _| a.ip() == Some(ip)) >= limit as usize {
+                warn!(parent: self.span(), "maximum number of connections with {} ({}) reached", ip, limit);
+                return false;
+            }
+        }
+
+        true
     }
 
-    /// Gracefully shuts the node down.
+    /// Gracefully shuts the node down; the connections are drained first if [`Config::drain_timeout_ms`] is set.
     pub async fn shut_down(&self) {
         debug!(parent: self.span(), "shutting down");
 
@@ -400,8 +1232,29 @@ impl Node {
             listening_task.abort(); // abort the listening task first
         }
 
-        for addr in self.connected_addrs() {
-            self.disconnect(addr).await;
+        // don't redial the persistent peers
+        for (_, task) in self.persistent_peers.lock().drain() {
+            task.abort();
+        }
+
+        // save the known peers before the disconnects remove some of them
+        #[cfg(feature = "serde")]
+        self.save_known_peers().await;
+
+        // disconnect concurrently, so that any drains don't add up
+        let disconnects = self
+            .connections
+            .all_ids()
+            .into_iter()
+            .map(|id| {
+                let node = self.clone();
+                tokio::spawn(
+                    async move { node.close_connection(id, DisconnectReason::ShutDown).await },
+                )
+            })
+            .collect::<Vec<_>>();
+        for disconnect in disconnects {
+            let _ = disconnect.await;
         }
 
         for handle in tasks {
@@ -410,28 +1263,50 @@ impl Node {
     }
 }
 
-// FIXME: this can probably be done more elegantly
+/// Orders the candidate addresses so that IPv6 and IPv4 ones alternate, starting with the family of the first one;
+/// the addresses without an IP come last.
+fn interleave_ip_families(addrs: &[PeerAddr]) -> Vec<PeerAddr> {
+    let (ip_addrs, mut non_ip_addrs): (Vec<PeerAddr>, Vec<PeerAddr>) =
+        addrs.iter().cloned().partition(|addr| addr.ip().is_some());
+    let is_ipv6 = |addr: &PeerAddr| addr.ip().map(|ip| ip.is_ipv6()) == Some(true);
+    let first_is_ipv6 = ip_addrs.first().map(is_ipv6) == Some(true);
+    let (preferred, other): (Vec<PeerAddr>, Vec<PeerAddr>) = ip_addrs
+        .into_iter()
+        .partition(|addr| is_ipv6(addr) == first_is_ipv6);
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+
+    let mut interleaved = Vec::with_capacity(addrs.len());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+    interleaved.append(&mut non_ip_addrs);
+
+    interleaved
+}
+
 /// Creates the node's tracing span based on its name.
 fn create_span(node_name: &str) -> Span {
-    let mut span = trace_span!("node", name = node_name);
-    if !span.is_disabled() {
-        return span;
-    } else {
-        span = debug_span!("node", name = node_name);
-    }
-    if !span.is_disabled() {
-        return span;
-    } else {
-        span = info_span!("node", name = node_name);
-    }
-    if !span.is_disabled() {
-        return span;
-    } else {
-        span = warn_span!("node", name = node_name);
-    }
-    if !span.is_disabled() {
-        span
-    } else {
-        error_span!("node", name = node_name)
-    }
+    enabled_span!("node", name = node_name)
+}
+
+/// Creates the tracing span of a connection as a child of the node's span; the peer's [`PeerId`] is
+/// recorded once it is known.
+fn create_connection_span(
+    node_span: &Span,
+    addr: &PeerAddr,
+    side: ConnectionSide,
+    id: ConnectionId,
+) -> Span {
+    enabled_span!(
+        parent: node_span,
+        "conn",
+        addr = %addr,
+        side = ?side,
+        id = %id,
+        peer_id = field::Empty
+    )
 }
@@ -0,0 +1,373 @@
+//! A built-in [Noise protocol](https://noiseprotocol.org/noise.html) handshake and the related transparent
+//! encryption layer; once the handshake is concluded, the connection's reader and writer are replaced with
+//! ones that decrypt and encrypt everything passing through them, so [`Reading::read_message`] and
+//! [`Writing::write_message`] operate on plaintext.
+//!
+//! note: Every Noise message is prefixed with its length, encoded as a little-endian `u16`.
+
+use crate::{
+    protocols::Handshake,
+    transport::{ReadHalf, WriteHalf},
+    Connection, ConnectionSide, Pea2Pea, PeerAddr, PeerId,
+};
+
+#[cfg(doc)]
+use crate::protocols::{Reading, Writing};
+
+use async_trait::async_trait;
+use snow::{HandshakeState, StatelessTransportState};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tracing::*;
+
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+pub use snow;
+
+/// The maximum size of a Noise message, as specified by the protocol.
+const MAX_MESSAGE_LEN: usize = 65535;
+
+/// The size of the authentication tag appended to every encrypted Noise message.
+const TAG_LEN: usize = 16;
+
+/// The size of the length prefix of every Noise message.
+const PREFIX_LEN: usize = 2;
+
+/// The configuration of the Noise handshake.
+#[derive(Debug, Clone)]
+pub struct NoiseConfig {
+    /// The Noise protocol name, specifying the handshake pattern and the cryptographic primitives.
+    pub pattern: String,
+    /// The local static private key; if set to `None`, a new one is generated for every handshake.
+    pub static_key: Option<Vec<u8>>,
+    /// The peer's static public key, required by patterns where it is known in advance (e.g. `IK` or `KK`).
+    pub remote_static_key: Option<Vec<u8>>,
+    /// The pre-shared keys along with their locations, required by the `psk` patterns.
+    pub psks: Vec<(u8, Vec<u8>)>,
+    /// An optional prologue both sides of the handshake need to agree on.
+    pub prologue: Option<Vec<u8>>,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        Self {
+            pattern: "Noise_XX_25519_ChaChaPoly_BLAKE2s".into(),
+            static_key: None,
+            remote_static_key: None,
+            psks: Vec::new(),
+            prologue: None,
+        }
+    }
+}
+
+/// A ready [`Handshake`] implementation based on the Noise protocol; any object implementing it automatically
+/// implements [`Handshake`], so it can enable it via [`Handshake::enable_handshake`].
+///
+/// note: In order to perform extra steps in the handshake, implement [`Handshake`] directly and call
+/// [`perform_handshake`] in [`Handshake::perform_handshake`] instead.
+pub trait NoiseHandshake: Pea2Pea {
+    /// Returns the configuration of the Noise handshake.
+    fn noise_config(&self) -> &NoiseConfig;
+
+    /// Allows the peer's static public key to be inspected once the handshake is concluded; returning an
+    /// error causes the connection to be rejected.
+    ///
+    /// note: The default implementation accepts any key.
+    fn verify_remote_static_key(&self, _addr: &PeerAddr, _key: Option<&[u8]>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: NoiseHandshake + Clone + Send + Sync + 'static> Handshake for T {
+    async fn perform_handshake(&self, conn: Connection) -> io::Result<Connection> {
+        let conn = perform_handshake(conn, self.noise_config()).await?;
+        self.verify_remote_static_key(&conn.addr, conn.remote_static_key())?;
+        debug!(parent: self.node().span(), "concluded the Noise handshake with {}", conn.addr);
+
+        Ok(conn)
+    }
+}
+
+/// Performs the Noise handshake using the provided configuration, and replaces the [`Connection`]'s
+/// reader and writer with ones that transparently decrypt and encrypt all the subsequent messages;
+/// unless already set, the peer's static public key (if available) becomes its [`PeerId`].
+pub async fn perform_handshake(
+    mut conn: Connection,
+    config: &NoiseConfig,
+) -> io::Result<Connection> {
+    let mut noise = build_handshake_state(config, !conn.side)?;
+    let mut buffer = vec![0u8; MAX_MESSAGE_LEN];
+
+    while !noise.is_handshake_finished() {
+        if noise.is_my_turn() {
+            let len = noise.write_message(&[], &mut buffer).map_err(noise_error)?;
+            let writer = conn.writer();
+            writer.write_all(&(len as u16).to_le_bytes()).await?;
+            writer.write_all(&buffer[..len]).await?;
+            writer.flush().await?;
+        } else {
+            let reader = conn.reader();
+            let mut len = [0u8; PREFIX_LEN];
+            reader.read_exact(&mut len).await?;
+            let len = u16::from_le_bytes(len) as usize;
+            let mut message = vec![0u8; len];
+            reader.read_exact(&mut message).await?;
+            noise
+                .read_message(&message, &mut buffer)
+                .map_err(noise_error)?;
+        }
+    }
+
+    let state = Arc::new(noise.into_stateless_transport_mode().map_err(noise_error)?);
+
+    conn.remote_static_key = state.get_remote_static().map(|key| key.to_vec());
+    // unless the peer has already been identified, identify it by its static key
+    if conn.peer_id.is_none() {
+        conn.peer_id = state.get_remote_static().map(PeerId::from);
+    }
+
+    let reader = conn.reader.take().unwrap(); // safe; the handshake has just used it
+    let writer = conn.writer.take().unwrap(); // safe; the handshake has just used it
+    conn.reader = Some(Box::new(NoiseReader::new(reader, Arc::clone(&state))));
+    conn.writer = Some(Box::new(NoiseWriter::new(writer, state)));
+
+    Ok(conn)
+}
+
+/// Prepares the handshake state based on the given configuration and the node's side of the connection.
+fn build_handshake_state(
+    config: &NoiseConfig,
+    own_side: ConnectionSide,
+) -> io::Result<HandshakeState> {
+    let params = config
+        .pattern
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut builder = snow::Builder::new(params);
+
+    let generated_key;
+    let static_key = if let Some(ref key) = config.static_key {
+        key
+    } else {
+        generated_key = builder.generate_keypair().map_err(noise_error)?.private;
+        &generated_key
+    };
+    builder = builder.local_private_key(static_key);
+
+    if let Some(ref key) = config.remote_static_key {
+        builder = builder.remote_public_key(key);
+    }
+    for (location, psk) in &config.psks {
+        builder = builder.psk(*location, psk);
+    }
+    if let Some(ref prologue) = config.prologue {
+        builder = builder.prologue(prologue);
+    }
+
+    match own_side {
+        ConnectionSide::Initiator => builder.build_initiator(),
+        ConnectionSide::Responder => builder.build_responder(),
+    }
+    .map_err(noise_error)
+}
+
+/// Converts a Noise error into an IO one.
+fn noise_error(e: snow::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Decrypts the Noise messages read from the underlying reader.
+struct NoiseReader {
+    /// The underlying reader.
+    inner: ReadHalf,
+    /// The Noise state shared with the related [`NoiseWriter`].
+    state: Arc<StatelessTransportState>,
+    /// The nonce of the next inbound message.
+    nonce: u64,
+    /// The encrypted bytes read from the underlying reader.
+    encrypted: Vec<u8>,
+    /// The decrypted bytes.
+    decrypted: Vec<u8>,
+    /// The number of decrypted bytes that were already read.
+    decrypted_pos: usize,
+}
+
+impl NoiseReader {
+    fn new(inner: ReadHalf, state: Arc<StatelessTransportState>) -> Self {
+        Self {
+            inner,
+            state,
+            nonce: 0,
+            encrypted: Vec::with_capacity(PREFIX_LEN + MAX_MESSAGE_LEN),
+            decrypted: Vec::with_capacity(MAX_MESSAGE_LEN),
+            decrypted_pos: 0,
+        }
+    }
+
+    /// Attempts to decrypt a full message from the encrypted bytes; returns `false` if there isn't one yet.
+    fn decrypt_message(&mut self) -> io::Result<bool> {
+        if self.encrypted.len() < PREFIX_LEN {
+            return Ok(false);
+        }
+        let len = u16::from_le_bytes([self.encrypted[0], self.encrypted[1]]) as usize;
+        if self.encrypted.len() < PREFIX_LEN + len {
+            return Ok(false);
+        }
+
+        let mut decrypted = std::mem::take(&mut self.decrypted);
+        decrypted.resize(MAX_MESSAGE_LEN, 0);
+        let decrypted_len = self
+            .state
+            .read_message(
+                self.nonce,
+                &self.encrypted[PREFIX_LEN..][..len],
+                &mut decrypted,
+            )
+            .map_err(noise_error)?;
+        decrypted.truncate(decrypted_len);
+        self.decrypted = decrypted;
+        self.decrypted_pos = 0;
+        self.nonce += 1;
+        self.encrypted.drain(..PREFIX_LEN + len);
+
+        Ok(true)
+    }
+}
+
+impl AsyncRead for NoiseReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        loop {
+            // hand out the already decrypted bytes first
+            if this.decrypted_pos < this.decrypted.len() {
+                let len = buf
+                    .remaining()
+                    .min(this.decrypted.len() - this.decrypted_pos);
+                buf.put_slice(&this.decrypted[this.decrypted_pos..][..len]);
+                this.decrypted_pos += len;
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.decrypt_message()? {
+                continue;
+            }
+
+            // read more encrypted bytes
+            let mut chunk = [0u8; 4096];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf) {
+                Poll::Ready(Ok(())) => {
+                    if chunk_buf.filled().is_empty() {
+                        // the stream was closed; it's only clean in between messages
+                        return if this.encrypted.is_empty() {
+                            Poll::Ready(Ok(()))
+                        } else {
+                            Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()))
+                        };
+                    }
+                    this.encrypted.extend_from_slice(chunk_buf.filled());
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Encrypts everything written to it as Noise messages and passes them to the underlying writer.
+struct NoiseWriter {
+    /// The underlying writer.
+    inner: WriteHalf,
+    /// The Noise state shared with the related [`NoiseReader`].
+    state: Arc<StatelessTransportState>,
+    /// The nonce of the next outbound message.
+    nonce: u64,
+    /// The encrypted message that is being written to the underlying writer.
+    encrypted: Vec<u8>,
+    /// The number of encrypted bytes that were already written.
+    encrypted_pos: usize,
+}
+
+impl NoiseWriter {
+    fn new(inner: WriteHalf, state: Arc<StatelessTransportState>) -> Self {
+        Self {
+            inner,
+            state,
+            nonce: 0,
+            encrypted: Vec::with_capacity(PREFIX_LEN + MAX_MESSAGE_LEN),
+            encrypted_pos: 0,
+        }
+    }
+
+    /// Writes any pending encrypted bytes to the underlying writer.
+    fn poll_write_encrypted(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.encrypted_pos < self.encrypted.len() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.encrypted[self.encrypted_pos..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(len)) => self.encrypted_pos += len,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        self.encrypted.clear();
+        self.encrypted_pos = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for NoiseWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+
+        // the previous message needs to be written out first
+        match this.poll_write_encrypted(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+
+        let len = buf.len().min(MAX_MESSAGE_LEN - TAG_LEN);
+        this.encrypted.resize(PREFIX_LEN + MAX_MESSAGE_LEN, 0);
+        let encrypted_len = this
+            .state
+            .write_message(this.nonce, &buf[..len], &mut this.encrypted[PREFIX_LEN..])
+            .map_err(noise_error)?;
+        this.nonce += 1;
+        this.encrypted[..PREFIX_LEN].copy_from_slice(&(encrypted_len as u16).to_le_bytes());
+        this.encrypted.truncate(PREFIX_LEN + encrypted_len);
+
+        // start writing the message out right away; any errors will surface with the next call
+        let _ = this.poll_write_encrypted(cx);
+
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.poll_write_encrypted(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut self.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.poll_write_encrypted(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut self.inner).poll_shutdown(cx),
+            other => other,
+        }
+    }
+}
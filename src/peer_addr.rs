@@ -0,0 +1,101 @@
+use std::{
+    fmt, io,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+};
+
+#[cfg(doc)]
+use crate::{transport::UnixTransport, KnownPeers, Transport};
+
+/// The prefix of the textual representation of [`PeerAddr::Unix`].
+const UNIX_PREFIX: &str = "unix:";
+
+/// The prefix of the textual representation of [`PeerAddr::UnnamedUnix`].
+const UNNAMED_UNIX_PREFIX: &str = "unix-unnamed:";
+
+/// The address of a peer (or of the node itself); the kinds of addresses that can be used depend on the
+/// [`Transport`].
+///
+/// note: Only [`PeerAddr::Ip`] addresses are subject to the features based on IPs, e.g. the bans of IPs,
+/// the [`Cidr`](crate::Cidr)-based access control rules or [`Config::max_connections_per_ip`].
+///
+/// [`Config::max_connections_per_ip`]: crate::Config::max_connections_per_ip
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PeerAddr {
+    /// An IP socket address.
+    Ip(SocketAddr),
+    /// The path of a Unix domain socket (see [`UnixTransport`]).
+    Unix(PathBuf),
+    /// A peer connected from an unnamed Unix domain socket; such peers have no address of their own, so
+    /// the [`UnixTransport`] identifies each of their connections with a distinct number. Just like other
+    /// addresses of inbound connections, it is removed from the [`KnownPeers`] once the connection ends.
+    UnnamedUnix(u64),
+}
+
+impl PeerAddr {
+    /// Returns the IP socket address, if it is one.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Ip(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+
+    /// Returns the IP address, if there is one.
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.socket_addr().map(|addr| addr.ip())
+    }
+}
+
+impl From<SocketAddr> for PeerAddr {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Ip(addr)
+    }
+}
+
+impl FromStr for PeerAddr {
+    type Err = io::Error;
+
+    /// Parses the address from its textual representation (see the `Display` impl).
+    fn from_str(s: &str) -> io::Result<Self> {
+        if let Some(path) = s.strip_prefix(UNIX_PREFIX) {
+            Ok(Self::Unix(path.into()))
+        } else if let Some(n) = s.strip_prefix(UNNAMED_UNIX_PREFIX) {
+            n.parse()
+                .map(Self::UnnamedUnix)
+                .map_err(|_| io::ErrorKind::InvalidInput.into())
+        } else {
+            s.parse()
+                .map(Self::Ip)
+                .map_err(|_| io::ErrorKind::InvalidInput.into())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PeerAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PeerAddr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let addr = <String as serde::Deserialize>::deserialize(deserializer)?;
+        addr.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for PeerAddr {
+    /// Displays IP socket addresses as-is, and the other ones with a prefix indicating their kind, e.g.
+    /// `unix:/tmp/node.sock` or `unix-unnamed:1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(addr) => write!(f, "{}", addr),
+            Self::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+            Self::UnnamedUnix(n) => write!(f, "{}{}", UNNAMED_UNIX_PREFIX, n),
+        }
+    }
+}
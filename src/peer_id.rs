@@ -0,0 +1,84 @@
+use std::{fmt, io, str::FromStr, sync::Arc};
+
+#[cfg(doc)]
+use crate::{protocols::Handshake, Connection, KnownPeers};
+
+/// A stable identifier of a peer, independent of the address it happens to be connected from; it is
+/// usually derived from the peer's public key, and is assigned to the [`Connection`] during the
+/// [`Handshake`]. Peers with a known `PeerId` retain their [`KnownPeers`] entries across reconnects.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PeerId(Arc<[u8]>);
+
+impl PeerId {
+    /// Returns the bytes the identifier consists of.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for PeerId {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes.into())
+    }
+}
+
+impl From<&[u8]> for PeerId {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.into())
+    }
+}
+
+impl AsRef<[u8]> for PeerId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl FromStr for PeerId {
+    type Err = io::Error;
+
+    /// Parses the identifier from its hexadecimal representation (see the `Display` impl).
+    fn from_str(s: &str) -> io::Result<Self> {
+        if s.len() % 2 != 0 || !s.is_ascii() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map(Self::from)
+            .map_err(|_| io::ErrorKind::InvalidInput.into())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PeerId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PeerId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = <String as serde::Deserialize>::deserialize(deserializer)?;
+        hex.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PeerId({})", self)
+    }
+}
@@ -1,16 +1,14 @@
-use crate::{protocols::ReturnableItem, Pea2Pea};
+use crate::{protocols::ReturnableItem, DisconnectReason, Pea2Pea, PeerAddr};
 
 #[cfg(doc)]
-use crate::{protocols::Writing, Connection};
+use crate::{protocols::Writing, Config, Connection};
 
 use tokio::{
-    sync::{mpsc::{self, unbounded_channel}, oneshot},
+    sync::{mpsc, oneshot},
     task,
 };
 use tracing::*;
 
-use std::net::SocketAddr;
-
 /// Can be used to automatically perform some extra actions when the node disconnects from its
 /// peer, which is especially practical if the disconnect is triggered automatically, e.g. due
 /// to the peer exceeding the allowed number of failures or severing its connection with the node
@@ -24,7 +22,7 @@ where
     /// node disconnecting from a peer.
     async fn enable_disconnect(&self) {
         let (from_node_sender, mut from_node_receiver) =
-        mpsc::unbounded_channel::<(SocketAddr, oneshot::Sender<()>)>();
+            mpsc::unbounded_channel::<ReturnableItem<(PeerAddr, DisconnectReason), ()>>();
 
         // Use a channel to know when the disconnect task is ready.
         let (tx, rx) = oneshot::channel::<()>();
@@ -35,11 +33,11 @@ where
             trace!(parent: self_clone.node().span(), "spawned the Disconnect handler task");
             tx.send(()).unwrap(); // safe; the channel was just opened
 
-            while let Some((addr, notifier)) = from_node_receiver.recv().await {
+            while let Some(((addr, reason), notifier)) = from_node_receiver.recv().await {
                 let self_clone2 = self_clone.clone();
                 task::spawn(async move {
                     // perform the specified extra actions
-                    self_clone2.handle_disconnect(addr).await;
+                    self_clone2.handle_disconnect(addr, reason).await;
                     // notify the node that the extra actions have concluded
                     // and that the related connection can be dropped
                     let _ = notifier.send(()); // can't really fail
@@ -58,16 +56,22 @@ where
     }
 
     /// Any extra actions to be executed during a disconnect; in order to still be able to
-    /// communicate with the peer in the usual manner (i.e. via [`Writing`]), only its [`SocketAddr`]
-    /// (as opposed to the related [`Connection`] object) is provided as an argument.
-    async fn handle_disconnect(&self, addr: SocketAddr);
+    /// communicate with the peer in the usual manner (i.e. via [`Writing`]), only its [`PeerAddr`]
+    /// (as opposed to the related [`Connection`] object) is provided as an argument, along with the
+    /// [`DisconnectReason`].
+    ///
+    /// note: If the connection is drained (see [`Config::drain_timeout_ms`]), this method is called
+    /// afterwards, so no further messages can be sent to the peer at that point.
+    async fn handle_disconnect(&self, addr: PeerAddr, reason: DisconnectReason);
 }
 
 /// The handler object dedicated to the [`Disconnect`] protocol.
-pub struct DisconnectHandler(mpsc::UnboundedSender<ReturnableItem<SocketAddr, ()>>);
+pub struct DisconnectHandler(
+    mpsc::UnboundedSender<ReturnableItem<(PeerAddr, DisconnectReason), ()>>,
+);
 
 impl DisconnectHandler {
-    pub(crate) fn trigger(&self, item: ReturnableItem<SocketAddr, ()>) {
+    pub(crate) fn trigger(&self, item: ReturnableItem<(PeerAddr, DisconnectReason), ()>) {
         if self.0.send(item).is_err() {
             unreachable!(); // protocol's task is down! can't recover
         }
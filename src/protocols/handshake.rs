@@ -1,4 +1,4 @@
-use crate::{protocols::ReturnableConnection, Connection, Pea2Pea};
+use crate::{protocols::ReturnableConnection, Connection, NodeEvent, Pea2Pea};
 
 use tokio::{
     sync::{mpsc, oneshot},
@@ -19,7 +19,8 @@ where
 {
     /// Prepares the node to perform specified Pea2pea handshakes.
     async fn enable_handshake(&self) {
-        let (from_node_sender, mut from_node_receiver) = mpsc::unbounded_channel::<ReturnableConnection>();
+        let (from_node_sender, mut from_node_receiver) =
+            mpsc::unbounded_channel::<ReturnableConnection>();
 
         // Use a channel to know when the handshake task is ready.
         let (tx, rx) = oneshot::channel::<()>();
@@ -31,37 +32,53 @@ where
             tx.send(()).unwrap(); // safe; the channel was just opened
 
             while let Some((conn, result_sender)) = from_node_receiver.recv().await {
-                let addr = conn.addr;
+                let addr = conn.addr.clone();
+                let span = conn.span().clone();
+                let task_span = span.clone();
 
                 let node = self_clone.clone();
-                task::spawn(async move {
-                    debug!(parent: node.node().span(), "shaking hands with {} as the {:?}", addr, !conn.side);
-                    let result = timeout(
-                        Duration::from_millis(node.node().config().max_handshake_time_ms),
-                        node.perform_handshake(conn),
-                    )
-                    .await;
+                task::spawn(
+                    async move {
+                        debug!(parent: &span, "shaking hands with {} as the {:?}", addr, !conn.side);
+                        node.node().emit_event(NodeEvent::HandshakeStarted { addr: addr.clone() });
+                        let result = timeout(
+                            Duration::from_millis(node.node().config().max_handshake_time_ms),
+                            node.perform_handshake(conn),
+                        )
+                        .await;
 
-                    let ret = match result {
-                        Ok(Ok(conn)) => {
-                            debug!(parent: node.node().span(), "successfully handshaken with {}", addr);
-                            Ok(conn)
-                        }
-                        Ok(Err(e)) => {
-                            error!(parent: node.node().span(), "handshake with {} failed: {}", addr, e);
-                            Err(e)
-                        }
-                        Err(_) => {
-                            error!(parent: node.node().span(), "handshake with {} timed out", addr);
-                            Err(io::ErrorKind::TimedOut.into())
+                        let ret = match result {
+                            Ok(Ok(conn)) => {
+                                debug!(parent: &span, "successfully handshaken with {}", addr);
+                                Ok(conn)
+                            }
+                            Ok(Err(e)) => {
+                                error!(parent: &span, "handshake with {} failed: {}", addr, e);
+                                Err(e)
+                            }
+                            Err(_) => {
+                                error!(parent: &span, "handshake with {} timed out", addr);
+                                node.node().adjust_score_by_policy(&addr, |policy| {
+                                    -policy.handshake_timeout_penalty
+                                });
+                                Err(io::ErrorKind::TimedOut.into())
+                            }
+                        };
+
+                        if let Err(ref e) = ret {
+                            node.node().emit_event(NodeEvent::HandshakeFailed {
+                                addr,
+                                error: e.kind(),
+                            });
                         }
-                    };
 
-                    // return the Connection to the Node, resuming Node::adapt_stream
-                    if result_sender.send(ret).is_err() {
-                        unreachable!("could't return a Connection to the Node");
+                        // return the Connection to the Node, resuming Node::adapt_stream
+                        if result_sender.send(ret).is_err() {
+                            unreachable!("could't return a Connection to the Node");
+                        }
                     }
-                });
+                    .instrument(task_span),
+                );
             }
         });
         let _ = rx.await;
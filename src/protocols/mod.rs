@@ -11,11 +11,13 @@ use std::io;
 
 mod disconnect;
 mod handshake;
+mod queue;
 mod reading;
 mod writing;
 
 pub use disconnect::{Disconnect, DisconnectHandler};
 pub use handshake::{Handshake, HandshakeHandler};
+pub use queue::MessageQueue;
 pub use reading::{Reading, ReadingHandler};
 pub use writing::{Writing, WritingHandler};
 
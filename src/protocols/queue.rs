@@ -0,0 +1,171 @@
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+#[cfg(doc)]
+use crate::{
+    protocols::{Reading, Writing},
+    Config, QueuePolicy,
+};
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Allows the lengths of [`MessageQueue`]s to be checked regardless of the type of their messages.
+pub(crate) trait QueueLen: Send + Sync {
+    /// Returns the number of currently queued messages.
+    fn queue_len(&self) -> usize;
+}
+
+impl<T: Send> QueueLen for MessageQueue<T> {
+    fn queue_len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A bounded, per-connection message queue; it is used to pass inbound messages from the reader
+/// task to the message processing task in [`Reading`], and outbound messages from the callers of
+/// [`Writing::send_direct_message`] (and [`Writing::send_broadcast`]) to the writer task. Its depth
+/// is determined by [`Config::inbound_queue_depth`] and [`Config::outbound_queue_depth`] respectively,
+/// and what happens when it's full is specified by the related [`QueuePolicy`].
+pub struct MessageQueue<T> {
+    /// The queued messages.
+    items: Mutex<VecDeque<T>>,
+    /// The maximum number of queued messages.
+    depth: usize,
+    /// Notifies the consumer that a message was queued.
+    pushed: Notify,
+    /// Notifies the producer that there is room for another message.
+    popped: Notify,
+    /// Set once the consumer or the producer is gone; it is only modified while the messages are locked.
+    closed: AtomicBool,
+}
+
+impl<T> MessageQueue<T> {
+    /// Creates a new queue of the given depth.
+    pub(crate) fn new(depth: usize) -> Self {
+        // a queue that can't hold a single message would be unusable
+        let depth = depth.max(1);
+
+        Self {
+            items: Mutex::new(VecDeque::with_capacity(depth)),
+            depth,
+            pushed: Default::default(),
+            popped: Default::default(),
+            closed: Default::default(),
+        }
+    }
+
+    /// Returns the number of currently queued messages.
+    pub fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    /// Checks whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().is_empty()
+    }
+
+    /// Returns the maximum number of messages the queue can hold.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Queues a message, waiting until there's room for it if the queue is full; if the queue is closed,
+    /// the message is dropped.
+    pub async fn push(&self, mut item: T) {
+        loop {
+            // register the interest in a pop before the attempt, so that it can't be missed
+            let popped = self.popped.notified();
+            match self.try_push(item) {
+                Ok(()) => return,
+                Err(_) if self.is_closed() => return,
+                Err(rejected) => item = rejected,
+            }
+            popped.await;
+        }
+    }
+
+    /// Attempts to queue a message; if the queue is full or closed, the message is returned.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        let mut items = self.items.lock();
+        if items.len() >= self.depth || self.is_closed() {
+            return Err(item);
+        }
+        items.push_back(item);
+        drop(items);
+        self.pushed.notify_one();
+
+        Ok(())
+    }
+
+    /// Queues a message; if the queue is full, the oldest message is removed from it and returned. If the
+    /// queue is closed, the message itself is returned.
+    pub fn push_evicting(&self, item: T) -> Option<T> {
+        let mut items = self.items.lock();
+        if self.is_closed() {
+            return Some(item);
+        }
+        let evicted = if items.len() >= self.depth {
+            items.pop_front()
+        } else {
+            None
+        };
+        items.push_back(item);
+        drop(items);
+        self.pushed.notify_one();
+
+        evicted
+    }
+
+    /// Checks whether the queue is closed, i.e. whether its consumer or its producer is gone.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Closes the queue once its consumer or its producer is gone: the queued messages are dropped, no
+    /// further ones are accepted, and the consumer stops waiting for them.
+    pub(crate) fn close(&self) {
+        let mut items = self.items.lock();
+        self.closed.store(true, Ordering::Release);
+        let dropped = std::mem::take(&mut *items);
+        drop(items);
+        drop(dropped);
+        self.popped.notify_waiters();
+        // there is a single consumer; the permit wakes it up even if it isn't waiting yet
+        self.pushed.notify_one();
+    }
+
+    /// Removes the oldest message from the queue, waiting for one to arrive if the queue is empty; returns
+    /// `None` once the queue is closed.
+    pub(crate) async fn pop(&self) -> Option<T> {
+        loop {
+            let item = {
+                let mut items = self.items.lock();
+                if self.is_closed() {
+                    return None;
+                }
+                items.pop_front()
+            };
+            if let Some(item) = item {
+                self.popped.notify_one();
+                return Some(item);
+            }
+            self.pushed.notified().await;
+        }
+    }
+}
+
+/// Closes a [`MessageQueue`] once the task holding it quits (including when it is aborted), so that the
+/// other side of the queue doesn't wait in vain.
+pub(crate) struct CloseOnExit<T>(pub(crate) Arc<MessageQueue<T>>);
+
+impl<T> Drop for CloseOnExit<T> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
@@ -1,17 +1,24 @@
-use crate::{protocols::ReturnableConnection, Pea2Pea};
+use crate::{
+    protocols::{
+        queue::{CloseOnExit, QueueLen},
+        MessageQueue, ReturnableConnection,
+    },
+    ConnectionId, DisconnectReason, Pea2Pea, PeerAddr, QueuePolicy,
+};
 
 #[cfg(doc)]
 use crate::{protocols::Handshake, Config};
 
 use async_trait::async_trait;
+use parking_lot::RwLock;
 use tokio::{
     io::{AsyncRead, AsyncReadExt},
-    sync::{mpsc, oneshot},
+    sync::{mpsc, oneshot, Mutex as AsyncMutex, OwnedMutexGuard},
     time::sleep,
 };
 use tracing::*;
 
-use std::{io, net::SocketAddr, time::Duration};
+use std::{collections::HashMap, io, sync::Arc, time::Duration};
 
 /// Can be used to specify and enable reading, i.e. receiving inbound messages. If the [`Handshake`]
 /// protocol is enabled too, it goes into force only after the handshake has been concluded.
@@ -40,30 +47,68 @@ where
 
             // these objects are sent from `Node::adapt_stream`
             while let Some((mut conn, conn_returner)) = conn_receiver.recv().await {
-                let addr = conn.addr;
+                let addr = conn.addr.clone();
+                let id = conn.id();
+                let span = conn.span().clone();
+                let ready = conn.readiness();
                 let mut reader = conn.reader.take().unwrap(); // safe; it is available at this point
                 let mut buffer = Vec::new();
 
-                let (inbound_message_sender, mut inbound_message_receiver) =
-                    mpsc::unbounded_channel();
+                let inbound_queue = Arc::new(MessageQueue::new(
+                    self_clone.node().config().inbound_queue_depth,
+                ));
+                let inbound_queue_clone = Arc::clone(&inbound_queue);
+
+                // held while a message is being processed
+                let processing_lock = Arc::new(AsyncMutex::new(()));
+                let processing_lock_clone = Arc::clone(&processing_lock);
+
+                if let Some(handler) = self_clone.node().protocols.reading_handler.get() {
+                    handler
+                        .queues
+                        .write()
+                        .insert(id, Arc::clone(&inbound_queue) as Arc<dyn QueueLen>);
+                    handler.processing.write().insert(id, processing_lock);
+                } else {
+                    unreachable!();
+                }
 
                 // Use a channel to know when the processing task is ready.
                 let (tx_processing, rx_processing) = oneshot::channel::<()>();
 
                 // the task for processing parsed messages
                 let processing_clone = self_clone.clone();
-                let inbound_processing_task = tokio::spawn(async move {
-                    let node = processing_clone.node();
-                    trace!(parent: node.span(), "spawned a task for processing messages from {}", addr);
-                    tx_processing.send(()).unwrap(); // safe; the channel was just opened
-
-                    while let Some(msg) = inbound_message_receiver.recv().await {
-                        if let Err(e) = processing_clone.process_message(addr, msg).await {
-                            error!(parent: node.span(), "can't process a message from {}: {}", addr, e);
-                            node.known_peers().register_failure(addr);
+                let processing_span = span.clone();
+                let processing_addr = addr.clone();
+                let inbound_processing_task = tokio::spawn(
+                    PROCESSING_FROM.scope(
+                        id,
+                        async move {
+                            let addr = processing_addr;
+                            let node = processing_clone.node();
+                            trace!(parent: &processing_span, "spawned a task for processing messages from {}", addr);
+                            tx_processing.send(()).unwrap(); // safe; the channel was just opened
+
+                            loop {
+                                // the queue is closed once the reader task quits
+                                let msg = match inbound_queue_clone.pop().await {
+                                    Some(msg) => msg,
+                                    None => break,
+                                };
+                                let _processing = processing_lock_clone.lock().await;
+                                if let Err(e) = processing_clone.process_message(addr.clone(), msg).await {
+                                    error!(parent: &processing_span, "can't process a message from {}: {}", addr, e);
+                                    node.register_failure(&addr);
+                                } else {
+                                    node.adjust_score_by_policy(&addr, |policy| {
+                                        policy.message_reward
+                                    });
+                                }
+                            }
                         }
-                    }
-                });
+                        .instrument(span.clone()),
+                    ),
+                );
                 let _ = rx_processing.await;
                 conn.tasks.push(inbound_processing_task);
 
@@ -72,39 +117,52 @@ where
 
                 // the task for reading messages from a stream
                 let reader_clone = self_clone.clone();
-                let reader_task = tokio::spawn(async move {
-                    let node = reader_clone.node();
-                    trace!(parent: node.span(), "spawned a task for reading messages from {}", addr);
-                    tx_reader.send(()).unwrap(); // safe; the channel was just opened
-
-                    // postpone reads until the connection is fully established; if the process fails,
-                    // this task gets aborted, so there is no need for a dedicated timeout
-                    while !node.connected_addrs().contains(&addr) {
-                        sleep(Duration::from_millis(1)).await;
-                    }
+                let reader_span = span.clone();
+                let reader_task = tokio::spawn(READING_FROM.scope(
+                    id,
+                    async move {
+                        let node = reader_clone.node();
+                        let _close_on_exit = CloseOnExit(Arc::clone(&inbound_queue));
+                        trace!(parent: &reader_span, "spawned a task for reading messages from {}", addr);
+                        tx_reader.send(()).unwrap(); // safe; the channel was just opened
+
+                        // postpone reads until the connection is fully established; if the process fails,
+                        // the connection is dropped, so there is no need for a dedicated timeout
+                        if !ready.await {
+                            return;
+                        }
 
-                    loop {
-                        if let Err(e) = reader_clone
-                            .read_from_stream(
-                                addr,
-                                &mut buffer,
-                                &mut reader,
-                                &inbound_message_sender,
-                            )
-                            .await
-                        {
-                            node.known_peers().register_failure(addr);
-                            buffer.clear();
-                            if node.config().fatal_io_errors.contains(&e.kind()) {
-                                node.disconnect(addr).await;
-                                break;
-                            } else {
-                                sleep(Duration::from_secs(node.config().invalid_read_delay_secs))
+                        loop {
+                            if let Err(e) = reader_clone
+                                .read_from_stream(&addr, &mut buffer, &mut reader, &inbound_queue)
+                                .await
+                            {
+                                // the connection could have been dropped while processing the read
+                                if !node.has_connection(id) {
+                                    break;
+                                }
+
+                                node.register_failure(&addr);
+                                buffer.clear();
+                                if node.config().fatal_io_errors.contains(&e.kind()) {
+                                    let reason = if e.kind() == io::ErrorKind::UnexpectedEof {
+                                        DisconnectReason::PeerClosed
+                                    } else {
+                                        DisconnectReason::ReadError(e.kind())
+                                    };
+                                    node.close_connection(id, reason).await;
+                                    break;
+                                } else {
+                                    sleep(Duration::from_secs(
+                                        node.config().invalid_read_delay_secs,
+                                    ))
                                     .await;
+                                }
                             }
                         }
                     }
-                });
+                    .instrument(span),
+                ));
                 let _ = rx_reader.await;
                 conn.tasks.push(reader_task);
 
@@ -118,7 +176,11 @@ where
         self.node().tasks.lock().push(reading_task);
 
         // register the ReadingHandler with the Node
-        let hdl = ReadingHandler(conn_sender);
+        let hdl = ReadingHandler {
+            handler: conn_sender,
+            queues: Default::default(),
+            processing: Default::default(),
+        };
         assert!(
             self.node().protocols.reading_handler.set(hdl).is_ok(),
             "the Reading protocol was enabled more than once!"
@@ -129,10 +191,10 @@ where
     /// simplicity for better performance.
     async fn read_from_stream<R: AsyncRead + Unpin + Send>(
         &self,
-        addr: SocketAddr,
+        addr: &PeerAddr,
         buffer: &mut Vec<u8>,
         reader: &mut R,
-        message_sender: &mpsc::UnboundedSender<Self::Message>,
+        message_queue: &MessageQueue<Self::Message>,
     ) -> io::Result<()> {
         // register the number of bytes carried over from the previous read (if there were any)
         let carry = buffer.len();
@@ -147,30 +209,31 @@ where
         match read_result {
             Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
             Err(e) => {
-                error!(parent: self.node().span(), "can't read from {}: {}", addr, e);
+                error!(parent: &self.node().connection_span(addr), "can't read from {}: {}", addr, e);
                 Err(e)
             }
             Ok(read_len) => {
                 // the number of bytes left to *process* - this includes the initial carried bytes and the read
                 let left = carry + read_len;
 
-                trace!(parent: self.node().span(), "read {}B from {}; {}B waiting to be processed", read_len, addr, left);
+                trace!(parent: &self.node().connection_span(addr), "read {}B from {}; {}B waiting to be processed", read_len, addr, left);
 
-                self.process_buffer(addr, buffer, left, message_sender)
+                self.process_buffer(addr, buffer, left, message_queue).await
             }
         }
     }
 
     /// Attempts to isolate full messages from the connection's read buffer using [`Reading::read_message`]. Once
     /// no more messages can be extracted, it preserves any leftover bytes and moves them to the beginning of the
-    /// buffer, and further reads from the stream are appended to them. Read messages are sent to a separate message
-    /// processing task in order not to block further reads.
-    fn process_buffer(
+    /// buffer, and further reads from the stream are appended to them. Read messages are queued for a separate message
+    /// processing task in order not to block further reads; if the queue is full, [`Config::inbound_queue_policy`]
+    /// is applied.
+    async fn process_buffer(
         &self,
-        addr: SocketAddr,
+        addr: &PeerAddr,
         buffer: &mut Vec<u8>,
         mut left: usize,
-        message_sender: &mpsc::UnboundedSender<Self::Message>,
+        message_queue: &MessageQueue<Self::Message>,
     ) -> io::Result<()> {
         // wrap the read buffer in a reader
         let mut buf_reader = io::Cursor::new(&buffer[..left]);
@@ -195,17 +258,41 @@ where
                     // subtract the number of successfully processed bytes from the ones left to process
                     left -= parse_size;
 
-                    trace!(parent: self.node().span(), "isolated {}B as a message from {}", parse_size, addr);
+                    trace!(parent: &self.node().connection_span(addr), "isolated {}B as a message from {}", parse_size, addr);
 
                     self.node()
                         .known_peers()
                         .register_received_message(addr, parse_size);
                     self.node().stats().register_received_message(parse_size);
 
-                    // send the message for further processing
-                    if let Err(e) = message_sender.send(msg) {
-                        error!(parent: self.node().span(), "can't process a message from {}: {}", addr, e);
-                        self.node().stats().register_failure();
+                    // queue the message for further processing
+                    match self.node().config().inbound_queue_policy {
+                        QueuePolicy::Wait => message_queue.push(msg).await,
+                        QueuePolicy::DropNewest => {
+                            if message_queue.try_push(msg).is_err() {
+                                warn!(parent: &self.node().connection_span(addr), "the inbound queue for {} is full; dropping the new message", addr);
+                                self.node().stats().register_failure();
+                            }
+                        }
+                        QueuePolicy::DropOldest => {
+                            if message_queue.push_evicting(msg).is_some() {
+                                warn!(parent: &self.node().connection_span(addr), "the inbound queue for {} is full; dropping the oldest message", addr);
+                                self.node().stats().register_failure();
+                            }
+                        }
+                        QueuePolicy::Disconnect => {
+                            if message_queue.try_push(msg).is_err() {
+                                error!(parent: &self.node().connection_span(addr), "the inbound queue for {} is full; disconnecting", addr);
+                                self.node().stats().register_failure();
+                                let reason = DisconnectReason::QueueOverflow;
+                                if let Ok(id) = READING_FROM.try_with(|id| *id) {
+                                    self.node().close_connection(id, reason).await;
+                                } else {
+                                    self.node().disconnect_with_reason(addr, reason).await;
+                                }
+                                return Err(io::ErrorKind::ConnectionAborted.into());
+                            }
+                        }
                     }
 
                     // if the read is exhausted, clear the read buffer and return
@@ -218,11 +305,13 @@ where
                 Ok(None) => {
                     // forbid messages that are larger than the read buffer
                     if left > self.node().config().read_buffer_size {
-                        error!(parent: self.node().span(), "a message from {} is too large", addr);
+                        error!(parent: &self.node().connection_span(addr), "a message from {} is too large", addr);
+                        self.node()
+                            .adjust_score_by_policy(addr, |policy| -policy.invalid_message_penalty);
                         return Err(io::ErrorKind::InvalidData.into());
                     }
 
-                    trace!(parent: self.node().span(), "incomplete message from {}; carrying {}B over", addr, left);
+                    trace!(parent: &self.node().connection_span(addr), "incomplete message from {}; carrying {}B over", addr, left);
 
                     // move the leftover bytes to the beginning of the buffer; the next read will append bytes
                     // starting from where the leftover ones end, allowing the message to be completed
@@ -233,7 +322,9 @@ where
                 }
                 // an erroneous message (e.g. an unexpected zero-length payload)
                 Err(e) => {
-                    error!(parent: self.node().span(), "a message from {} is invalid", addr);
+                    error!(parent: &self.node().connection_span(addr), "a message from {} is invalid", addr);
+                    self.node()
+                        .adjust_score_by_policy(addr, |policy| -policy.invalid_message_penalty);
                     return Err(e);
                 }
             }
@@ -249,21 +340,49 @@ where
     /// but your implementation is free to impose a limit lower than the size of the buffer.
     fn read_message<R: io::Read>(
         &self,
-        source: SocketAddr,
+        source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>>;
 
     /// Processes an inbound message. Can be used to update state, send replies etc.
-    async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()>;
+    async fn process_message(&self, source: PeerAddr, message: Self::Message) -> io::Result<()>;
+}
+
+tokio::task_local! {
+    /// The connection whose messages are processed by the current task.
+    static PROCESSING_FROM: ConnectionId;
+    /// The connection whose stream is read from by the current task.
+    static READING_FROM: ConnectionId;
 }
 
 /// The handler object dedicated to the [`Reading`] protocol.
-pub struct ReadingHandler(mpsc::UnboundedSender<ReturnableConnection>);
+pub struct ReadingHandler {
+    handler: mpsc::UnboundedSender<ReturnableConnection>,
+    pub(crate) queues: RwLock<HashMap<ConnectionId, Arc<dyn QueueLen>>>,
+    /// The locks held while messages from the given connection are being processed.
+    pub(crate) processing: RwLock<HashMap<ConnectionId, Arc<AsyncMutex<()>>>>,
+}
 
 impl ReadingHandler {
     pub(crate) fn trigger(&self, item: ReturnableConnection) {
-        if self.0.send(item).is_err() {
+        if self.handler.send(item).is_err() {
             unreachable!(); // protocol's task is down! can't recover
         }
     }
+
+    /// Waits until the message from the given connection that is currently being processed (if there is one)
+    /// has been processed; the returned guard prevents the processing of further messages.
+    pub(crate) async fn wait_for_processing(
+        &self,
+        id: ConnectionId,
+    ) -> Option<OwnedMutexGuard<()>> {
+        // the disconnect could have been triggered while processing a message from the same connection
+        if PROCESSING_FROM.try_with(|source| *source == id) == Ok(true) {
+            return None;
+        }
+
+        let processing = self.processing.read().get(&id).cloned()?;
+
+        Some(processing.lock_owned().await)
+    }
 }
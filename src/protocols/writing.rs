@@ -1,4 +1,7 @@
-use crate::{protocols::ReturnableConnection, Pea2Pea};
+use crate::{
+    protocols::{queue::CloseOnExit, MessageQueue, ReturnableConnection},
+    ConnectionId, DisconnectReason, Node, Pea2Pea, PeerAddr, PeerId, QueuePolicy,
+};
 
 #[cfg(doc)]
 use crate::{protocols::Handshake, Config};
@@ -11,7 +14,7 @@ use tokio::{
 };
 use tracing::*;
 
-use std::{any::Any, collections::HashMap, io, net::SocketAddr};
+use std::{any::Any, collections::HashMap, io, sync::Arc, time::Instant};
 
 /// Can be used to specify and enable writing, i.e. sending outbound messages. If the [`Handshake`]
 /// protocol is enabled too, it goes into force only after the handshake has been concluded.
@@ -40,18 +43,22 @@ where
 
             // these objects are sent from `Node::adapt_stream`
             while let Some((mut conn, conn_returner)) = conn_receiver.recv().await {
-                let addr = conn.addr;
+                let addr = conn.addr.clone();
+                let id = conn.id();
+                let span = conn.span().clone();
+                let ready = conn.readiness();
                 let mut writer = conn.writer.take().unwrap(); // safe; it is available at this point
                 let mut buffer = Vec::new();
 
-                let (outbound_message_sender, mut outbound_message_receiver) =
-                    mpsc::unbounded_channel();
+                let outbound_queue = Arc::new(MessageQueue::new(
+                    self_clone.node().config().outbound_queue_depth,
+                ));
 
                 if let Some(handler) = self_clone.node().protocols.writing_handler.get() {
                     handler
                         .senders
                         .write()
-                        .insert(addr, outbound_message_sender);
+                        .insert(id, (addr.clone(), Arc::clone(&outbound_queue)));
                 } else {
                     unreachable!();
                 }
@@ -61,36 +68,71 @@ where
 
                 // the task for writing outbound messages
                 let writer_clone = self_clone.clone();
-                let writer_task = tokio::spawn(async move {
-                    let node = writer_clone.node();
-                    trace!(parent: node.span(), "spawned a task for writing messages to {}", addr);
-                    tx_writer.send(()).unwrap(); // safe; the channel was just opened
-
-                    while let Some(wrapped_msg) = outbound_message_receiver.recv().await {
-                        let msg = wrapped_msg.msg.downcast::<Self::Message>().unwrap();
-
-                        match writer_clone
-                            .write_to_stream(*msg, addr, &mut buffer, &mut writer)
-                            .await
-                        {
-                            Ok(len) => {
-                                let _ = wrapped_msg.delivery_notification.send(true);
-                                node.known_peers().register_sent_message(addr, len);
-                                node.stats().register_sent_message(len);
-                                trace!(parent: node.span(), "sent {}B to {}", len, addr);
+                let writer_span = span.clone();
+                let writer_task = tokio::spawn(
+                    async move {
+                        let node = writer_clone.node();
+                        let _close_on_exit = CloseOnExit(Arc::clone(&outbound_queue));
+                        trace!(parent: &writer_span, "spawned a task for writing messages to {}", addr);
+                        tx_writer.send(()).unwrap(); // safe; the channel was just opened
+
+                        // postpone writes until the connection is fully established, just like the reads
+                        if !ready.await {
+                            return;
+                        }
+
+                        loop {
+                            let wrapped_msg = match outbound_queue.pop().await {
+                                Some(msg) => msg,
+                                None => break,
+                            };
+
+                            // all the messages queued before the marker have already been sent
+                            if wrapped_msg.msg.is::<Drain>() {
+                                let result = writer.shutdown().await;
+                                if let Err(ref e) = result {
+                                    debug!(parent: &writer_span, "couldn't shut the stream to {} down: {}", addr, e);
+                                }
+                                let _ = wrapped_msg.delivery_notification.send(result.is_ok());
+                                break;
                             }
-                            Err(e) => {
-                                let _ = wrapped_msg.delivery_notification.send(false);
-                                node.known_peers().register_failure(addr);
-                                error!(parent: node.span(), "couldn't send a message to {}: {}", addr, e);
-                                if node.config().fatal_io_errors.contains(&e.kind()) {
-                                    node.disconnect(addr).await;
-                                    break;
+
+                            let msg = wrapped_msg.msg.downcast::<Self::Message>().unwrap();
+
+                            match writer_clone
+                                .write_to_stream(*msg, &addr, &mut buffer, &mut writer)
+                                .await
+                            {
+                                Ok(len) => {
+                                    let _ = wrapped_msg.delivery_notification.send(true);
+                                    let latency = wrapped_msg.queued_at.elapsed();
+                                    node.known_peers().register_sent_message(&addr, len);
+                                    node.known_peers().register_write_latency(&addr, latency);
+                                    node.stats().register_sent_message(len);
+                                    node.stats().register_write_latency(latency);
+                                    trace!(parent: &writer_span, "sent {}B to {}", len, addr);
+                                }
+                                Err(e) => {
+                                    let _ = wrapped_msg.delivery_notification.send(false);
+                                    node.register_failure(&addr);
+                                    error!(parent: &writer_span, "couldn't send a message to {}: {}", addr, e);
+                                    if node.config().fatal_io_errors.contains(&e.kind()) {
+                                        // no more writes will be performed, even though the task lives on
+                                        // until the disconnect is finished
+                                        outbound_queue.close();
+                                        node.close_connection(
+                                            id,
+                                            DisconnectReason::WriteError(e.kind()),
+                                        )
+                                        .await;
+                                        break;
+                                    }
                                 }
                             }
                         }
                     }
-                });
+                    .instrument(span),
+                );
                 let _ = rx_writer.await;
                 conn.tasks.push(writer_task);
 
@@ -115,11 +157,12 @@ where
     }
 
     /// Writes the given message to the given writer, using the provided intermediate buffer; returns the number of
-    /// bytes written to the writer.
+    /// bytes written to the writer. The writer is flushed afterwards, so that the message isn't left in the buffers
+    /// of any layers wrapping the stream (e.g. TLS or Noise).
     async fn write_to_stream<W: AsyncWrite + Unpin + Send>(
         &self,
         message: Self::Message,
-        addr: SocketAddr,
+        addr: &PeerAddr,
         buffer: &mut Vec<u8>,
         writer: &mut W,
     ) -> io::Result<usize> {
@@ -127,6 +170,7 @@ where
         let len = buffer.len();
         writer.write_all(buffer).await?;
         buffer.clear();
+        writer.flush().await?;
 
         Ok(len)
     }
@@ -138,36 +182,55 @@ where
     /// note: The default `writer` is a memory buffer and thus writing to it is infallible.
     fn write_message<W: io::Write>(
         &self,
-        target: SocketAddr,
+        target: &PeerAddr,
         message: &Self::Message,
         writer: &mut W,
     ) -> io::Result<()>;
 
-    /// Sends the provided message to the specified [`SocketAddr`]. Returns as soon as the message is queued to
+    /// Sends the provided message to the specified [`PeerAddr`]. Returns as soon as the message is queued to
     /// be sent, without waiting for the actual delivery; instead, the caller is provided with a [`oneshot::Receiver`]
     /// which can be used to determine when and whether the message has been delivered.
     ///
+    /// note: If there are multiple connections with the address (see [`Config::max_connections_per_peer`]),
+    /// the message is sent via the oldest one.
+    ///
     /// # Errors
     ///
     /// The following errors can be returned:
     /// - [`io::ErrorKind::NotConnected`] if the node is not connected to the provided address
-    /// - [`io::ErrorKind::Other`] if the outbound message queue for this address is full
+    /// - [`io::ErrorKind::Other`] if the outbound message queue for this address is full (unless
+    ///   [`Config::outbound_queue_policy`] is [`QueuePolicy::DropOldest`])
     /// - [`io::ErrorKind::Unsupported`] if [`Writing::enable_writing`] hadn't been called yet
     fn send_direct_message(
         &self,
-        addr: SocketAddr,
+        addr: &PeerAddr,
+        message: Self::Message,
+    ) -> io::Result<oneshot::Receiver<bool>> {
+        if self.node().protocols.writing_handler.get().is_none() {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+
+        let id = self
+            .node()
+            .connection_id(addr)
+            .ok_or(io::ErrorKind::NotConnected)?;
+
+        self.send_to_connection(id, message)
+    }
+
+    /// Sends the provided message via the connection with the given identifier (see
+    /// [`Node::connection_ids`]); otherwise it works just like [`Writing::send_direct_message`].
+    fn send_to_connection(
+        &self,
+        id: ConnectionId,
         message: Self::Message,
     ) -> io::Result<oneshot::Receiver<bool>> {
         // access the protocol handler
         if let Some(handler) = self.node().protocols.writing_handler.get() {
-            // find the message sender for the given address
-            if let Some(sender) = handler.senders.read().get(&addr).cloned() {
+            // find the message queue for the given connection
+            if let Some((addr, queue)) = handler.senders.read().get(&id).cloned() {
                 let (msg, delivery) = WrappedMessage::new(Box::new(message));
-                sender.send(msg).map_err(|e| {
-                    error!(parent: self.node().span(), "can't send a message to {}: {}", addr, e);
-                    self.node().stats().register_failure();
-                    io::ErrorKind::Other.into()
-                }).map(|_| delivery)
+                queue_message(self.node(), id, &addr, &queue, msg).map(|_| delivery)
             } else {
                 Err(io::ErrorKind::NotConnected.into())
             }
@@ -176,6 +239,26 @@ where
         }
     }
 
+    /// Sends the provided message to the peer with the given [`PeerId`]; otherwise it works just like
+    /// [`Writing::send_direct_message`].
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors returned by [`Writing::send_direct_message`], [`io::ErrorKind::NotConnected`]
+    /// is returned if there is no connected peer with the given [`PeerId`].
+    fn send_to_peer(
+        &self,
+        peer_id: &PeerId,
+        message: Self::Message,
+    ) -> io::Result<oneshot::Receiver<bool>> {
+        let addr = self
+            .node()
+            .peer_addr(peer_id)
+            .ok_or(io::ErrorKind::NotConnected)?;
+
+        self.send_direct_message(&addr, message)
+    }
+
     /// Broadcasts the provided message to all connected peers. Returns as soon as the message is queued to
     /// be sent to all the peers, without waiting for the actual delivery. This method doesn't provide the
     /// means to check when and if the messages actually get delivered; you can achieve that by calling
@@ -191,12 +274,9 @@ where
         // access the protocol handler
         if let Some(handler) = self.node().protocols.writing_handler.get() {
             let senders = handler.senders.read().clone();
-            for (addr, message_sender) in senders {
+            for (id, (addr, queue)) in senders {
                 let (msg, _delivery) = WrappedMessage::new(Box::new(message.clone()));
-                let _ = message_sender.send(msg).map_err(|e| {
-                    error!(parent: self.node().span(), "can't send a message to {}: {}", addr, e);
-                    self.node().stats().register_failure();
-                });
+                let _ = queue_message(self.node(), id, &addr, &queue, msg);
             }
 
             Ok(())
@@ -206,12 +286,53 @@ where
     }
 }
 
+/// Queues an outbound message, applying [`Config::outbound_queue_policy`] if the queue is full.
+fn queue_message(
+    node: &Node,
+    id: ConnectionId,
+    addr: &PeerAddr,
+    queue: &MessageQueue<WrappedMessage>,
+    msg: WrappedMessage,
+) -> io::Result<()> {
+    match node.config().outbound_queue_policy {
+        // rejected by Node::new
+        QueuePolicy::Wait => unreachable!(),
+        QueuePolicy::DropNewest => {
+            if queue.try_push(msg).is_err() {
+                error!(parent: node.span(), "can't send a message to {}: the outbound queue is full", addr);
+                node.stats().register_failure();
+                return Err(io::ErrorKind::Other.into());
+            }
+        }
+        QueuePolicy::DropOldest => {
+            if let Some(evicted) = queue.push_evicting(msg) {
+                warn!(parent: node.span(), "the outbound queue for {} is full; dropping the oldest message", addr);
+                let _ = evicted.delivery_notification.send(false);
+                node.stats().register_failure();
+            }
+        }
+        QueuePolicy::Disconnect => {
+            if queue.try_push(msg).is_err() {
+                error!(parent: node.span(), "the outbound queue for {} is full; disconnecting", addr);
+                node.stats().register_failure();
+                let node = node.clone();
+                tokio::spawn(async move {
+                    node.close_connection(id, DisconnectReason::QueueOverflow)
+                        .await;
+                });
+                return Err(io::ErrorKind::Other.into());
+            }
+        }
+    }
 
+    Ok(())
+}
 
 /// Used to queue messages for delivery.
 pub(crate) struct WrappedMessage {
     msg: Box<dyn Any + Send>,
     delivery_notification: oneshot::Sender<bool>,
+    queued_at: Instant,
 }
 
 impl WrappedMessage {
@@ -220,16 +341,24 @@ impl WrappedMessage {
         let wrapped_msg = Self {
             msg,
             delivery_notification: tx,
+            queued_at: Instant::now(),
         };
 
         (wrapped_msg, rx)
     }
 }
 
+/// The outbound queue of a connection, along with its address.
+type OutboundQueue = (PeerAddr, Arc<MessageQueue<WrappedMessage>>);
+
+/// A marker queued behind the outbound messages of a connection that is being drained.
+struct Drain;
+
 /// The handler object dedicated to the [`Writing`] protocol.
 pub struct WritingHandler {
     handler: mpsc::UnboundedSender<ReturnableConnection>,
-    pub(crate) senders: RwLock<HashMap<SocketAddr, mpsc::UnboundedSender<WrappedMessage>>>,
+    /// The outbound queues of the connections.
+    pub(crate) senders: RwLock<HashMap<ConnectionId, OutboundQueue>>,
 }
 
 impl WritingHandler {
@@ -238,4 +367,19 @@ impl WritingHandler {
             unreachable!(); // protocol's task is down! can't recover
         }
     }
+
+    /// Stops accepting new messages for the given connection, waits until the already queued ones are sent,
+    /// and shuts the stream down. Returns `false` if the stream couldn't be shut down cleanly, including when
+    /// the writer task had already quit, which is detected right away.
+    pub(crate) async fn drain(&self, id: ConnectionId) -> bool {
+        let outbound_queue = match self.senders.write().remove(&id) {
+            Some((_, queue)) => queue,
+            None => return false,
+        };
+
+        let (marker, shut_down) = WrappedMessage::new(Box::new(Drain));
+        outbound_queue.push(marker).await;
+
+        shut_down.await == Ok(true)
+    }
 }
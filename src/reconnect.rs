@@ -0,0 +1,106 @@
+use crate::{Node, NodeEvent, PeerAddr};
+
+use tokio::{sync::broadcast::error::RecvError, time::sleep};
+use tracing::*;
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// Determines how the node attempts to (re)connect to a persistent peer (see [`Node::add_persistent_peer`]).
+/// The delay between consecutive failed attempts grows exponentially, and is extended by a random jitter
+/// in order to avoid synchronized reconnects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReconnectPolicy {
+    /// The delay before the first redial attempt.
+    pub initial_backoff_ms: u64,
+    /// The maximum delay between redial attempts.
+    pub max_backoff_ms: u64,
+    /// The factor the delay is multiplied by after every failed attempt.
+    pub backoff_factor: u32,
+    /// The fraction of the delay (between `0.0` and `1.0`) that may be randomly added to it.
+    pub jitter: f64,
+    /// The number of consecutive failed attempts after which the node stops trying to connect to the peer.
+    ///
+    /// note: If set to `None`, the node never gives up.
+    pub max_retries: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            backoff_factor: 2,
+            jitter: 0.1,
+            max_retries: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay to apply after the given number of consecutive failed attempts.
+    fn backoff(&self, failed_attempts: u32) -> Duration {
+        let factor = (self.backoff_factor as u64).saturating_pow(failed_attempts.saturating_sub(1));
+        let delay = self
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(self.max_backoff_ms);
+
+        // the hashers produced by a fresh RandomState are randomly seeded
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        let jitter = (delay as f64 * self.jitter.clamp(0.0, 1.0) * random) as u64;
+
+        Duration::from_millis(delay + jitter)
+    }
+}
+
+/// Keeps the node connected to the given address, redialing it in accordance with the given policy.
+pub(crate) async fn maintain_connection(node: Node, addr: PeerAddr, policy: ReconnectPolicy) {
+    let mut failed_attempts = 0;
+
+    loop {
+        // subscribe before connecting, so that the related disconnect can't be missed
+        let mut events = node.subscribe_events();
+
+        if !node.is_connected(&addr) {
+            node.known_peers().add(&addr);
+            node.known_peers().register_connection_attempt(&addr);
+
+            if let Err(e) = node.connect(&addr).await {
+                failed_attempts += 1;
+
+                if policy.max_retries.map(|max| failed_attempts > max) == Some(true) {
+                    error!(parent: node.span(), "giving up on the persistent peer {} after {} attempts", addr, failed_attempts);
+                    node.persistent_peers.lock().remove(&addr);
+                    node.known_peers().remove_persistent_peer(&addr);
+                    return;
+                }
+
+                let delay = policy.backoff(failed_attempts);
+                debug!(parent: node.span(), "couldn't connect to the persistent peer {} ({}); retrying in {:?}", addr, e, delay);
+                sleep(delay).await;
+                continue;
+            }
+        }
+
+        failed_attempts = 0;
+
+        // wait for the peer to become disconnected
+        loop {
+            match events.recv().await {
+                Ok(NodeEvent::Disconnected { addr: a, .. }) if a == addr => break,
+                Err(RecvError::Lagged(_)) if !node.is_connected(&addr) => break,
+                Err(RecvError::Closed) => return,
+                _ => {}
+            }
+        }
+
+        let delay = policy.backoff(1);
+        debug!(parent: node.span(), "the persistent peer {} got disconnected; redialing in {:?}", addr, delay);
+        sleep(delay).await;
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(doc)]
+use crate::{protocols::Reading, Config, KnownPeers, Node};
+
+/// Determines how the scores of the node's peers are adjusted automatically, how quickly they decay, and
+/// what happens to the peers whose scores drop too low (see [`Config::reputation_policy`]). The scores are
+/// kept in [`KnownPeers`], and can also be adjusted manually via [`Node::adjust_score`], e.g. from within
+/// [`Reading::process_message`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReputationPolicy {
+    /// The amount the score is increased by whenever a message from the peer is processed successfully.
+    pub message_reward: f64,
+    /// The amount the score is decreased by whenever a failure is registered (see [`Node::register_failure`]).
+    pub failure_penalty: f64,
+    /// The amount the score is decreased by whenever the peer sends a message that can't be read.
+    ///
+    /// note: Such messages also count as failures, so the [`ReputationPolicy::failure_penalty`] applies too.
+    pub invalid_message_penalty: f64,
+    /// The amount the score is decreased by whenever the handshake with the peer times out.
+    ///
+    /// note: Such timeouts also count as failures, so the [`ReputationPolicy::failure_penalty`] applies too.
+    pub handshake_timeout_penalty: f64,
+    /// If set, the scores decay towards zero, halving every period of this length.
+    pub half_life_ms: Option<u64>,
+    /// If set, the peer is disconnected from once an adjustment leaves its score at or below this value.
+    pub disconnect_threshold: Option<f64>,
+    /// If set, the peer is disconnected from and banned once an adjustment leaves its score at or below
+    /// this value; if the node initiated the connection, the ban applies to the peer's address, otherwise it
+    /// applies to its IP (see [`Node::ban`]).
+    pub ban_threshold: Option<f64>,
+    /// The duration of the bans caused by the [`ReputationPolicy::ban_threshold`].
+    pub ban_duration_ms: u64,
+}
+
+impl Default for ReputationPolicy {
+    fn default() -> Self {
+        Self {
+            message_reward: 1.0,
+            failure_penalty: 10.0,
+            invalid_message_penalty: 20.0,
+            handshake_timeout_penalty: 20.0,
+            half_life_ms: Some(10 * 60 * 1000),
+            disconnect_threshold: None,
+            ban_threshold: None,
+            ban_duration_ms: 60 * 60 * 1000,
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use parking_lot::Mutex;
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 /// Contains statistics related to a node.
 #[derive(Default)]
@@ -13,6 +18,383 @@ pub struct Stats {
     bytes_received: AtomicU64,
     /// The number of failures.
     failures: AtomicU64,
+    /// The number of connection attempts.
+    connection_attempts: AtomicU64,
+    /// The number of rejected connections.
+    rejections: AtomicU64,
+    /// The number of established connections.
+    connections: AtomicU64,
+    // note: the times below are in milliseconds since the Unix epoch; `0` means never
+    /// The time the peer was first seen at.
+    first_seen: AtomicU64,
+    /// The time the peer was last seen at.
+    last_seen: AtomicU64,
+    /// The time the last message was sent at.
+    last_sent: AtomicU64,
+    /// The time the last message was received at.
+    last_received: AtomicU64,
+    /// The time the current connection was established at.
+    connected_since: AtomicU64,
+    /// The cumulative duration of the past connections, in milliseconds.
+    connected_duration: AtomicU64,
+    /// The rates of sent messages and bytes; they are only updated when a snapshot is taken.
+    sent_rates: Mutex<RateTracker>,
+    /// The rates of received messages and bytes; they are only updated when a snapshot is taken.
+    received_rates: Mutex<RateTracker>,
+    /// The sizes of sent messages, in bytes.
+    sent_sizes: AtomicHistogram,
+    /// The sizes of received messages, in bytes.
+    received_sizes: AtomicHistogram,
+    /// The times between queueing messages for sending and writing them, in microseconds.
+    write_latencies: AtomicHistogram,
+    /// The reputation score.
+    score: Mutex<Score>,
+}
+
+/// A point-in-time copy of [`Stats`]; unlike the [`Stats`] themselves, it doesn't change, so it can be
+/// compared, diffed (e.g. to obtain the exact changes caused by a test scenario), merged, and - with the
+/// `serde` feature - serialized.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatsSnapshot {
+    /// The number of sent messages.
+    pub msgs_sent: u64,
+    /// The number of sent bytes.
+    pub bytes_sent: u64,
+    /// The number of received messages.
+    pub msgs_received: u64,
+    /// The number of received bytes.
+    pub bytes_received: u64,
+    /// The number of failures.
+    pub failures: u64,
+    /// The number of connection attempts.
+    pub connection_attempts: u64,
+    /// The number of rejected connections.
+    pub rejections: u64,
+    /// The number of established connections.
+    pub connections: u64,
+    /// The time the peer was first seen at.
+    pub first_seen: Option<SystemTime>,
+    /// The time the peer was last seen at.
+    pub last_seen: Option<SystemTime>,
+    /// The time the last message was sent at.
+    pub last_sent: Option<SystemTime>,
+    /// The time the last message was received at.
+    pub last_received: Option<SystemTime>,
+    /// The time the current connection was established at.
+    pub connected_since: Option<SystemTime>,
+    /// The cumulative duration of all the connections, including the current one.
+    pub connected_duration: Duration,
+    /// The rates of sent messages and bytes.
+    pub sent_rates: Rates,
+    /// The rates of received messages and bytes.
+    pub received_rates: Rates,
+    /// The sizes of sent messages, in bytes.
+    pub sent_sizes: Histogram,
+    /// The sizes of received messages, in bytes.
+    pub received_sizes: Histogram,
+    /// The times between queueing messages for sending (e.g. via [`Writing::send_direct_message`]) and
+    /// writing them to the stream, in microseconds.
+    ///
+    /// [`Writing::send_direct_message`]: crate::protocols::Writing::send_direct_message
+    pub write_latencies: Histogram,
+}
+
+impl StatsSnapshot {
+    /// Returns the changes that have occurred since the given earlier snapshot: the differences between
+    /// the counters, the durations and the histograms. The times and the rates are taken from `self`, as
+    /// they are not cumulative.
+    pub fn diff(&self, earlier: &Self) -> Self {
+        Self {
+            msgs_sent: self.msgs_sent.saturating_sub(earlier.msgs_sent),
+            bytes_sent: self.bytes_sent.saturating_sub(earlier.bytes_sent),
+            msgs_received: self.msgs_received.saturating_sub(earlier.msgs_received),
+            bytes_received: self.bytes_received.saturating_sub(earlier.bytes_received),
+            failures: self.failures.saturating_sub(earlier.failures),
+            connection_attempts: self
+                .connection_attempts
+                .saturating_sub(earlier.connection_attempts),
+            rejections: self.rejections.saturating_sub(earlier.rejections),
+            connections: self.connections.saturating_sub(earlier.connections),
+            connected_duration: self
+                .connected_duration
+                .checked_sub(earlier.connected_duration)
+                .unwrap_or_default(),
+            sent_sizes: self.sent_sizes.diff(&earlier.sent_sizes),
+            received_sizes: self.received_sizes.diff(&earlier.received_sizes),
+            write_latencies: self.write_latencies.diff(&earlier.write_latencies),
+            ..self.clone()
+        }
+    }
+
+    /// Merges the given snapshot into this one, e.g. in order to aggregate the stats of multiple peers: the
+    /// counters, the durations, the rates and the histograms are added up, the earliest of the first-seen and
+    /// connection times are kept, and so are the latest of the other times.
+    pub fn merge(&mut self, other: &Self) {
+        fn earliest(a: Option<SystemTime>, b: Option<SystemTime>) -> Option<SystemTime> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                _ => a.or(b),
+            }
+        }
+
+        self.msgs_sent += other.msgs_sent;
+        self.bytes_sent += other.bytes_sent;
+        self.msgs_received += other.msgs_received;
+        self.bytes_received += other.bytes_received;
+        self.failures += other.failures;
+        self.connection_attempts += other.connection_attempts;
+        self.rejections += other.rejections;
+        self.connections += other.connections;
+        self.first_seen = earliest(self.first_seen, other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.last_sent = self.last_sent.max(other.last_sent);
+        self.last_received = self.last_received.max(other.last_received);
+        self.connected_since = earliest(self.connected_since, other.connected_since);
+        self.connected_duration += other.connected_duration;
+        self.sent_rates.merge(&other.sent_rates);
+        self.received_rates.merge(&other.received_rates);
+        self.sent_sizes.merge(&other.sent_sizes);
+        self.received_sizes.merge(&other.received_sizes);
+        self.write_latencies.merge(&other.write_latencies);
+    }
+}
+
+/// Per-second rates of messages and bytes, as exponential moving averages over 1 second and 1 minute; the
+/// messages registered between two consecutive snapshots are assumed to have been spread evenly over time.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rates {
+    /// The number of messages per second, averaged over 1 second.
+    pub msgs_1s: f64,
+    /// The number of bytes per second, averaged over 1 second.
+    pub bytes_1s: f64,
+    /// The number of messages per second, averaged over 1 minute.
+    pub msgs_1m: f64,
+    /// The number of bytes per second, averaged over 1 minute.
+    pub bytes_1m: f64,
+}
+
+impl Rates {
+    /// Adds the given rates to these ones.
+    pub fn merge(&mut self, other: &Self) {
+        self.msgs_1s += other.msgs_1s;
+        self.bytes_1s += other.bytes_1s;
+        self.msgs_1m += other.msgs_1m;
+        self.bytes_1m += other.bytes_1m;
+    }
+}
+
+/// The time constants of the moving averages, in seconds.
+const RATE_WINDOWS: [f64; 2] = [1.0, 60.0];
+
+/// Derives the moving averages of the rates of messages and bytes from the related counters; they are sampled
+/// lazily, so that registering a message doesn't involve any locking.
+struct RateTracker {
+    /// The time of the last sample.
+    sampled: Instant,
+    /// The numbers of messages and bytes at the time of the last sample.
+    counters: (u64, u64),
+    /// The message rates for each of the [`RATE_WINDOWS`].
+    msgs: [f64; 2],
+    /// The byte rates for each of the [`RATE_WINDOWS`].
+    bytes: [f64; 2],
+}
+
+impl Default for RateTracker {
+    fn default() -> Self {
+        Self {
+            sampled: Instant::now(),
+            counters: (0, 0),
+            msgs: [0.0; 2],
+            bytes: [0.0; 2],
+        }
+    }
+}
+
+impl RateTracker {
+    /// Updates the rates with the current values of the counters, and returns them.
+    fn sample(&mut self, (msgs, bytes): (u64, u64)) -> Rates {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.sampled).as_secs_f64();
+
+        if elapsed > 0.0 {
+            // the average rates since the previous sample
+            let msg_rate = msgs.saturating_sub(self.counters.0) as f64 / elapsed;
+            let byte_rate = bytes.saturating_sub(self.counters.1) as f64 / elapsed;
+
+            for (i, window) in RATE_WINDOWS.iter().enumerate() {
+                let factor = (-elapsed / window).exp();
+                self.msgs[i] = self.msgs[i] * factor + msg_rate * (1.0 - factor);
+                self.bytes[i] = self.bytes[i] * factor + byte_rate * (1.0 - factor);
+            }
+
+            self.sampled = now;
+            self.counters = (msgs, bytes);
+        }
+
+        Rates {
+            msgs_1s: self.msgs[0],
+            bytes_1s: self.bytes[0],
+            msgs_1m: self.msgs[1],
+            bytes_1m: self.bytes[1],
+        }
+    }
+}
+
+/// The number of histogram buckets; one for zero, and one for every possible bit length of a `u64`.
+const NUM_BUCKETS: usize = 65;
+
+/// A histogram of `u64` values with power-of-two buckets: the first one counts zeros, and the bucket
+/// with the index `i > 0` counts the values in the range `[2^(i-1), 2^i)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram {
+    /// The counts of values in each of the buckets.
+    pub buckets: Vec<u64>,
+    /// The number of values.
+    pub count: u64,
+    /// The sum of the values.
+    pub sum: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; NUM_BUCKETS],
+            count: 0,
+            sum: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Returns the index of the bucket the given value belongs to.
+    pub fn bucket_index(value: u64) -> usize {
+        (u64::BITS - value.leading_zeros()) as usize
+    }
+
+    /// Returns the (inclusive) upper bound of the values in the bucket with the given index.
+    pub fn bucket_upper_bound(index: usize) -> u64 {
+        match index {
+            0 => 0,
+            i if i >= 64 => u64::MAX,
+            i => (1u64 << i) - 1,
+        }
+    }
+
+    /// Returns the values registered since the given earlier state of the histogram.
+    pub fn diff(&self, earlier: &Self) -> Self {
+        let buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| count.saturating_sub(earlier.buckets.get(i).copied().unwrap_or(0)))
+            .collect();
+
+        Self {
+            buckets,
+            count: self.count.saturating_sub(earlier.count),
+            sum: self.sum.saturating_sub(earlier.sum),
+        }
+    }
+
+    /// Adds the values of the given histogram to this one.
+    pub fn merge(&mut self, other: &Self) {
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (count, other_count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *count += other_count;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+    }
+
+    /// Returns the mean of the values, or `None` if there aren't any.
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum as f64 / self.count as f64)
+        }
+    }
+
+    /// Returns an upper bound of the given quantile (between `0.0` and `1.0`) of the values, based on the
+    /// bucket it falls into, or `None` if there aren't any values.
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        let total = self.buckets.iter().sum::<u64>();
+        if total == 0 {
+            return None;
+        }
+
+        let rank = ((total as f64 * quantile.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::bucket_upper_bound(i));
+            }
+        }
+
+        None
+    }
+}
+
+/// A histogram that can be updated concurrently.
+struct AtomicHistogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl Default for AtomicHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..NUM_BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: Default::default(),
+            sum: Default::default(),
+        }
+    }
+}
+
+impl AtomicHistogram {
+    /// Registers the given value.
+    fn register(&self, value: u64) {
+        self.buckets[Histogram::bucket_index(value)].fetch_add(1, Relaxed);
+        self.count.fetch_add(1, Relaxed);
+        self.sum.fetch_add(value, Relaxed);
+    }
+
+    /// Returns a snapshot of the histogram.
+    fn snapshot(&self) -> Histogram {
+        Histogram {
+            buckets: self.buckets.iter().map(|b| b.load(Relaxed)).collect(),
+            count: self.count.load(Relaxed),
+            sum: self.sum.load(Relaxed),
+        }
+    }
+}
+
+/// A reputation score, along with the time it was last updated at.
+#[derive(Default)]
+struct Score {
+    value: f64,
+    updated: Option<Instant>,
+}
+
+impl Score {
+    /// Returns the value of the score, decayed in accordance with the given half-life.
+    fn decayed(&self, now: Instant, half_life: Option<Duration>) -> f64 {
+        match (half_life, self.updated) {
+            (Some(half_life), Some(updated)) if !half_life.is_zero() => {
+                let half_lives =
+                    now.duration_since(updated).as_secs_f64() / half_life.as_secs_f64();
+                self.value * 0.5f64.powf(half_lives)
+            }
+            _ => self.value,
+        }
+    }
 }
 
 impl Stats {
@@ -20,12 +402,46 @@ impl Stats {
     pub fn register_sent_message(&self, size: usize) {
         self.msgs_sent.fetch_add(1, Relaxed);
         self.bytes_sent.fetch_add(size as u64, Relaxed);
+        self.last_sent.store(now_millis(), Relaxed);
+        self.sent_sizes.register(size as u64);
     }
 
     /// Registers a received message of the provided `size` in bytes.
     pub fn register_received_message(&self, size: usize) {
         self.msgs_received.fetch_add(1, Relaxed);
         self.bytes_received.fetch_add(size as u64, Relaxed);
+        self.last_received.store(now_millis(), Relaxed);
+        self.received_sizes.register(size as u64);
+        self.register_seen();
+    }
+
+    /// Registers the time between queueing a message for sending and writing it to the stream.
+    pub fn register_write_latency(&self, latency: Duration) {
+        self.write_latencies
+            .register(latency.as_micros().min(u64::MAX as u128) as u64);
+    }
+
+    /// Registers that the peer was seen, i.e. that it has connected or sent a message, just now.
+    pub fn register_seen(&self) {
+        let now = now_millis();
+        let _ = self.first_seen.compare_exchange(0, now, Relaxed, Relaxed);
+        self.last_seen.store(now, Relaxed);
+    }
+
+    /// Registers an established connection.
+    pub fn register_connection(&self) {
+        self.connections.fetch_add(1, Relaxed);
+        self.connected_since.store(now_millis(), Relaxed);
+        self.register_seen();
+    }
+
+    /// Registers the end of the current connection, adding its duration to the cumulative one.
+    pub fn register_disconnection(&self) {
+        let connected_since = self.connected_since.swap(0, Relaxed);
+        if connected_since != 0 {
+            let duration = now_millis().saturating_sub(connected_since);
+            self.connected_duration.fetch_add(duration, Relaxed);
+        }
     }
 
     /// Registers a failure.
@@ -33,6 +449,16 @@ impl Stats {
         self.failures.fetch_add(1, Relaxed);
     }
 
+    /// Registers a connection attempt.
+    pub fn register_connection_attempt(&self) {
+        self.connection_attempts.fetch_add(1, Relaxed);
+    }
+
+    /// Registers a rejected connection.
+    pub fn register_rejection(&self) {
+        self.rejections.fetch_add(1, Relaxed);
+    }
+
     /// Returns the number of sent messages and their collective size in bytes.
     pub fn sent(&self) -> (u64, u64) {
         let msgs = self.msgs_sent.load(Relaxed);
@@ -53,4 +479,208 @@ impl Stats {
     pub fn failures(&self) -> u64 {
         self.failures.load(Relaxed)
     }
+
+    /// Returns the number of connection attempts.
+    pub fn connection_attempts(&self) -> u64 {
+        self.connection_attempts.load(Relaxed)
+    }
+
+    /// Returns the number of rejected connections.
+    pub fn rejections(&self) -> u64 {
+        self.rejections.load(Relaxed)
+    }
+
+    /// Returns the number of established connections.
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Relaxed)
+    }
+
+    /// Returns the number of connections established after the first one.
+    pub fn reconnections(&self) -> u64 {
+        self.connections().saturating_sub(1)
+    }
+
+    /// Returns the time the peer was first seen at.
+    pub fn first_seen(&self) -> Option<SystemTime> {
+        load_time(&self.first_seen)
+    }
+
+    /// Returns the time the peer was last seen at, i.e. when it last connected or sent a message.
+    pub fn last_seen(&self) -> Option<SystemTime> {
+        load_time(&self.last_seen)
+    }
+
+    /// Returns the time the last message was sent at.
+    pub fn last_sent(&self) -> Option<SystemTime> {
+        load_time(&self.last_sent)
+    }
+
+    /// Returns the time the last message was received at.
+    pub fn last_received(&self) -> Option<SystemTime> {
+        load_time(&self.last_received)
+    }
+
+    /// Returns the time the current connection was established at, if there is one.
+    pub fn connected_since(&self) -> Option<SystemTime> {
+        load_time(&self.connected_since)
+    }
+
+    /// Returns the cumulative duration of all the connections, including the current one.
+    pub fn connected_duration(&self) -> Duration {
+        let current = match self.connected_since.load(Relaxed) {
+            0 => 0,
+            since => now_millis().saturating_sub(since),
+        };
+
+        Duration::from_millis(self.connected_duration.load(Relaxed) + current)
+    }
+
+    /// Returns a point-in-time copy of the stats.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let (msgs_sent, bytes_sent) = self.sent();
+        let (msgs_received, bytes_received) = self.received();
+
+        StatsSnapshot {
+            msgs_sent,
+            bytes_sent,
+            msgs_received,
+            bytes_received,
+            failures: self.failures(),
+            connection_attempts: self.connection_attempts(),
+            rejections: self.rejections(),
+            connections: self.connections(),
+            first_seen: self.first_seen(),
+            last_seen: self.last_seen(),
+            last_sent: self.last_sent(),
+            last_received: self.last_received(),
+            connected_since: self.connected_since(),
+            connected_duration: self.connected_duration(),
+            sent_rates: self.sent_rates.lock().sample((msgs_sent, bytes_sent)),
+            received_rates: self
+                .received_rates
+                .lock()
+                .sample((msgs_received, bytes_received)),
+            sent_sizes: self.sent_sizes.snapshot(),
+            received_sizes: self.received_sizes.snapshot(),
+            write_latencies: self.write_latencies.snapshot(),
+        }
+    }
+
+    /// Returns the time that has passed since the peer was last seen, or `None` if it was never seen.
+    pub fn idle_for(&self) -> Option<Duration> {
+        self.last_seen()
+            .map(|last_seen| last_seen.elapsed().unwrap_or_default())
+    }
+
+    /// Adjusts the reputation score by the given amount, taking the given half-life into account; returns
+    /// the updated score.
+    pub(crate) fn adjust_score(&self, delta: f64, half_life: Option<Duration>) -> f64 {
+        let now = Instant::now();
+        let mut score = self.score.lock();
+        score.value = score.decayed(now, half_life) + delta;
+        score.updated = Some(now);
+
+        score.value
+    }
+
+    /// Returns the reputation score, taking the given half-life into account.
+    pub(crate) fn score(&self, half_life: Option<Duration>) -> f64 {
+        self.score.lock().decayed(Instant::now(), half_life)
+    }
+
+    /// Returns the persistent part of the stats, with the score decayed in accordance with the given half-life.
+    #[cfg(feature = "serde")]
+    pub(crate) fn to_record(&self, half_life: Option<Duration>) -> StatsRecord {
+        let (msgs_sent, bytes_sent) = self.sent();
+        let (msgs_received, bytes_received) = self.received();
+
+        StatsRecord {
+            msgs_sent,
+            bytes_sent,
+            msgs_received,
+            bytes_received,
+            failures: self.failures(),
+            connection_attempts: self.connection_attempts(),
+            rejections: self.rejections(),
+            connections: self.connections(),
+            first_seen: self.first_seen.load(Relaxed),
+            last_seen: self.last_seen.load(Relaxed),
+            last_sent: self.last_sent.load(Relaxed),
+            last_received: self.last_received.load(Relaxed),
+            connected_duration: self.connected_duration().as_millis() as u64,
+            score: self.score(half_life),
+        }
+    }
+
+    /// Restores the stats from their persistent part.
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_record(record: StatsRecord) -> Self {
+        let stats = Self::default();
+
+        for (counter, value) in [
+            (&stats.msgs_sent, record.msgs_sent),
+            (&stats.bytes_sent, record.bytes_sent),
+            (&stats.msgs_received, record.msgs_received),
+            (&stats.bytes_received, record.bytes_received),
+            (&stats.failures, record.failures),
+            (&stats.connection_attempts, record.connection_attempts),
+            (&stats.rejections, record.rejections),
+            (&stats.connections, record.connections),
+            (&stats.first_seen, record.first_seen),
+            (&stats.last_seen, record.last_seen),
+            (&stats.last_sent, record.last_sent),
+            (&stats.last_received, record.last_received),
+            (&stats.connected_duration, record.connected_duration),
+        ] {
+            counter.store(value, Relaxed);
+        }
+        *stats.score.lock() = Score {
+            value: record.score,
+            updated: Some(Instant::now()),
+        };
+
+        stats
+    }
+}
+
+/// The persistent part of [`Stats`], saved along with the [`KnownPeers`](crate::KnownPeers); the times are in
+/// milliseconds since the Unix epoch (with `0` meaning never), and the missing fields default to zero.
+#[cfg(feature = "serde")]
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub(crate) struct StatsRecord {
+    msgs_sent: u64,
+    bytes_sent: u64,
+    msgs_received: u64,
+    bytes_received: u64,
+    failures: u64,
+    connection_attempts: u64,
+    rejections: u64,
+    connections: u64,
+    first_seen: u64,
+    last_seen: u64,
+    last_sent: u64,
+    last_received: u64,
+    connected_duration: u64,
+    score: f64,
+}
+
+/// Returns the number of milliseconds between the Unix epoch and now.
+fn now_millis() -> u64 {
+    unix_millis(SystemTime::now())
+}
+
+/// Returns the time stored in the given atomic, if it is set.
+fn load_time(millis: &AtomicU64) -> Option<SystemTime> {
+    match millis.load(Relaxed) {
+        0 => None,
+        millis => Some(UNIX_EPOCH + Duration::from_millis(millis)),
+    }
+}
+
+/// Returns the number of milliseconds between the Unix epoch and the given time.
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
 }
@@ -27,11 +27,11 @@ pub async fn connect_nodes<T: Pea2Pea>(nodes: &[T], topology: Topology) -> io::R
         Topology::Line | Topology::Ring => {
             for i in 0..(count - 1) {
                 let addr = nodes[i + 1].node().listening_addr()?;
-                nodes[i].node().connect(addr).await?;
+                nodes[i].node().connect(&addr).await?;
             }
             if topology == Topology::Ring {
                 let addr = nodes[0].node().listening_addr()?;
-                nodes[count - 1].node().connect(addr).await?;
+                nodes[count - 1].node().connect(&addr).await?;
             }
         }
         Topology::Mesh => {
@@ -40,7 +40,7 @@ pub async fn connect_nodes<T: Pea2Pea>(nodes: &[T], topology: Topology) -> io::R
                 for (j, peer) in nodes.iter().enumerate() {
                     if i != j && connected_pairs.insert((i, j)) && connected_pairs.insert((j, i)) {
                         let addr = peer.node().listening_addr()?;
-                        nodes[i].node().connect(addr).await?;
+                        nodes[i].node().connect(&addr).await?;
                     }
                 }
             }
@@ -48,7 +48,7 @@ pub async fn connect_nodes<T: Pea2Pea>(nodes: &[T], topology: Topology) -> io::R
         Topology::Star => {
             let hub_addr = nodes[0].node().listening_addr()?;
             for node in nodes.iter().skip(1) {
-                node.node().connect(hub_addr).await?;
+                node.node().connect(&hub_addr).await?;
             }
         }
     }
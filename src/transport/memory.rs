@@ -0,0 +1,228 @@
+use crate::{
+    transport::{ReadHalf, Transport, TransportListener, TransportStream, WriteHalf},
+    PeerAddr,
+};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::{
+    io::{self as tokio_io, AsyncRead, AsyncWrite, DuplexStream, ReadBuf},
+    sync::{mpsc, Mutex as AsyncMutex},
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// The size of the in-memory buffer of each direction of a [`MemoryStream`].
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The first port assigned to listeners bound to port 0 and to outbound connections.
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// An in-process transport backed by [`tokio::io::duplex`]; it doesn't touch the network stack at all,
+/// which makes it a good fit for tests involving many nodes. The listening addresses are virtual and
+/// are only visible to the nodes sharing the same `MemoryTransport` (or its clones). Just like with TCP,
+/// the ports assigned to the dialing sides of connections remain in use until the connections are closed.
+#[derive(Clone, Default)]
+pub struct MemoryTransport(Arc<Mutex<Registry>>);
+
+/// Contains the virtual addresses registered with a [`MemoryTransport`].
+#[derive(Default)]
+struct Registry {
+    /// The listeners' addresses and the means to pass new streams to them.
+    listeners: HashMap<SocketAddr, mpsc::UnboundedSender<(MemoryStream, SocketAddr)>>,
+    /// The addresses of the dialing sides of the live connections.
+    connections: HashSet<SocketAddr>,
+    /// The last port assigned automatically.
+    last_port: u16,
+}
+
+impl Registry {
+    /// Checks whether the given address is used by a listener or by a live connection.
+    fn is_used(&self, addr: &SocketAddr) -> bool {
+        self.listeners.contains_key(addr) || self.connections.contains(addr)
+    }
+
+    /// Assigns an ephemeral port that is not used by any of the registered listeners or live connections;
+    /// returns an error if all of them are taken.
+    fn next_port(&mut self, ip: IpAddr) -> io::Result<u16> {
+        for _ in FIRST_EPHEMERAL_PORT..=u16::MAX {
+            self.last_port = if self.last_port < FIRST_EPHEMERAL_PORT || self.last_port == u16::MAX
+            {
+                FIRST_EPHEMERAL_PORT
+            } else {
+                self.last_port + 1
+            };
+            if !self.is_used(&SocketAddr::new(ip, self.last_port)) {
+                return Ok(self.last_port);
+            }
+        }
+
+        Err(io::ErrorKind::AddrNotAvailable.into())
+    }
+}
+
+/// Keeps the address of the dialing side of a connection in use until both of its streams are dropped.
+struct PortLease {
+    /// The address of the dialing side.
+    addr: SocketAddr,
+    /// The registry the address is registered in.
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Drop for PortLease {
+    fn drop(&mut self) {
+        self.registry.lock().connections.remove(&self.addr);
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn listen(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportListener>> {
+        let mut addr = addr.socket_addr().ok_or(io::ErrorKind::InvalidInput)?;
+        let mut registry = self.0.lock();
+
+        if addr.port() == 0 {
+            addr.set_port(registry.next_port(addr.ip())?);
+        } else if registry.is_used(&addr) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        registry.listeners.insert(addr, sender);
+
+        Ok(Box::new(MemoryListener {
+            addr,
+            receiver: AsyncMutex::new(receiver),
+            registry: Arc::clone(&self.0),
+        }))
+    }
+
+    async fn dial(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportStream>> {
+        let addr = addr.socket_addr().ok_or(io::ErrorKind::InvalidInput)?;
+
+        // the lock must not be held once the lease can be dropped
+        let (sender, local_addr) = {
+            let mut registry = self.0.lock();
+
+            let sender = registry
+                .listeners
+                .get(&addr)
+                .cloned()
+                .ok_or(io::ErrorKind::ConnectionRefused)?;
+
+            let local_addr = SocketAddr::new(addr.ip(), registry.next_port(addr.ip())?);
+            registry.connections.insert(local_addr);
+
+            (sender, local_addr)
+        };
+
+        let lease = Arc::new(PortLease {
+            addr: local_addr,
+            registry: Arc::clone(&self.0),
+        });
+        let (local, remote) = tokio_io::duplex(STREAM_BUFFER_SIZE);
+
+        let remote = MemoryStream {
+            inner: remote,
+            local_addr: addr,
+            _lease: Arc::clone(&lease),
+        };
+        sender
+            .send((remote, local_addr))
+            .map_err(|_| io::Error::from(io::ErrorKind::ConnectionRefused))?;
+
+        Ok(Box::new(MemoryStream {
+            inner: local,
+            local_addr,
+            _lease: lease,
+        }))
+    }
+}
+
+/// A listener bound to a virtual address registered with a [`MemoryTransport`].
+struct MemoryListener {
+    /// The listener's virtual address.
+    addr: SocketAddr,
+    /// Receives streams dialed by other nodes.
+    receiver: AsyncMutex<mpsc::UnboundedReceiver<(MemoryStream, SocketAddr)>>,
+    /// The registry the listener's address is registered in.
+    registry: Arc<Mutex<Registry>>,
+}
+
+#[async_trait]
+impl TransportListener for MemoryListener {
+    fn local_addr(&self) -> io::Result<PeerAddr> {
+        Ok(self.addr.into())
+    }
+
+    async fn accept(&self) -> io::Result<(Box<dyn TransportStream>, PeerAddr)> {
+        match self.receiver.lock().await.recv().await {
+            Some((stream, addr)) => Ok((Box::new(stream), addr.into())),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+impl Drop for MemoryListener {
+    fn drop(&mut self) {
+        // free the address, so that it can be bound to again
+        self.registry.lock().listeners.remove(&self.addr);
+    }
+}
+
+/// A stream between two nodes using a [`MemoryTransport`].
+struct MemoryStream {
+    /// The in-memory stream.
+    inner: DuplexStream,
+    /// The virtual address of the stream's own side.
+    local_addr: SocketAddr,
+    /// Keeps the address of the connection's dialing side in use.
+    _lease: Arc<PortLease>,
+}
+
+impl AsyncRead for MemoryStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MemoryStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+impl TransportStream for MemoryStream {
+    fn local_addr(&self) -> io::Result<PeerAddr> {
+        Ok(self.local_addr.into())
+    }
+
+    fn split(self: Box<Self>) -> (ReadHalf, WriteHalf) {
+        let (reader, writer) = tokio_io::split(*self);
+
+        (Box::new(reader), Box::new(writer))
+    }
+}
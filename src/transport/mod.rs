@@ -0,0 +1,84 @@
+//! Objects associated with the transports the node can use to establish connections.
+
+use crate::{ConnectionSide, PeerAddr};
+
+#[cfg(doc)]
+use crate::{protocols::Handshake, Config, Connection, Node};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use std::io;
+
+mod memory;
+mod tcp;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(unix)]
+mod unix;
+
+pub use memory::MemoryTransport;
+pub use tcp::TcpTransport;
+#[cfg(feature = "tls")]
+pub use tls::{rustls, TlsTransport};
+#[cfg(unix)]
+pub use unix::UnixTransport;
+
+/// The read half of a [`TransportStream`], owned by the [`Connection`] until the protocols take it.
+pub type ReadHalf = Box<dyn AsyncRead + Unpin + Send + Sync>;
+
+/// The write half of a [`TransportStream`], owned by the [`Connection`] until the protocols take it.
+pub type WriteHalf = Box<dyn AsyncWrite + Unpin + Send + Sync>;
+
+/// Specifies the means by which the node listens for inbound connections and establishes outbound ones;
+/// the [`TcpTransport`] is used by default, but any other stream-based transport can be provided via
+/// [`Node::with_transport`].
+#[async_trait]
+pub trait Transport: Send + Sync + 'static {
+    /// Creates a listener bound to the given address; it returns an [`io::ErrorKind::InvalidInput`] error if
+    /// the transport doesn't support that kind of [`PeerAddr`].
+    async fn listen(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportListener>>;
+
+    /// Establishes a connection with the given address; it returns an [`io::ErrorKind::InvalidInput`] error if
+    /// the transport doesn't support that kind of [`PeerAddr`].
+    async fn dial(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportStream>>;
+
+    /// Prepares a freshly established stream for use, e.g. by securing it; it is called for both inbound and
+    /// outbound connections (the `side` being the node's own one), before the [`Handshake`] (if enabled). It
+    /// is subject to the same time limit as the handshake ([`Config::max_handshake_time_ms`]).
+    ///
+    /// note: The default implementation returns the stream as-is.
+    async fn upgrade(
+        &self,
+        stream: Box<dyn TransportStream>,
+        _addr: &PeerAddr,
+        _side: ConnectionSide,
+    ) -> io::Result<Box<dyn TransportStream>> {
+        Ok(stream)
+    }
+}
+
+/// A listener created by [`Transport::listen`].
+#[async_trait]
+pub trait TransportListener: Send + Sync {
+    /// Returns the address the listener is bound to.
+    fn local_addr(&self) -> io::Result<PeerAddr>;
+
+    /// Accepts a new inbound connection, returning its stream and the address of the peer.
+    async fn accept(&self) -> io::Result<(Box<dyn TransportStream>, PeerAddr)>;
+}
+
+/// A bidirectional stream created by [`Transport::dial`] or [`TransportListener::accept`].
+pub trait TransportStream: AsyncRead + AsyncWrite + Unpin + Send + Sync {
+    /// Returns the local address of the stream.
+    fn local_addr(&self) -> io::Result<PeerAddr>;
+
+    /// Splits the stream into separately owned read and write halves.
+    fn split(self: Box<Self>) -> (ReadHalf, WriteHalf);
+
+    /// Returns the certificates presented by the peer, if the stream is secured with TLS.
+    #[cfg(feature = "tls")]
+    fn peer_certificates(&self) -> Option<Vec<rustls::pki_types::CertificateDer<'static>>> {
+        None
+    }
+}
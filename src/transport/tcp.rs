@@ -0,0 +1,53 @@
+use crate::{
+    transport::{ReadHalf, Transport, TransportListener, TransportStream, WriteHalf},
+    PeerAddr,
+};
+
+use async_trait::async_trait;
+use tokio::net::{TcpListener, TcpStream};
+
+use std::io;
+
+/// The default transport, based on TCP.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TcpTransport;
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn listen(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportListener>> {
+        let addr = addr.socket_addr().ok_or(io::ErrorKind::InvalidInput)?;
+
+        Ok(Box::new(TcpListener::bind(addr).await?))
+    }
+
+    async fn dial(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportStream>> {
+        let addr = addr.socket_addr().ok_or(io::ErrorKind::InvalidInput)?;
+
+        Ok(Box::new(TcpStream::connect(addr).await?))
+    }
+}
+
+#[async_trait]
+impl TransportListener for TcpListener {
+    fn local_addr(&self) -> io::Result<PeerAddr> {
+        TcpListener::local_addr(self).map(PeerAddr::from)
+    }
+
+    async fn accept(&self) -> io::Result<(Box<dyn TransportStream>, PeerAddr)> {
+        let (stream, addr) = TcpListener::accept(self).await?;
+
+        Ok((Box::new(stream), addr.into()))
+    }
+}
+
+impl TransportStream for TcpStream {
+    fn local_addr(&self) -> io::Result<PeerAddr> {
+        TcpStream::local_addr(self).map(PeerAddr::from)
+    }
+
+    fn split(self: Box<Self>) -> (ReadHalf, WriteHalf) {
+        let (reader, writer) = self.into_split();
+
+        (Box::new(reader), Box::new(writer))
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    transport::{ReadHalf, TcpTransport, Transport, TransportListener, TransportStream, WriteHalf},
+    ConnectionSide, PeerAddr,
+};
+
+#[cfg(doc)]
+use crate::Connection;
+
+use async_trait::async_trait;
+use tokio::io;
+use tokio_rustls::{
+    rustls::{
+        pki_types::{CertificateDer, ServerName},
+        ClientConfig, ServerConfig,
+    },
+    TlsAcceptor, TlsConnector, TlsStream,
+};
+
+pub use tokio_rustls::rustls;
+
+use std::sync::Arc;
+
+/// A transport securing the streams of another transport (TCP by default) with TLS; the TLS handshake
+/// is performed in [`Transport::upgrade`], i.e. before the [`Handshake`](crate::protocols::Handshake) (if
+/// enabled). The certificates and client authentication (mutual TLS) are specified via the provided
+/// [`ServerConfig`] and [`ClientConfig`], and the peer's certificates become available via
+/// [`Connection::peer_certificates`].
+#[derive(Clone)]
+pub struct TlsTransport<T = TcpTransport> {
+    /// The underlying transport.
+    inner: T,
+    /// Secures inbound connections.
+    acceptor: TlsAcceptor,
+    /// Secures outbound connections.
+    connector: TlsConnector,
+    /// The name used to verify the certificates of the peers the node connects to.
+    server_name: Option<ServerName<'static>>,
+}
+
+impl<T: Transport> TlsTransport<T> {
+    /// Creates a TLS transport on top of the given one; by default, the server certificates are
+    /// verified against the IP address the node connects to, so the addresses without an IP can
+    /// only be connected to once a server name is provided (see [`TlsTransport::with_server_name`]).
+    pub fn new(
+        inner: T,
+        server_config: Arc<ServerConfig>,
+        client_config: Arc<ClientConfig>,
+    ) -> Self {
+        Self {
+            inner,
+            acceptor: server_config.into(),
+            connector: client_config.into(),
+            server_name: None,
+        }
+    }
+
+    /// Makes the node verify the server certificates against the given name instead of the IP
+    /// address it connects to.
+    pub fn with_server_name(mut self, server_name: ServerName<'static>) -> Self {
+        self.server_name = Some(server_name);
+        self
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for TlsTransport<T> {
+    async fn listen(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportListener>> {
+        self.inner.listen(addr).await
+    }
+
+    async fn dial(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportStream>> {
+        self.inner.dial(addr).await
+    }
+
+    async fn upgrade(
+        &self,
+        stream: Box<dyn TransportStream>,
+        addr: &PeerAddr,
+        side: ConnectionSide,
+    ) -> io::Result<Box<dyn TransportStream>> {
+        let stream = self.inner.upgrade(stream, addr, side).await?;
+
+        let stream: TlsStream<_> = match side {
+            ConnectionSide::Initiator => {
+                let server_name = match (&self.server_name, addr.ip()) {
+                    (Some(server_name), _) => server_name.clone(),
+                    (None, Some(ip)) => ip.into(),
+                    (None, None) => return Err(io::ErrorKind::InvalidInput.into()),
+                };
+                self.connector.connect(server_name, stream).await?.into()
+            }
+            ConnectionSide::Responder => self.acceptor.accept(stream).await?.into(),
+        };
+
+        Ok(Box::new(stream))
+    }
+}
+
+impl TransportStream for TlsStream<Box<dyn TransportStream>> {
+    fn local_addr(&self) -> io::Result<PeerAddr> {
+        self.get_ref().0.local_addr()
+    }
+
+    fn split(self: Box<Self>) -> (ReadHalf, WriteHalf) {
+        let (reader, writer) = io::split(*self);
+
+        (Box::new(reader), Box::new(writer))
+    }
+
+    fn peer_certificates(&self) -> Option<Vec<CertificateDer<'static>>> {
+        self.get_ref()
+            .1
+            .peer_certificates()
+            .map(|certs| certs.to_vec())
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    transport::{ReadHalf, Transport, TransportListener, TransportStream, WriteHalf},
+    PeerAddr,
+};
+
+use async_trait::async_trait;
+use tokio::net::{UnixListener, UnixStream};
+
+use std::{
+    fs, io,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+};
+
+/// A transport based on Unix domain sockets; the peers are identified by the paths of their sockets
+/// ([`PeerAddr::Unix`]), except for the ones that connect from unnamed sockets (which is what the
+/// dialing sides use), whose connections are assigned distinct [`PeerAddr::UnnamedUnix`] numbers.
+///
+/// note: Since these addresses have no IP, the features based on IPs (e.g. the bans of IPs, the
+/// [`Cidr`](crate::Cidr)-based access control rules or [`Config::max_connections_per_ip`]) don't apply
+/// to them.
+///
+/// [`Config::max_connections_per_ip`]: crate::Config::max_connections_per_ip
+#[derive(Clone, Default)]
+pub struct UnixTransport {
+    /// The path the node's listener binds to.
+    listening_path: Option<PathBuf>,
+    /// The number of peers accepted from unnamed sockets so far; it is shared by the transport's clones.
+    num_unnamed: Arc<AtomicU64>,
+}
+
+impl UnixTransport {
+    /// Creates a transport whose listener (if enabled via [`Config::listener_ip`](crate::Config::listener_ip))
+    /// binds to the given socket path, unless a different one is requested (see [`Transport::listen`]).
+    pub fn with_listening_path<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            listening_path: Some(path.into()),
+            num_unnamed: Default::default(),
+        }
+    }
+}
+
+/// Removes the socket file at the given path if it was left behind by a listener that is gone (e.g. one
+/// that crashed), so that the path can be bound to again; a socket that is still in use is left alone.
+async fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => match UnixStream::connect(path).await {
+            Ok(_) => Err(io::ErrorKind::AddrInUse.into()),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+            Err(e) => Err(e),
+        },
+        // any other kind of file is left alone, so binding to its path fails
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl Transport for UnixTransport {
+    /// Binds to the given socket path if the address is a [`PeerAddr::Unix`] one, and to the listening path
+    /// otherwise (as the IP-based addresses derived from the [`Config`](crate::Config) are not applicable
+    /// to Unix domain sockets). A socket file left behind by a previous listener is removed first.
+    async fn listen(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportListener>> {
+        let path = match addr {
+            PeerAddr::Unix(path) => path.clone(),
+            PeerAddr::Ip(_) => self
+                .listening_path
+                .clone()
+                .ok_or(io::ErrorKind::AddrNotAvailable)?,
+            PeerAddr::UnnamedUnix(_) => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+
+        remove_stale_socket(&path).await?;
+        let listener = UnixListener::bind(&path)?;
+
+        Ok(Box::new(UnixSocketListener {
+            listener,
+            path,
+            num_unnamed: Arc::clone(&self.num_unnamed),
+        }))
+    }
+
+    async fn dial(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportStream>> {
+        let path = match addr {
+            PeerAddr::Unix(path) => path,
+            _ => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+
+        Ok(Box::new(UnixStream::connect(path).await?))
+    }
+}
+
+/// A listener bound to a Unix domain socket path.
+struct UnixSocketListener {
+    /// The underlying listener.
+    listener: UnixListener,
+    /// The socket path the listener is bound to.
+    path: PathBuf,
+    /// The number of peers accepted from unnamed sockets so far.
+    num_unnamed: Arc<AtomicU64>,
+}
+
+#[async_trait]
+impl TransportListener for UnixSocketListener {
+    fn local_addr(&self) -> io::Result<PeerAddr> {
+        Ok(PeerAddr::Unix(self.path.clone()))
+    }
+
+    async fn accept(&self) -> io::Result<(Box<dyn TransportStream>, PeerAddr)> {
+        let (stream, peer_addr) = self.listener.accept().await?;
+
+        let addr = if let Some(path) = peer_addr.as_pathname() {
+            PeerAddr::Unix(path.to_owned())
+        } else {
+            PeerAddr::UnnamedUnix(self.num_unnamed.fetch_add(1, Relaxed) + 1)
+        };
+
+        Ok((Box::new(stream), addr))
+    }
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        // remove the socket file, so that the path can be bound to again
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl TransportStream for UnixStream {
+    /// Returns the path the stream's own socket is bound to; the sockets of the dialing sides are unnamed,
+    /// so it returns an [`io::ErrorKind::AddrNotAvailable`] error for them.
+    fn local_addr(&self) -> io::Result<PeerAddr> {
+        match UnixStream::local_addr(self)?.as_pathname() {
+            Some(path) => Ok(PeerAddr::Unix(path.to_owned())),
+            None => Err(io::ErrorKind::AddrNotAvailable.into()),
+        }
+    }
+
+    fn split(self: Box<Self>) -> (ReadHalf, WriteHalf) {
+        let (reader, writer) = self.into_split();
+
+        (Box::new(reader), Box::new(writer))
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+use pea2pea::{protocols::Reading, BanTarget, Cidr, DisconnectReason, NodeEvent, Pea2Pea};
+
+use std::{io, net::IpAddr, time::Duration};
+
+#[test]
+fn cidr_parsing_and_matching() {
+    let net: Cidr = "10.1.0.0/16".parse().unwrap();
+    assert!(net.contains("10.1.2.3".parse().unwrap()));
+    assert!(!net.contains("10.2.0.1".parse().unwrap()));
+    assert!(!net.contains("::1".parse().unwrap()));
+
+    let net: Cidr = "fd00::/8".parse().unwrap();
+    assert!(net.contains("fd12::1".parse().unwrap()));
+    assert!(!net.contains("fe80::1".parse().unwrap()));
+
+    let any: Cidr = "0.0.0.0/0".parse().unwrap();
+    assert!(any.contains("192.168.0.1".parse().unwrap()));
+
+    let single: Cidr = "127.0.0.1".parse().unwrap();
+    assert_eq!(single.to_string(), "127.0.0.1/32");
+
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("10.0.0/8".parse::<Cidr>().is_err());
+}
+
+#[tokio::test]
+async fn access_control_rules() {
+    let nodes = common::start_nodes(2, None).await;
+    let (guard, peer) = (&nodes[0], &nodes[1]);
+    let peer_addr = peer.listening_addr().unwrap();
+
+    // only the documentation range is allowed
+    guard
+        .access_control()
+        .allow("192.0.2.0/24".parse().unwrap());
+    let err = guard.connect(&peer_addr).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+    // inbound connections are checked too
+    peer.connect(&guard.listening_addr().unwrap())
+        .await
+        .unwrap();
+    wait_until!(1, guard.stats().rejections() == 2);
+    assert_eq!(guard.num_connected(), 0);
+
+    // deny rules take precedence
+    guard.access_control().allow("127.0.0.0/8".parse().unwrap());
+    guard.access_control().deny("127.0.0.1".parse().unwrap());
+    assert!(guard.connect(&peer_addr).await.is_err());
+
+    assert!(guard.access_control().remove("127.0.0.1".parse().unwrap()));
+    guard.connect(&peer_addr).await.unwrap();
+}
+
+#[tokio::test]
+async fn bans() {
+    let alice = common::MessagingNode::new("alice").await;
+    let bob = common::MessagingNode::new("bob").await;
+    alice.enable_reading().await;
+    bob.enable_reading().await;
+    let bob_addr = bob.node().listening_addr().unwrap();
+    let mut alice_events = alice.node().subscribe_events();
+
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+
+    // banning a connected peer disconnects it
+    alice
+        .node()
+        .ban(&bob_addr, Duration::from_millis(100))
+        .await;
+    assert!(!alice.node().is_connected(&bob_addr));
+    loop {
+        if let NodeEvent::Disconnected { reason, .. } = alice_events.recv().await.unwrap() {
+            assert_eq!(reason, DisconnectReason::Banned);
+            break;
+        }
+    }
+
+    // the ban is observable and enforced
+    assert!(alice.node().known_peers().is_banned(&bob_addr));
+    assert!(alice
+        .node()
+        .known_peers()
+        .bans()
+        .contains_key(&BanTarget::Addr(bob_addr.clone())));
+    assert!(alice.node().connect(&bob_addr).await.is_err());
+
+    // the ban expires
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(alice.node().known_peers().bans().is_empty());
+    alice.node().connect(&bob_addr).await.unwrap();
+
+    // IP bans apply to inbound connections regardless of the port
+    let ip: IpAddr = "127.0.0.1".parse().unwrap();
+    bob.node().ban(ip, Duration::from_secs(60)).await;
+    wait_until!(1, bob.node().num_connected() == 0);
+    alice.node().disconnect(&bob_addr).await;
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().stats().rejections() == 1);
+    assert_eq!(bob.node().num_connected(), 0);
+
+    assert!(bob.node().known_peers().unban(ip));
+    assert!(!bob.node().known_peers().is_banned(&bob_addr));
+}
@@ -5,10 +5,11 @@ use rand::{distributions::Standard, rngs::SmallRng, Rng, SeedableRng};
 mod common;
 use pea2pea::{
     protocols::{Reading, Writing},
-    Config, Node, Pea2Pea,
+    transport::{MemoryTransport, TcpTransport},
+    Config, Node, Pea2Pea, PeerAddr, Transport,
 };
 
-use std::{convert::TryInto, io, net::SocketAddr, time::Instant};
+use std::{convert::TryInto, io, time::Instant};
 
 const NUM_MESSAGES: usize = 10_000;
 const MSG_SIZE: usize = 32 * 1024;
@@ -37,7 +38,7 @@ impl Reading for Sink {
 
     fn read_message<R: io::Read>(
         &self,
-        _source: SocketAddr,
+        _source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>> {
         let mut buf = [0u8; MSG_SIZE];
@@ -56,17 +57,18 @@ impl Reading for Sink {
         }
     }
 
-    async fn process_message(&self, _src: SocketAddr, _msg: Self::Message) -> io::Result<()> {
+    async fn process_message(&self, _src: PeerAddr, _msg: Self::Message) -> io::Result<()> {
         Ok(())
     }
 }
 
-async fn run_bench_scenario(sender_count: usize) -> f64 {
+async fn run_bench_scenario<T: Transport + Clone>(sender_count: usize, transport: T) -> f64 {
     let config = Config {
         outbound_queue_depth: NUM_MESSAGES,
         ..Default::default()
     };
-    let spammers = common::start_nodes(sender_count, Some(config)).await;
+    let spammers =
+        common::start_nodes_with_transport(sender_count, Some(config), transport.clone()).await;
     let spammers = spammers
         .into_iter()
         .map(common::MessagingNode)
@@ -81,14 +83,14 @@ async fn run_bench_scenario(sender_count: usize) -> f64 {
         max_connections: sender_count as u16,
         ..Default::default()
     };
-    let sink = Sink(Node::new(Some(config)).await.unwrap());
+    let sink = Sink(Node::with_transport(Some(config), transport).await.unwrap());
 
     sink.enable_reading().await;
 
     for spammer in &spammers {
         spammer
             .node()
-            .connect(sink.node().listening_addr().unwrap())
+            .connect(&sink.node().listening_addr().unwrap())
             .await
             .unwrap();
     }
@@ -99,10 +101,11 @@ async fn run_bench_scenario(sender_count: usize) -> f64 {
 
     let start = Instant::now();
     for spammer in spammers {
+        let sink_addr = sink_addr.clone();
         tokio::spawn(async move {
             for _ in 0..NUM_MESSAGES {
                 spammer
-                    .send_direct_message(sink_addr, RANDOM_BYTES.clone())
+                    .send_direct_message(&sink_addr, RANDOM_BYTES.clone())
                     .unwrap()
                     .await
                     .unwrap();
@@ -121,12 +124,10 @@ async fn run_bench_scenario(sender_count: usize) -> f64 {
     (bytes_received as f64) / (time_elapsed as f64 / 1000.0)
 }
 
-#[ignore]
-#[tokio::test(flavor = "multi_thread")]
-async fn bench_spam_to_one() {
+async fn bench_spam_to_one<T: Transport + Clone>(transport: T) {
     let mut results = Vec::with_capacity(4);
     for sender_count in &[1, 10, 20, 50, 100] {
-        let throughput = run_bench_scenario(*sender_count).await;
+        let throughput = run_bench_scenario(*sender_count, transport.clone()).await;
         println!(
             "throughput with {:>3} sender(s), 1 receiver: {}/s",
             sender_count,
@@ -138,3 +139,15 @@ async fn bench_spam_to_one() {
     let avg_throughput = results.iter().sum::<f64>() / results.len() as f64;
     println!("\naverage: {}/s", common::display_bytes(avg_throughput));
 }
+
+#[ignore]
+#[tokio::test(flavor = "multi_thread")]
+async fn bench_spam_to_one_tcp() {
+    bench_spam_to_one(TcpTransport).await;
+}
+
+#[ignore]
+#[tokio::test(flavor = "multi_thread")]
+async fn bench_spam_to_one_in_memory() {
+    bench_spam_to_one(MemoryTransport::default()).await;
+}
@@ -52,7 +52,7 @@ async fn broadcast_example() {
     for rando in &random_nodes {
         broadcaster
             .0
-            .connect(rando.node().listening_addr().unwrap())
+            .connect(&rando.node().listening_addr().unwrap())
             .await
             .unwrap();
     }
@@ -4,10 +4,10 @@ use peak_alloc::PeakAlloc;
 mod common;
 use pea2pea::{
     protocols::{Disconnect, Handshake, Reading, Writing},
-    Connection, Pea2Pea,
+    Connection, DisconnectReason, Pea2Pea, PeerAddr,
 };
 
-use std::{io, net::SocketAddr};
+use std::io;
 
 #[async_trait::async_trait]
 impl Handshake for common::MessagingNode {
@@ -19,7 +19,7 @@ impl Handshake for common::MessagingNode {
 
 #[async_trait::async_trait]
 impl Disconnect for common::MessagingNode {
-    async fn handle_disconnect(&self, _addr: SocketAddr) {
+    async fn handle_disconnect(&self, _addr: PeerAddr, _reason: DisconnectReason) {
         // nothing to do here, just using all protocols
     }
 }
@@ -67,7 +67,7 @@ async fn check_node_cleanups() {
         // this connection direction allows the collection of `KnownPeers` to remain empty
         temporary_node
             .node()
-            .connect(persistent_addr)
+            .connect(&persistent_addr)
             .await
             .unwrap();
         wait_until!(
@@ -75,16 +75,16 @@ async fn check_node_cleanups() {
             persistent_node.node().num_connected() == 1
                 && temporary_node.node().num_connected() == 1
         );
-        let temporary_addr = persistent_node.node().connected_addrs()[0];
+        let temporary_addr = persistent_node.node().connected_addrs()[0].clone();
 
         persistent_node
-            .send_direct_message(temporary_addr, Bytes::from(&b"herp"[..]))
+            .send_direct_message(&temporary_addr, Bytes::from(&b"herp"[..]))
             .unwrap()
             .await
             .unwrap();
 
         temporary_node
-            .send_direct_message(persistent_addr, Bytes::from(&b"derp"[..]))
+            .send_direct_message(&persistent_addr, Bytes::from(&b"derp"[..]))
             .unwrap()
             .await
             .unwrap();
@@ -5,13 +5,13 @@ use tracing::*;
 
 use pea2pea::{
     protocols::{Reading, Writing},
-    Config, Node, Pea2Pea,
+    transport::MemoryTransport,
+    Config, Node, Pea2Pea, PeerAddr, Transport,
 };
 
 use std::{
     convert::TryInto,
     io::{self, Read},
-    net::SocketAddr,
 };
 
 pub async fn start_nodes(count: usize, config: Option<Config>) -> Vec<Node> {
@@ -25,6 +25,23 @@ pub async fn start_nodes(count: usize, config: Option<Config>) -> Vec<Node> {
     nodes
 }
 
+pub async fn start_nodes_with_transport<T: Transport + Clone>(
+    count: usize,
+    config: Option<Config>,
+    transport: T,
+) -> Vec<Node> {
+    let mut nodes = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let node = Node::with_transport(config.clone(), transport.clone())
+            .await
+            .unwrap();
+        nodes.push(node);
+    }
+
+    nodes
+}
+
 #[derive(Clone)]
 pub struct InertNode(pub Node);
 
@@ -50,6 +67,14 @@ pub async fn start_inert_nodes(count: usize, config: Option<Config>) -> Vec<Iner
         .collect()
 }
 
+pub async fn start_inert_memory_nodes(count: usize, config: Option<Config>) -> Vec<InertNode> {
+    start_nodes_with_transport(count, config, MemoryTransport::default())
+        .await
+        .into_iter()
+        .map(InertNode)
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct MessagingNode(pub Node);
 
@@ -135,13 +160,13 @@ macro_rules! impl_messaging {
         impl Reading for $target {
             type Message = bytes::Bytes;
 
-            fn read_message<R: io::Read>(&self, _source: SocketAddr, reader: &mut R) -> io::Result<Option<Self::Message>> {
-                let vec = crate::common::read_len_prefixed_message::<R, 2>(reader)?;
+            fn read_message<R: io::Read>(&self, _source: &PeerAddr, reader: &mut R) -> io::Result<Option<Self::Message>> {
+                let vec = $crate::common::read_len_prefixed_message::<R, 2>(reader)?;
 
                 Ok(vec.map(bytes::Bytes::from))
             }
 
-            async fn process_message(&self, source: SocketAddr, _message: Self::Message) -> io::Result<()> {
+            async fn process_message(&self, source: PeerAddr, _message: Self::Message) -> io::Result<()> {
                 info!(parent: self.node().span(), "received a message from {}", source);
 
                 Ok(())
@@ -151,7 +176,7 @@ macro_rules! impl_messaging {
         impl Writing for $target {
             type Message = bytes::Bytes;
 
-            fn write_message<W: io::Write>(&self, _target: SocketAddr, payload: &Self::Message, writer: &mut W) -> io::Result<()> {
+            fn write_message<W: io::Write>(&self, _target: &PeerAddr, payload: &Self::Message, writer: &mut W) -> io::Result<()> {
                 writer.write_all(&(payload.len() as u16).to_le_bytes())?;
                 writer.write_all(payload)
             }
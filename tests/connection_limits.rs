@@ -0,0 +1,63 @@
+mod common;
+use pea2pea::{Config, Node};
+
+use std::io;
+
+#[tokio::test]
+async fn limits_inbound_and_outbound() {
+    let config = Config {
+        max_inbound: Some(1),
+        max_outbound: Some(1),
+        ..Default::default()
+    };
+    let hub = Node::new(Some(config)).await.unwrap();
+    let hub_addr = hub.listening_addr().unwrap();
+    let peers = common::start_nodes(4, None).await;
+
+    // inbound
+    peers[0].connect(&hub_addr).await.unwrap();
+    wait_until!(1, hub.num_connected() == 1);
+    peers[1].connect(&hub_addr).await.unwrap();
+    wait_until!(1, hub.stats().rejections() == 1);
+    assert_eq!(hub.num_connected(), 1);
+
+    // an inbound connection doesn't take an outbound slot
+    hub.connect(&peers[2].listening_addr().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(hub.num_connected(), 2);
+
+    // outbound
+    let err = hub
+        .connect(&peers[3].listening_addr().unwrap())
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(hub.stats().rejections(), 2);
+    assert_eq!(hub.num_connected(), 2);
+}
+
+#[tokio::test]
+async fn limits_per_ip() {
+    let config = Config {
+        max_connections_per_ip: Some(2),
+        ..Default::default()
+    };
+    let hub = Node::new(Some(config)).await.unwrap();
+    let hub_addr = hub.listening_addr().unwrap();
+    let peers = common::start_nodes(3, None).await;
+
+    // all the peers share the same IP
+    for peer in &peers {
+        peer.connect(&hub_addr).await.unwrap();
+    }
+    wait_until!(1, hub.stats().rejections() == 1);
+    wait_until!(1, hub.num_connected() == 2);
+
+    // the limit applies to outbound connections as well
+    assert!(hub
+        .connect(&peers[2].listening_addr().unwrap())
+        .await
+        .is_err());
+    assert_eq!(hub.stats().rejections(), 2);
+}
@@ -1,19 +1,21 @@
 use bytes::Bytes;
+use parking_lot::Mutex;
+use tracing::*;
 
 mod common;
 use pea2pea::{
     protocols::{Disconnect, Reading, Writing},
-    Pea2Pea,
+    DisconnectReason, Node, NodeEvent, Pea2Pea, PeerAddr,
 };
 
-use std::net::SocketAddr;
+use std::{io, sync::Arc};
 
 #[async_trait::async_trait]
 impl Disconnect for common::MessagingNode {
-    async fn handle_disconnect(&self, addr: SocketAddr) {
+    async fn handle_disconnect(&self, addr: PeerAddr, _reason: DisconnectReason) {
         let disconnect_message = Bytes::from("bye-bye!".as_bytes());
 
-        self.send_direct_message(addr, disconnect_message)
+        self.send_direct_message(&addr, disconnect_message)
             .unwrap()
             .await
             .unwrap();
@@ -33,13 +35,85 @@ async fn send_message_before_disconnect() {
 
     let connectee_addr = connectee.node().listening_addr().unwrap();
 
-    connector.node().connect(connectee_addr).await.unwrap();
+    connector.node().connect(&connectee_addr).await.unwrap();
 
     wait_until!(1, connectee.node().num_connected() == 1);
 
     assert_eq!(connectee.node().stats().received().0, 0);
 
-    connector.node().disconnect(connectee_addr).await;
+    connector.node().disconnect(&connectee_addr).await;
 
     wait_until!(1, connectee.node().stats().received().0 == 1);
 }
+
+#[derive(Clone)]
+struct ReasonNode {
+    node: Node,
+    reasons: Arc<Mutex<Vec<DisconnectReason>>>,
+}
+
+impl ReasonNode {
+    async fn new() -> Self {
+        let node = Self {
+            node: Node::new(None).await.unwrap(),
+            reasons: Default::default(),
+        };
+        node.enable_reading().await;
+        node.enable_writing().await;
+        node.enable_disconnect().await;
+
+        node
+    }
+}
+
+impl Pea2Pea for ReasonNode {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+#[async_trait::async_trait]
+impl Disconnect for ReasonNode {
+    async fn handle_disconnect(&self, _addr: PeerAddr, reason: DisconnectReason) {
+        self.reasons.lock().push(reason);
+    }
+}
+
+impl_messaging!(ReasonNode);
+
+#[tokio::test]
+async fn disconnect_reasons() {
+    let alice = ReasonNode::new().await;
+    let bob = ReasonNode::new().await;
+    let bob_addr = bob.node().listening_addr().unwrap();
+    let mut bob_events = bob.node().subscribe_events();
+
+    // alice disconnects on purpose, so bob sees the connection closed
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+    alice.node().disconnect(&bob_addr).await;
+    wait_until!(1, bob.node().num_connected() == 0);
+
+    assert_eq!(*alice.reasons.lock(), vec![DisconnectReason::Requested]);
+    assert_eq!(*bob.reasons.lock(), vec![DisconnectReason::PeerClosed]);
+
+    // the reason is also part of the related event
+    loop {
+        if let NodeEvent::Disconnected { reason, .. } = bob_events.recv().await.unwrap() {
+            assert_eq!(reason, DisconnectReason::PeerClosed);
+            break;
+        }
+    }
+
+    // bob shuts down
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+    bob.node().shut_down().await;
+    wait_until!(1, alice.node().num_connected() == 0);
+
+    assert_eq!(bob.reasons.lock().last(), Some(&DisconnectReason::ShutDown));
+    assert_eq!(
+        alice.reasons.lock().last(),
+        Some(&DisconnectReason::PeerClosed)
+    );
+}
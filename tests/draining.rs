@@ -0,0 +1,259 @@
+use bytes::Bytes;
+use parking_lot::Mutex;
+use tokio::{io::AsyncReadExt, net::TcpListener, time::sleep};
+use tracing::*;
+
+mod common;
+use pea2pea::{
+    protocols::{Disconnect, Reading, Writing},
+    Config, DisconnectReason, Node, Pea2Pea, PeerAddr,
+};
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering::*},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const NUM_MESSAGES: usize = 64;
+const MSG_SIZE: usize = 60_000;
+
+fn draining_config(name: &str, drain_timeout_ms: u64) -> Config {
+    Config {
+        name: Some(name.into()),
+        drain_timeout_ms: Some(drain_timeout_ms),
+        ..Default::default()
+    }
+}
+
+// queues a lot of data for a raw peer and closes the connection using the given method;
+// returns the number of bytes received by the peer before it encountered an EOF
+async fn deliver_and_close(shut_down: bool) -> usize {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener_addr: PeerAddr = listener.local_addr().unwrap().into();
+
+    let reader = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).await.unwrap();
+
+        received.len()
+    });
+
+    let config = Config {
+        outbound_queue_depth: NUM_MESSAGES,
+        ..draining_config("sender", 5_000)
+    };
+    let sender = common::MessagingNode(Node::new(Some(config)).await.unwrap());
+    sender.enable_writing().await;
+    sender.node().connect(&listener_addr).await.unwrap();
+
+    let payload = Bytes::from(vec![0u8; MSG_SIZE]);
+    for _ in 0..NUM_MESSAGES {
+        sender
+            .send_direct_message(&listener_addr, payload.clone())
+            .unwrap();
+    }
+
+    if shut_down {
+        sender.node().shut_down().await;
+    } else {
+        assert!(sender.node().disconnect(&listener_addr).await);
+
+        // no new messages are accepted after a disconnect
+        assert!(sender
+            .send_direct_message(&listener_addr, payload.clone())
+            .is_err());
+    }
+
+    reader.await.unwrap()
+}
+
+#[tokio::test]
+async fn disconnect_flushes_outbound_queue() {
+    assert_eq!(
+        deliver_and_close(false).await,
+        NUM_MESSAGES * (2 + MSG_SIZE)
+    );
+}
+
+#[tokio::test]
+async fn shut_down_flushes_outbound_queue() {
+    assert_eq!(deliver_and_close(true).await, NUM_MESSAGES * (2 + MSG_SIZE));
+}
+
+// a node that processes messages slowly, and disconnects from peers that say goodbye; it can't send
+// messages that say "fail"
+#[derive(Clone)]
+struct SlowNode {
+    node: Node,
+    processing_time: Duration,
+    processed: Arc<AtomicUsize>,
+    // the number of processed messages seen by the Disconnect protocol
+    processed_at_disconnect: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Pea2Pea for SlowNode {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+#[async_trait::async_trait]
+impl Reading for SlowNode {
+    type Message = Bytes;
+
+    fn read_message<R: io::Read>(
+        &self,
+        _source: &PeerAddr,
+        reader: &mut R,
+    ) -> io::Result<Option<Self::Message>> {
+        let vec = common::read_len_prefixed_message::<R, 2>(reader)?;
+
+        Ok(vec.map(Bytes::from))
+    }
+
+    async fn process_message(&self, source: PeerAddr, message: Self::Message) -> io::Result<()> {
+        if &message[..] == b"bye" {
+            self.node().disconnect(&source).await;
+        } else {
+            sleep(self.processing_time).await;
+            self.processed.fetch_add(1, SeqCst);
+            info!(parent: self.node().span(), "processed a message from {}", source);
+        }
+
+        Ok(())
+    }
+}
+
+impl Writing for SlowNode {
+    type Message = Bytes;
+
+    fn write_message<W: io::Write>(
+        &self,
+        _target: &PeerAddr,
+        payload: &Self::Message,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        if &payload[..] == b"fail" {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        writer.write_all(&(payload.len() as u16).to_le_bytes())?;
+        writer.write_all(payload)
+    }
+}
+
+#[async_trait::async_trait]
+impl Disconnect for SlowNode {
+    async fn handle_disconnect(&self, _addr: PeerAddr, reason: DisconnectReason) {
+        self.processed_at_disconnect
+            .lock()
+            .push(self.processed.load(SeqCst));
+
+        // keep the disconnect caused by a failed write pending for a while
+        if matches!(reason, DisconnectReason::WriteError(_)) {
+            sleep(Duration::from_secs(2)).await;
+        }
+    }
+}
+
+async fn slow_node_and_peer(
+    processing_time: Duration,
+    drain_timeout_ms: u64,
+) -> (SlowNode, common::MessagingNode) {
+    let slow = SlowNode {
+        node: Node::new(Some(draining_config("slow", drain_timeout_ms)))
+            .await
+            .unwrap(),
+        processing_time,
+        processed: Default::default(),
+        processed_at_disconnect: Default::default(),
+    };
+    slow.enable_reading().await;
+    slow.enable_writing().await;
+    slow.enable_disconnect().await;
+    let slow_addr = slow.node().listening_addr().unwrap();
+
+    let peer = common::MessagingNode::new("peer").await;
+    peer.enable_reading().await;
+    peer.enable_writing().await;
+    peer.node().connect(&slow_addr).await.unwrap();
+    wait_until!(1, slow.node().num_connected() == 1);
+
+    (slow, peer)
+}
+
+#[tokio::test]
+async fn disconnect_waits_for_processing() {
+    let (slow, peer) = slow_node_and_peer(Duration::from_millis(200), 5_000).await;
+    let slow_addr = slow.node().listening_addr().unwrap();
+    let peer_addr = slow.node().connected_addrs()[0].clone();
+
+    peer.send_direct_message(&slow_addr, Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, slow.node().stats().received().0 == 1);
+
+    // the message that is being processed is not interrupted
+    assert!(slow.node().disconnect(&peer_addr).await);
+    assert_eq!(slow.processed.load(SeqCst), 1);
+    assert_eq!(slow.node().num_connected(), 0);
+
+    // the Disconnect protocol is triggered once the connection is drained
+    assert_eq!(*slow.processed_at_disconnect.lock(), vec![1]);
+}
+
+#[tokio::test]
+async fn drain_after_failed_write() {
+    let (slow, _peer) = slow_node_and_peer(Duration::from_millis(1), 5_000).await;
+    let peer_addr = slow.node().connected_addrs()[0].clone();
+
+    // the failed write causes a disconnect that takes a while
+    assert!(!slow
+        .send_direct_message(&peer_addr, Bytes::from_static(b"fail"))
+        .unwrap()
+        .await
+        .unwrap());
+
+    // there is no need to wait for the writer task that has already quit
+    let start = Instant::now();
+    assert!(slow.node().disconnect(&peer_addr).await);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn drain_timeout_is_respected() {
+    let (slow, peer) = slow_node_and_peer(Duration::from_secs(60), 100).await;
+    let slow_addr = slow.node().listening_addr().unwrap();
+    let peer_addr = slow.node().connected_addrs()[0].clone();
+
+    peer.send_direct_message(&slow_addr, Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, slow.node().stats().received().0 == 1);
+
+    let start = Instant::now();
+    assert!(slow.node().disconnect(&peer_addr).await);
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(slow.processed.load(SeqCst), 0);
+    assert_eq!(slow.node().num_connected(), 0);
+}
+
+#[tokio::test]
+async fn disconnect_while_processing() {
+    let (slow, peer) = slow_node_and_peer(Duration::from_millis(1), 5_000).await;
+    let slow_addr = slow.node().listening_addr().unwrap();
+
+    // the node disconnects from the peer while processing its message
+    peer.send_direct_message(&slow_addr, Bytes::from_static(b"bye"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, slow.node().num_connected() == 0);
+    wait_until!(1, peer.node().num_connected() == 0);
+}
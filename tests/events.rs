@@ -0,0 +1,174 @@
+use tokio::{sync::broadcast, time::timeout};
+
+use pea2pea::{
+    protocols::Handshake, Config, Connection, ConnectionSide, DisconnectReason, Node, NodeEvent,
+    Pea2Pea,
+};
+
+use std::{io, time::Duration};
+
+#[derive(Clone)]
+struct EventfulNode {
+    node: Node,
+    reject_handshakes: bool,
+}
+
+impl EventfulNode {
+    async fn new(config: Option<Config>, reject_handshakes: bool) -> Self {
+        let node = Self {
+            node: Node::new(config).await.unwrap(),
+            reject_handshakes,
+        };
+        node.enable_handshake().await;
+
+        node
+    }
+}
+
+impl Pea2Pea for EventfulNode {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+#[async_trait::async_trait]
+impl Handshake for EventfulNode {
+    async fn perform_handshake(&self, conn: Connection) -> io::Result<Connection> {
+        if self.reject_handshakes {
+            Err(io::ErrorKind::PermissionDenied.into())
+        } else {
+            Ok(conn)
+        }
+    }
+}
+
+async fn next_event(events: &mut broadcast::Receiver<NodeEvent>) -> NodeEvent {
+    timeout(Duration::from_secs(1), events.recv())
+        .await
+        .expect("no event was emitted in time")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn events_connection_lifecycle() {
+    let alice = EventfulNode::new(None, false).await;
+    let bob = EventfulNode::new(None, false).await;
+    let mut alice_events = alice.node().subscribe_events();
+    let mut bob_events = bob.node().subscribe_events();
+
+    let bob_addr = bob.node().listening_addr().unwrap();
+    alice.node().connect(&bob_addr).await.unwrap();
+
+    let side = ConnectionSide::Responder;
+    let addr = bob_addr.clone();
+    assert_eq!(
+        next_event(&mut alice_events).await,
+        NodeEvent::Connecting {
+            addr: addr.clone(),
+            side
+        }
+    );
+    assert_eq!(
+        next_event(&mut alice_events).await,
+        NodeEvent::HandshakeStarted { addr: addr.clone() }
+    );
+    assert_eq!(
+        next_event(&mut alice_events).await,
+        NodeEvent::Connected {
+            addr: addr.clone(),
+            side
+        }
+    );
+
+    let side = ConnectionSide::Initiator;
+    let addr = match next_event(&mut bob_events).await {
+        NodeEvent::Connecting { addr, side: s } if s == side => addr,
+        event => panic!("unexpected event: {:?}", event),
+    };
+    assert_eq!(
+        next_event(&mut bob_events).await,
+        NodeEvent::HandshakeStarted { addr: addr.clone() }
+    );
+    assert_eq!(
+        next_event(&mut bob_events).await,
+        NodeEvent::Connected {
+            addr: addr.clone(),
+            side
+        }
+    );
+
+    assert!(bob.node().disconnect(&addr).await);
+    assert_eq!(
+        next_event(&mut bob_events).await,
+        NodeEvent::Disconnected {
+            addr: addr.clone(),
+            reason: DisconnectReason::Requested
+        }
+    );
+}
+
+#[tokio::test]
+async fn events_handshake_failure() {
+    let alice = EventfulNode::new(None, true).await;
+    let bob = EventfulNode::new(None, false).await;
+    let mut alice_events = alice.node().subscribe_events();
+
+    let addr = bob.node().listening_addr().unwrap();
+    assert!(alice.node().connect(&addr).await.is_err());
+
+    let error = io::ErrorKind::PermissionDenied;
+    assert_eq!(
+        next_event(&mut alice_events).await,
+        NodeEvent::Connecting {
+            addr: addr.clone(),
+            side: ConnectionSide::Responder
+        }
+    );
+    assert_eq!(
+        next_event(&mut alice_events).await,
+        NodeEvent::HandshakeStarted { addr: addr.clone() }
+    );
+    assert_eq!(
+        next_event(&mut alice_events).await,
+        NodeEvent::HandshakeFailed {
+            addr: addr.clone(),
+            error
+        }
+    );
+    assert_eq!(
+        next_event(&mut alice_events).await,
+        NodeEvent::ConnectionFailed {
+            addr: addr.clone(),
+            error
+        }
+    );
+}
+
+#[tokio::test]
+async fn events_rejection() {
+    let config = Config {
+        max_connections: 0,
+        ..Default::default()
+    };
+    let alice = EventfulNode::new(None, false).await;
+    let bob = EventfulNode::new(Some(config), false).await;
+    let mut bob_events = bob.node().subscribe_events();
+
+    // inbound
+    let _ = alice
+        .node()
+        .connect(&bob.node().listening_addr().unwrap())
+        .await;
+    assert!(matches!(
+        next_event(&mut bob_events).await,
+        NodeEvent::Rejected { .. }
+    ));
+
+    // outbound
+    let addr = alice.node().listening_addr().unwrap();
+    assert!(bob.node().connect(&addr).await.is_err());
+    assert_eq!(
+        next_event(&mut bob_events).await,
+        NodeEvent::Rejected { addr: addr.clone() }
+    );
+}
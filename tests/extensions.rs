@@ -0,0 +1,175 @@
+use bytes::Bytes;
+use parking_lot::Mutex;
+use tracing::*;
+
+mod common;
+use pea2pea::{
+    protocols::{Handshake, Reading, Writing},
+    Connection, Extensions, Node, Pea2Pea, PeerAddr,
+};
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering::*},
+        Arc,
+    },
+};
+
+// the number of messages received via a connection
+#[derive(Default)]
+struct MessageCount(Mutex<usize>);
+
+// sets the flag once dropped
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, SeqCst);
+    }
+}
+
+#[derive(Clone)]
+struct CountingNode {
+    node: Node,
+    dropped: Arc<AtomicBool>,
+}
+
+impl Pea2Pea for CountingNode {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+#[async_trait::async_trait]
+impl Handshake for CountingNode {
+    async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
+        conn.extensions.insert(MessageCount::default());
+        conn.extensions.insert(DropFlag(Arc::clone(&self.dropped)));
+
+        Ok(conn)
+    }
+}
+
+#[async_trait::async_trait]
+impl Reading for CountingNode {
+    type Message = Bytes;
+
+    fn read_message<R: io::Read>(
+        &self,
+        _source: &PeerAddr,
+        reader: &mut R,
+    ) -> io::Result<Option<Self::Message>> {
+        let vec = common::read_len_prefixed_message::<R, 2>(reader)?;
+
+        Ok(vec.map(Bytes::from))
+    }
+
+    async fn process_message(&self, source: PeerAddr, _message: Self::Message) -> io::Result<()> {
+        let count = self
+            .node()
+            .connection_extension::<MessageCount>(&source)
+            .unwrap();
+        *count.0.lock() += 1;
+        debug!(parent: self.node().span(), "counted a message from {}", source);
+
+        Ok(())
+    }
+}
+
+impl Writing for CountingNode {
+    type Message = Bytes;
+
+    fn write_message<W: io::Write>(
+        &self,
+        _target: &PeerAddr,
+        payload: &Self::Message,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        writer.write_all(&(payload.len() as u16).to_le_bytes())?;
+        writer.write_all(payload)
+    }
+}
+
+#[tokio::test]
+async fn connection_extensions() {
+    let counter = CountingNode {
+        node: Node::new(None).await.unwrap(),
+        dropped: Default::default(),
+    };
+    counter.enable_handshake().await;
+    counter.enable_reading().await;
+    counter.enable_writing().await;
+    let counter_addr = counter.node().listening_addr().unwrap();
+
+    let peer = common::MessagingNode::new("peer").await;
+    peer.enable_reading().await;
+    peer.enable_writing().await;
+    peer.node().connect(&counter_addr).await.unwrap();
+    wait_until!(1, counter.node().num_connected() == 1);
+    let peer_addr = counter.node().connected_addrs()[0].clone();
+
+    for _ in 0..3 {
+        peer.send_direct_message(&counter_addr, Bytes::from_static(b"hello"))
+            .unwrap()
+            .await
+            .unwrap();
+    }
+    wait_until!(1, counter.node().stats().received().0 == 3);
+
+    // the extensions populated during the handshake are available afterwards
+    let info = counter.node().connection_info(&peer_addr).unwrap();
+    assert_eq!(info.addr, peer_addr);
+    assert_eq!(info.id, counter.node().connection_id(&peer_addr).unwrap());
+    assert_eq!(info.extensions.len(), 2);
+    let count = info.extensions.get_shared::<MessageCount>().unwrap();
+    wait_until!(1, *count.0.lock() == 3);
+    drop(info);
+
+    // single values can be obtained without cloning the whole map
+    let shared = counter
+        .node()
+        .connection_extension::<MessageCount>(&peer_addr)
+        .unwrap();
+    assert!(Arc::ptr_eq(&count, &shared));
+    assert!(counter
+        .node()
+        .connection_extension::<u32>(&peer_addr)
+        .is_none());
+
+    // the extensions are dropped along with the connection
+    assert!(!counter.dropped.load(SeqCst));
+    assert!(counter.node().disconnect(&peer_addr).await);
+    assert!(counter.dropped.load(SeqCst));
+    assert!(counter.node().connection_info(&peer_addr).is_none());
+    assert!(counter
+        .node()
+        .connection_extension::<MessageCount>(&peer_addr)
+        .is_none());
+}
+
+#[test]
+fn extensions_type_map() {
+    let mut extensions = Extensions::default();
+    assert!(extensions.is_empty());
+
+    assert!(extensions.insert(1u32).is_none());
+    assert!(extensions.insert("one").is_none());
+    assert_eq!(extensions.insert(2u32).as_deref(), Some(&1));
+
+    assert_eq!(extensions.get::<u32>(), Some(&2));
+    assert_eq!(extensions.get::<&str>(), Some(&"one"));
+    assert!(extensions.get::<u64>().is_none());
+
+    // clones share the values
+    let clone = extensions.clone();
+    assert!(Arc::ptr_eq(
+        &extensions.get_shared::<u32>().unwrap(),
+        &clone.get_shared::<u32>().unwrap()
+    ));
+
+    assert_eq!(extensions.remove::<u32>().as_deref(), Some(&2));
+    assert!(!extensions.contains::<u32>());
+    assert!(clone.contains::<u32>());
+    assert_eq!(extensions.len(), 1);
+}
@@ -0,0 +1,282 @@
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+mod common;
+use pea2pea::{
+    protocols::{Handshake, Reading, Writing},
+    BanTarget, Config, Connection, DisconnectReason, FailurePolicy, Node, NodeEvent, Pea2Pea,
+    PeerAddr,
+};
+
+use std::{io, time::Duration};
+
+// a node that considers every message other than "ok" to be a failure
+#[derive(Clone)]
+struct PickyNode(Node);
+
+impl Pea2Pea for PickyNode {
+    fn node(&self) -> &Node {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl Reading for PickyNode {
+    type Message = Bytes;
+
+    fn read_message<R: io::Read>(
+        &self,
+        _source: &PeerAddr,
+        reader: &mut R,
+    ) -> io::Result<Option<Self::Message>> {
+        let vec = common::read_len_prefixed_message::<R, 2>(reader)?;
+
+        Ok(vec.map(Bytes::from))
+    }
+
+    async fn process_message(&self, _source: PeerAddr, message: Self::Message) -> io::Result<()> {
+        if &message[..] == b"ok" {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::InvalidData.into())
+        }
+    }
+}
+
+// only accepts peers that greet it with a 1
+#[async_trait::async_trait]
+impl Handshake for PickyNode {
+    async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
+        if conn.reader().read_u8().await? == 1 {
+            Ok(conn)
+        } else {
+            Err(io::ErrorKind::InvalidData.into())
+        }
+    }
+}
+
+impl Writing for PickyNode {
+    type Message = Bytes;
+
+    fn write_message<W: io::Write>(
+        &self,
+        _target: &PeerAddr,
+        payload: &Self::Message,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        writer.write_all(&(payload.len() as u16).to_le_bytes())?;
+        writer.write_all(payload)
+    }
+}
+
+// note: both Reading and Writing are enabled, as otherwise the related halves of the connections
+// would be dropped, causing the peers to disconnect
+async fn picky_node(policy: FailurePolicy) -> PickyNode {
+    let config = Config {
+        failure_policy: Some(policy),
+        ..Default::default()
+    };
+    let node = PickyNode(Node::new(Some(config)).await.unwrap());
+    node.enable_reading().await;
+    node.enable_writing().await;
+
+    node
+}
+
+#[tokio::test]
+async fn failure_policy_disconnects_and_bans() {
+    let picky = picky_node(FailurePolicy {
+        max_failures: 3,
+        ban_duration_ms: Some(60_000),
+        ..Default::default()
+    })
+    .await;
+    let picky_addr = picky.node().listening_addr().unwrap();
+    let mut picky_events = picky.node().subscribe_events();
+
+    let sender = common::MessagingNode::new("sender").await;
+    sender.enable_writing().await;
+    sender.node().connect(&picky_addr).await.unwrap();
+    wait_until!(1, picky.node().num_connected() == 1);
+    let sender_addr = picky.node().connected_addrs()[0].clone();
+
+    for msg in ["bad", "ok", "bad"] {
+        sender
+            .send_direct_message(&picky_addr, Bytes::from(msg))
+            .unwrap()
+            .await
+            .unwrap();
+    }
+    wait_until!(
+        1,
+        picky
+            .node()
+            .known_peers()
+            .get(&sender_addr)
+            .map(|stats| stats.failures())
+            == Some(2)
+    );
+    assert!(picky.node().is_connected(&sender_addr));
+
+    // the third failure exceeds the limit
+    sender
+        .send_direct_message(&picky_addr, Bytes::from("bad"))
+        .unwrap()
+        .await
+        .unwrap();
+    loop {
+        if let NodeEvent::Disconnected { reason, .. } = picky_events.recv().await.unwrap() {
+            assert_eq!(reason, DisconnectReason::TooManyFailures);
+            break;
+        }
+    }
+
+    // the peer initiated the connection, so its IP is banned
+    assert!(picky.node().known_peers().is_banned(&sender_addr));
+    assert!(picky.node().connect(&sender_addr).await.is_err());
+}
+
+#[tokio::test]
+async fn failure_policy_window_and_decay() {
+    let picky = picky_node(FailurePolicy {
+        max_failures: 2,
+        window_ms: 300,
+        decay_ms: Some(100),
+        ban_duration_ms: None,
+    })
+    .await;
+    let peer = common::MessagingNode::new("peer").await;
+    peer.enable_reading().await;
+    peer.enable_writing().await;
+    let peer_addr = peer.node().listening_addr().unwrap();
+
+    picky.node().connect(&peer_addr).await.unwrap();
+
+    // the failure falls out of the window
+    picky.node().register_failure(&peer_addr);
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    picky.node().register_failure(&peer_addr);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(picky.node().is_connected(&peer_addr));
+
+    // the failure is forgiven due to the decay, even though it's still within the window
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    picky.node().register_failure(&peer_addr);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(picky.node().is_connected(&peer_addr));
+
+    // two failures in quick succession exceed the limit
+    picky.node().register_failure(&peer_addr);
+    wait_until!(1, !picky.node().is_connected(&peer_addr));
+
+    // no ban was configured
+    assert!(!picky.node().known_peers().is_banned(&peer_addr));
+    assert_eq!(
+        picky
+            .node()
+            .known_peers()
+            .get(&peer_addr)
+            .unwrap()
+            .failures(),
+        4
+    );
+}
+
+#[tokio::test]
+async fn failure_policy_handshake_failure() {
+    let picky = picky_node(FailurePolicy {
+        max_failures: 1,
+        ban_duration_ms: Some(60_000),
+        ..Default::default()
+    })
+    .await;
+    picky.enable_handshake().await;
+    let picky_addr = picky.node().listening_addr().unwrap();
+
+    let mut stream = TcpStream::connect(picky_addr.socket_addr().unwrap())
+        .await
+        .unwrap();
+    stream.write_u8(0).await.unwrap();
+    let peer_addr: PeerAddr = stream.local_addr().unwrap().into();
+
+    // the peer initiated the connection, so its IP is banned
+    wait_until!(1, picky.node().known_peers().is_banned(&peer_addr));
+    assert!(picky
+        .node()
+        .known_peers()
+        .bans()
+        .contains_key(&BanTarget::Ip(peer_addr.ip().unwrap())));
+}
+
+#[tokio::test]
+async fn failure_policy_reconnecting_peer() {
+    // every connection registers 2 failures: the invalid message and the peer closing the connection
+    let picky = picky_node(FailurePolicy {
+        max_failures: 5,
+        ban_duration_ms: Some(60_000),
+        ..Default::default()
+    })
+    .await;
+    let picky_addr = picky.node().listening_addr().unwrap();
+    let mut picky_events = picky.node().subscribe_events();
+
+    let sender = common::MessagingNode::new("sender").await;
+    sender.enable_reading().await;
+    sender.enable_writing().await;
+
+    // the peer reconnects from a new port every time
+    for _ in 0..2 {
+        sender.node().connect(&picky_addr).await.unwrap();
+        wait_until!(1, picky.node().num_connected() == 1);
+        let sender_addr = picky.node().connected_addrs()[0].clone();
+
+        sender
+            .send_direct_message(&picky_addr, Bytes::from("bad"))
+            .unwrap()
+            .await
+            .unwrap();
+        wait_until!(
+            1,
+            picky
+                .node()
+                .known_peers()
+                .get(&sender_addr)
+                .map(|stats| stats.failures())
+                == Some(1)
+        );
+
+        sender.node().disconnect(&picky_addr).await;
+        wait_until!(1, picky.node().num_connected() == 0);
+    }
+
+    // the failures of the previous connections still count
+    sender.node().connect(&picky_addr).await.unwrap();
+    wait_until!(1, picky.node().num_connected() == 1);
+    let sender_addr = picky.node().connected_addrs()[0].clone();
+    sender
+        .send_direct_message(&picky_addr, Bytes::from("bad"))
+        .unwrap()
+        .await
+        .unwrap();
+    timeout(Duration::from_secs(1), async {
+        loop {
+            if let NodeEvent::Disconnected { reason, .. } = picky_events.recv().await.unwrap() {
+                if reason == DisconnectReason::TooManyFailures {
+                    break;
+                }
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(picky
+        .node()
+        .known_peers()
+        .bans()
+        .contains_key(&BanTarget::Ip(sender_addr.ip().unwrap())));
+}
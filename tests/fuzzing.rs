@@ -36,7 +36,7 @@ async fn fuzzing() {
     let sender = common::MessagingNode(Node::new(Some(config)).await.unwrap());
     sender.enable_writing().await;
 
-    sender.node().connect(receiver_addr).await.unwrap();
+    sender.node().connect(&receiver_addr).await.unwrap();
 
     wait_until!(1, receiver.node().num_connected() == 1);
 
@@ -51,7 +51,7 @@ async fn fuzzing() {
         let random_payload: Vec<u8> = (&mut rng).sample_iter(Standard).take(random_len).collect();
 
         sender
-            .send_direct_message(receiver_addr, random_payload.into())
+            .send_direct_message(&receiver_addr, random_payload.into())
             .unwrap()
             .await
             .unwrap();
@@ -103,7 +103,7 @@ async fn problem_combination() {
 
     sender
         .node()
-        .connect(receiver.node().listening_addr().unwrap())
+        .connect(&receiver.node().listening_addr().unwrap())
         .await
         .unwrap();
 
@@ -119,7 +119,7 @@ async fn problem_combination() {
 
         sender
             .send_direct_message(
-                receiver.node().listening_addr().unwrap(),
+                &receiver.node().listening_addr().unwrap(),
                 random_payload.into(),
             )
             .unwrap()
@@ -9,10 +9,10 @@ use tracing::*;
 mod common;
 use pea2pea::{
     protocols::{Handshake, Reading, Writing},
-    Config, Connection, ConnectionSide, Node, Pea2Pea,
+    Config, Connection, ConnectionSide, Node, Pea2Pea, PeerAddr,
 };
 
-use std::{collections::HashMap, convert::TryInto, io, net::SocketAddr, sync::Arc};
+use std::{collections::HashMap, convert::TryInto, io, sync::Arc};
 
 #[derive(Debug)]
 enum HandshakeMsg {
@@ -55,7 +55,7 @@ struct NoncePair(u64, u64); // (mine, peer's)
 #[derive(Clone)]
 struct SecureishNode {
     node: Node,
-    handshakes: Arc<RwLock<HashMap<SocketAddr, NoncePair>>>,
+    handshakes: Arc<RwLock<HashMap<PeerAddr, NoncePair>>>,
 }
 
 impl Pea2Pea for SecureishNode {
@@ -124,7 +124,9 @@ impl Handshake for SecureishNode {
         };
 
         // register the handshake nonce
-        self.handshakes.write().insert(conn.addr, nonce_pair);
+        self.handshakes
+            .write()
+            .insert(conn.addr.clone(), nonce_pair);
 
         Ok(conn)
     }
@@ -164,7 +166,7 @@ async fn handshake_example() {
 
     initiator
         .node()
-        .connect(responder.node().listening_addr().unwrap())
+        .connect(&responder.node().listening_addr().unwrap())
         .await
         .unwrap();
 
@@ -205,14 +207,14 @@ async fn no_handshake_no_messaging() {
 
     initiator
         .node()
-        .connect(responder.node().listening_addr().unwrap())
+        .connect(&responder.node().listening_addr().unwrap())
         .await
         .unwrap();
 
     let message = common::prefix_with_len(2, b"this won't get through, as there was no handshake");
 
     initiator
-        .send_direct_message(responder.node().listening_addr().unwrap(), message)
+        .send_direct_message(&responder.node().listening_addr().unwrap(), message)
         .unwrap()
         .await
         .unwrap();
@@ -255,7 +257,7 @@ async fn hung_handshake_fails() {
     // the connection attempt should register just fine for the connector, as it doesn't expect a handshake
     assert!(connector
         .node()
-        .connect(connectee.node().listening_addr().unwrap())
+        .connect(&connectee.node().listening_addr().unwrap())
         .await
         .is_ok());
 
@@ -304,7 +306,7 @@ async fn timeout_when_spammed_with_connections() {
     let mut sockets = Vec::with_capacity(NUM_ATTEMPTS as usize);
 
     for _ in 0..NUM_ATTEMPTS {
-        if let Ok(socket) = TcpStream::connect(victim_addr).await {
+        if let Ok(socket) = TcpStream::connect(victim_addr.socket_addr().unwrap()).await {
             sockets.push(socket);
         }
     }
@@ -19,7 +19,7 @@ impl common::MessagingNode {
                 let mut addrs_to_disconnect = Vec::new();
 
                 for addr in node.connected_addrs() {
-                    if let Some(stats) = node.known_peers().get(addr).as_deref() {
+                    if let Some(stats) = node.known_peers().get(&addr).as_deref() {
                         if stats.failures() > 0 {
                             addrs_to_disconnect.push(addr);
                         }
@@ -27,7 +27,7 @@ impl common::MessagingNode {
                 }
 
                 for addr in addrs_to_disconnect {
-                    node.disconnect(addr).await;
+                    node.disconnect(&addr).await;
                 }
 
                 sleep(Duration::from_millis(10)).await;
@@ -44,14 +44,14 @@ async fn maintenance_example() {
     let rando = common::MessagingNode::new("rando").await;
 
     tidy.node()
-        .connect(rando.node().listening_addr().unwrap())
+        .connect(&rando.node().listening_addr().unwrap())
         .await
         .unwrap();
 
     tidy.perform_periodic_maintenance();
     tidy.node()
         .known_peers()
-        .register_failure(rando.node().listening_addr().unwrap()); // artificially report an issue with rando
+        .register_failure(&rando.node().listening_addr().unwrap()); // artificially report an issue with rando
 
     wait_until!(1, tidy.node().num_connected() == 0);
 }
@@ -0,0 +1,48 @@
+use pea2pea::{transport::MemoryTransport, PeerAddr, Transport};
+
+use std::io;
+
+// the number of ports assigned automatically
+const NUM_EPHEMERAL_PORTS: usize = 16384;
+
+#[tokio::test]
+async fn memory_ports_of_live_connections_are_not_reused() {
+    let transport = MemoryTransport::default();
+    let any_addr: PeerAddr = "127.0.0.1:0".parse().unwrap();
+
+    let listener = transport.listen(&any_addr).await.unwrap();
+    let listener_addr = listener.local_addr().unwrap();
+
+    let dialed = transport.dial(&listener_addr).await.unwrap();
+    let conn_addr = dialed.local_addr().unwrap();
+
+    // the address of a live connection can't be listened on
+    let err = transport.listen(&conn_addr).await.map(drop).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+    // all the other ephemeral ports can be assigned, even after wrapping around
+    let mut listeners = Vec::with_capacity(NUM_EPHEMERAL_PORTS);
+    loop {
+        match transport.listen(&any_addr).await {
+            Ok(listener) => {
+                assert_ne!(listener.local_addr().unwrap(), conn_addr);
+                listeners.push(listener);
+            }
+            Err(e) => {
+                // the exhausted port space is reported instead of looping forever
+                assert_eq!(e.kind(), io::ErrorKind::AddrNotAvailable);
+                break;
+            }
+        }
+    }
+    assert_eq!(listeners.len(), NUM_EPHEMERAL_PORTS - 2);
+    assert!(transport.dial(&listener_addr).await.is_err());
+
+    // the port is freed once both sides of the connection are closed
+    let (accepted, _) = listener.accept().await.unwrap();
+    drop(dialed);
+    assert!(transport.listen(&any_addr).await.is_err());
+    drop(accepted);
+    let listener = transport.listen(&any_addr).await.unwrap();
+    assert_eq!(listener.local_addr().unwrap(), conn_addr);
+}
@@ -1,16 +1,20 @@
 use bytes::Bytes;
 use parking_lot::Mutex;
-use tokio::time::sleep;
+use tokio::{
+    io::{AsyncWriteExt, BufWriter},
+    time::sleep,
+};
 use tracing::*;
 
 mod common;
 use pea2pea::{
     protocols::{Reading, Writing},
-    Config, Node, Pea2Pea,
+    transport::MemoryTransport,
+    Config, Node, Pea2Pea, PeerAddr, Transport,
 };
 use TestMessage::*;
 
-use std::{collections::HashSet, io, net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::HashSet, io, sync::Arc, time::Duration};
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 enum TestMessage {
@@ -46,20 +50,20 @@ impl Reading for EchoNode {
 
     fn read_message<R: io::Read>(
         &self,
-        _source: SocketAddr,
+        _source: &PeerAddr,
         reader: &mut R,
     ) -> io::Result<Option<Self::Message>> {
         let byte = common::read_len_prefixed_message::<R, 2>(reader)?;
         Ok(byte.map(|byte| TestMessage::from(byte[0])))
     }
 
-    async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()> {
+    async fn process_message(&self, source: PeerAddr, message: Self::Message) -> io::Result<()> {
         info!(parent: self.node().span(), "got a {:?} from {}", message, source);
 
         if self.echoed.lock().insert(message) {
             info!(parent: self.node().span(), "it was new! echoing it");
 
-            self.send_direct_message(source, Bytes::copy_from_slice(&[message as u8]))
+            self.send_direct_message(&source, Bytes::copy_from_slice(&[message as u8]))
                 .unwrap()
                 .await
                 .unwrap();
@@ -76,7 +80,7 @@ impl Writing for EchoNode {
 
     fn write_message<W: io::Write>(
         &self,
-        _: SocketAddr,
+        _: &PeerAddr,
         payload: &Self::Message,
         writer: &mut W,
     ) -> io::Result<()> {
@@ -106,24 +110,24 @@ async fn messaging_example() {
 
     let picky_echo_addr = picky_echo.node().listening_addr().unwrap();
 
-    shouter.node().connect(picky_echo_addr).await.unwrap();
+    shouter.node().connect(&picky_echo_addr).await.unwrap();
 
     wait_until!(1, picky_echo.node().num_connected() == 1);
 
     for message in &[Herp, Derp, Herp] {
         let msg = Bytes::copy_from_slice(&[*message as u8]);
         shouter
-            .send_direct_message(picky_echo_addr, msg)
+            .send_direct_message(&picky_echo_addr, msg)
             .unwrap()
             .await
             .unwrap();
     }
 
     // let echo send one message on its own too, for good measure
-    let shouter_addr = picky_echo.node().connected_addrs()[0];
+    let shouter_addr = picky_echo.node().connected_addrs()[0].clone();
 
     picky_echo
-        .send_direct_message(shouter_addr, [Herp as u8][..].into())
+        .send_direct_message(&shouter_addr, [Herp as u8][..].into())
         .unwrap()
         .await
         .unwrap();
@@ -141,7 +145,7 @@ async fn drop_connection_on_invalid_message() {
     let writer = common::MessagingNode::new("writer").await;
     writer.enable_writing().await;
 
-    writer.node().connect(reader_addr).await.unwrap();
+    writer.node().connect(&reader_addr).await.unwrap();
 
     wait_until!(1, reader.node().num_connected() == 1);
 
@@ -149,7 +153,7 @@ async fn drop_connection_on_invalid_message() {
     let bad_message = Bytes::from(vec![]);
 
     writer
-        .send_direct_message(reader_addr, bad_message)
+        .send_direct_message(&reader_addr, bad_message)
         .unwrap()
         .await
         .unwrap();
@@ -173,7 +177,7 @@ async fn drop_connection_on_oversized_message() {
     let reader_addr = reader.node().listening_addr().unwrap();
     reader.enable_reading().await;
 
-    writer.node().connect(reader_addr).await.unwrap();
+    writer.node().connect(&reader_addr).await.unwrap();
 
     wait_until!(1, reader.node().num_connected() == 1);
 
@@ -181,7 +185,7 @@ async fn drop_connection_on_oversized_message() {
     let max_size_payload = vec![0u8; MSG_SIZE_LIMIT - 2];
 
     writer
-        .send_direct_message(reader_addr, max_size_payload.into())
+        .send_direct_message(&reader_addr, max_size_payload.into())
         .unwrap()
         .await
         .unwrap();
@@ -193,7 +197,7 @@ async fn drop_connection_on_oversized_message() {
     let oversized_payload = vec![0u8; MSG_SIZE_LIMIT - 1];
 
     writer
-        .send_direct_message(reader_addr, oversized_payload.into())
+        .send_direct_message(&reader_addr, oversized_payload.into())
         .unwrap()
         .await
         .unwrap();
@@ -208,7 +212,7 @@ async fn drop_connection_on_zero_read() {
     let peer = common::MessagingNode::new("peer").await;
 
     peer.node()
-        .connect(reader.node().listening_addr().unwrap())
+        .connect(&reader.node().listening_addr().unwrap())
         .await
         .unwrap();
 
@@ -229,13 +233,13 @@ async fn no_reading_no_delivery() {
     let writer = common::MessagingNode::new("writer").await;
     writer.enable_writing().await;
 
-    writer.node().connect(reader_addr).await.unwrap();
+    writer.node().connect(&reader_addr).await.unwrap();
 
     wait_until!(1, reader.node().num_connected() == 1);
 
     // writer sends a message
     writer
-        .send_direct_message(reader_addr, vec![0; 16].into())
+        .send_direct_message(&reader_addr, vec![0; 16].into())
         .unwrap()
         .await
         .unwrap();
@@ -254,13 +258,13 @@ async fn no_writing_no_delivery() {
 
     let writer = common::MessagingNode::new("defunct writer").await;
 
-    writer.node().connect(reader_addr).await.unwrap();
+    writer.node().connect(&reader_addr).await.unwrap();
 
     wait_until!(1, reader.node().num_connected() == 1);
 
     // writer tries to send a message
     assert!(writer
-        .send_direct_message(reader_addr, vec![0; 16].into())
+        .send_direct_message(&reader_addr, vec![0; 16].into())
         .is_err());
 
     sleep(Duration::from_millis(10)).await;
@@ -268,3 +272,95 @@ async fn no_writing_no_delivery() {
     // the writer didn't enable writing, so the reader won't receive anything
     wait_until!(1, reader.node().stats().received() == (0, 0));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reading_starts_once_connected() {
+    const NUM_CONNS: usize = 2000;
+
+    let transport = MemoryTransport::default();
+    let config = Config {
+        name: Some("reader".into()),
+        max_connections: NUM_CONNS as u16,
+        inbound_queue_depth: 16,
+        outbound_queue_depth: 16,
+        ..Default::default()
+    };
+    let reader = common::MessagingNode(
+        Node::with_transport(Some(config), transport.clone())
+            .await
+            .unwrap(),
+    );
+    reader.enable_reading().await;
+    reader.enable_writing().await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+
+    // all the peers connect at once and send a message right away
+    let mut peers = Vec::with_capacity(NUM_CONNS);
+    for _ in 0..NUM_CONNS {
+        let transport = transport.clone();
+        let reader_addr = reader_addr.clone();
+        peers.push(tokio::spawn(async move {
+            let (reader, mut writer) = transport.dial(&reader_addr).await.unwrap().split();
+            writer
+                .write_all(&common::prefix_with_len(2, b"hello"))
+                .await
+                .unwrap();
+
+            (reader, writer)
+        }));
+    }
+    let mut streams = Vec::with_capacity(NUM_CONNS);
+    for peer in peers {
+        streams.push(peer.await.unwrap());
+    }
+
+    // the messages are read as soon as the connections are established
+    wait_until!(5, reader.node().stats().received().0 == NUM_CONNS as u64);
+    assert_eq!(reader.node().num_connected(), NUM_CONNS);
+}
+
+#[tokio::test]
+async fn written_messages_are_flushed() {
+    let node = common::MessagingNode::new("writer").await;
+    let addr: PeerAddr = "127.0.0.1:1".parse().unwrap();
+
+    // the writer only passes the data on once flushed
+    let mut writer = BufWriter::new(Vec::new());
+    let mut buffer = Vec::new();
+    let len = node
+        .write_to_stream(
+            Bytes::from_static(b"hello"),
+            &addr,
+            &mut buffer,
+            &mut writer,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(len, 7);
+    assert_eq!(
+        writer.get_ref()[..],
+        common::prefix_with_len(2, b"hello")[..]
+    );
+}
+
+#[tokio::test]
+async fn no_writing_keeps_the_stream_open() {
+    let reader = common::MessagingNode::new("reader").await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+    reader.enable_reading().await;
+
+    let writer = common::MessagingNode::new("defunct writer").await;
+
+    writer.node().connect(&reader_addr).await.unwrap();
+
+    wait_until!(1, reader.node().num_connected() == 1);
+
+    // the writer doesn't close its side of the stream, so the reader doesn't read an EOF
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(reader.node().num_connected(), 1);
+
+    // until the writer disconnects
+    writer.node().disconnect(&reader_addr).await;
+    wait_until!(1, reader.node().num_connected() == 0);
+}
@@ -0,0 +1,138 @@
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+mod common;
+use pea2pea::{
+    metrics,
+    protocols::{Reading, Writing},
+    Config, Node, Pea2Pea,
+};
+
+use std::net::SocketAddr;
+
+async fn http_get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    response
+}
+
+#[tokio::test]
+async fn metrics_rendering() {
+    let reader = common::MessagingNode::new("reader").await;
+    reader.enable_reading().await;
+    reader.enable_writing().await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+
+    let writer = common::MessagingNode::new("writer").await;
+    writer.enable_reading().await;
+    writer.enable_writing().await;
+    writer.node().connect(&reader_addr).await.unwrap();
+
+    for _ in 0..3 {
+        writer
+            .send_direct_message(&reader_addr, Bytes::from_static(b"hello"))
+            .unwrap()
+            .await
+            .unwrap();
+    }
+    wait_until!(1, reader.node().stats().received().0 == 3);
+
+    let rendered = metrics::render(writer.node());
+    let peer = reader_addr.to_string();
+
+    for expected_line in [
+        "# TYPE pea2pea_connected gauge".to_owned(),
+        "pea2pea_connected{node=\"writer\"} 1".to_owned(),
+        "pea2pea_connecting{node=\"writer\"} 0".to_owned(),
+        "# TYPE pea2pea_node_messages_sent counter".to_owned(),
+        "pea2pea_node_messages_sent_total{node=\"writer\"} 3".to_owned(),
+        "pea2pea_node_bytes_sent_total{node=\"writer\"} 21".to_owned(),
+        format!(
+            "pea2pea_peer_messages_sent_total{{node=\"writer\",peer=\"{}\"}} 3",
+            peer
+        ),
+        format!(
+            "pea2pea_peer_connections_total{{node=\"writer\",peer=\"{}\"}} 1",
+            peer
+        ),
+        format!("pea2pea_peer_score{{node=\"writer\",peer=\"{}\"}} 0", peer),
+        "# TYPE pea2pea_node_message_size_bytes histogram".to_owned(),
+        // 7B messages belong to the [4, 7] bucket
+        "pea2pea_node_message_size_bytes_bucket{node=\"writer\",direction=\"sent\",le=\"3\"} 0"
+            .to_owned(),
+        "pea2pea_node_message_size_bytes_bucket{node=\"writer\",direction=\"sent\",le=\"7\"} 3"
+            .to_owned(),
+        "pea2pea_node_message_size_bytes_bucket{node=\"writer\",direction=\"sent\",le=\"+Inf\"} 3"
+            .to_owned(),
+        "pea2pea_node_message_size_bytes_count{node=\"writer\",direction=\"sent\"} 3".to_owned(),
+        "pea2pea_node_message_size_bytes_sum{node=\"writer\",direction=\"sent\"} 21".to_owned(),
+        "pea2pea_node_write_latency_microseconds_count{node=\"writer\"} 3".to_owned(),
+        format!(
+            "pea2pea_inbound_queue_length{{node=\"writer\",peer=\"{}\"}} 0",
+            peer
+        ),
+        format!(
+            "pea2pea_outbound_queue_length{{node=\"writer\",peer=\"{}\"}} 0",
+            peer
+        ),
+    ] {
+        assert!(
+            rendered.lines().any(|line| line == expected_line),
+            "missing line: {}\n{}",
+            expected_line,
+            rendered
+        );
+    }
+
+    // the rates are positive right after the messages are sent
+    assert!(rendered.lines().any(|line| line.starts_with(
+        "pea2pea_node_message_rate{node=\"writer\",direction=\"sent\",window=\"1m\"}"
+    ) && !line.ends_with(" 0")));
+
+    // every family is described exactly once, and the exposition is terminated
+    let mut families = rendered
+        .lines()
+        .filter(|line| line.starts_with("# TYPE "))
+        .collect::<Vec<_>>();
+    let num_families = families.len();
+    families.sort_unstable();
+    families.dedup();
+    assert_eq!(families.len(), num_families);
+    assert!(rendered.ends_with("# EOF\n"));
+}
+
+#[tokio::test]
+async fn metrics_endpoint() {
+    let config = Config {
+        name: Some("served".into()),
+        metrics_addr: Some("127.0.0.1:0".parse().unwrap()),
+        ..Default::default()
+    };
+    let node = Node::new(Some(config)).await.unwrap();
+    let metrics_addr = node.metrics_addr().unwrap();
+
+    let response = http_get(metrics_addr, metrics::METRICS_PATH).await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains(&format!("Content-Type: {}\r\n", metrics::CONTENT_TYPE)));
+    assert!(response.contains("pea2pea_connected{node=\"served\"} 0\n"));
+    assert!(response.ends_with("# EOF\n"));
+
+    let response = http_get(metrics_addr, "/elsewhere").await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    // the endpoint is shut down along with the node
+    node.shut_down().await;
+    wait_until!(1, TcpStream::connect(metrics_addr).await.is_err());
+
+    // nodes don't serve metrics by default
+    let node = Node::new(None).await.unwrap();
+    assert!(node.metrics_addr().is_err());
+}
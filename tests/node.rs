@@ -1,9 +1,14 @@
 use tokio::{net::TcpListener, time::sleep};
 
 mod common;
-use pea2pea::{connect_nodes, Config, Node, Topology};
+use pea2pea::{
+    connect_nodes,
+    transport::{TcpTransport, TransportListener, TransportStream},
+    Config, Node, PeerAddr, Topology, Transport,
+};
 
 use std::{
+    io,
     net::Ipv4Addr,
     sync::{
         atomic::{AtomicUsize, Ordering::Relaxed},
@@ -48,7 +53,7 @@ async fn node_connect_and_disconnect() {
 
     assert!(
         nodes[0]
-            .disconnect(nodes[1].listening_addr().unwrap())
+            .disconnect(&nodes[1].listening_addr().unwrap())
             .await
     );
 
@@ -62,7 +67,7 @@ async fn node_connect_and_disconnect() {
 #[tokio::test]
 async fn node_self_connection_fails() {
     let node = Node::new(None).await.unwrap();
-    assert!(node.connect(node.listening_addr().unwrap()).await.is_err());
+    assert!(node.connect(&node.listening_addr().unwrap()).await.is_err());
 }
 
 #[tokio::test]
@@ -90,7 +95,7 @@ async fn node_connector_limit_breach_fails() {
     let connectee = Node::new(None).await.unwrap();
 
     assert!(connector
-        .connect(connectee.listening_addr().unwrap())
+        .connect(&connectee.listening_addr().unwrap())
         .await
         .is_err());
 }
@@ -106,7 +111,7 @@ async fn node_connectee_limit_breach_fails() {
 
     // a breached connection limit doesn't close the listener, so this works
     connector
-        .connect(connectee.listening_addr().unwrap())
+        .connect(&connectee.listening_addr().unwrap())
         .await
         .unwrap();
 
@@ -126,8 +131,9 @@ async fn node_overlapping_duplicate_connection_attempts_fail() {
     for _ in 0..NUM_ATTEMPTS {
         let connector_clone = connector.clone();
         let err_count_clone = err_count.clone();
+        let addr = addr.clone();
         tokio::spawn(async move {
-            if connector_clone.connect(addr).await.is_err() {
+            if connector_clone.connect(&addr).await.is_err() {
                 err_count_clone.fetch_add(1, Relaxed);
             }
         });
@@ -139,7 +145,7 @@ async fn node_overlapping_duplicate_connection_attempts_fail() {
 #[tokio::test]
 async fn node_shutdown_closes_the_listener() {
     let node = Node::new(None).await.unwrap();
-    let addr = node.listening_addr().unwrap();
+    let addr = node.listening_addr().unwrap().socket_addr().unwrap();
 
     assert!(TcpListener::bind(addr).await.is_err());
     node.shut_down().await;
@@ -151,5 +157,132 @@ async fn node_shutdown_closes_the_listener() {
 async fn test_nodes_use_localhost() {
     let node = Node::new(None).await.unwrap();
 
-    assert_eq!(node.listening_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+    assert_eq!(
+        node.listening_addr().unwrap().ip(),
+        Some(Ipv4Addr::LOCALHOST.into())
+    );
+}
+
+#[derive(Clone, Default)]
+struct CountingTransport {
+    dials: Arc<AtomicUsize>,
+    listens: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl Transport for CountingTransport {
+    async fn listen(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportListener>> {
+        self.listens.fetch_add(1, Relaxed);
+        TcpTransport.listen(addr).await
+    }
+
+    async fn dial(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportStream>> {
+        self.dials.fetch_add(1, Relaxed);
+        TcpTransport.dial(addr).await
+    }
+}
+
+#[tokio::test]
+async fn node_custom_transport_works() {
+    let transport = CountingTransport::default();
+
+    let connector = Node::with_transport(None, transport.clone()).await.unwrap();
+    let connectee = Node::with_transport(None, transport.clone()).await.unwrap();
+
+    connector
+        .connect(&connectee.listening_addr().unwrap())
+        .await
+        .unwrap();
+
+    wait_until!(1, connectee.num_connected() == 1);
+    assert_eq!(transport.listens.load(Relaxed), 2);
+    assert_eq!(transport.dials.load(Relaxed), 1);
+}
+
+// never finishes dialing the blackholed addresses
+#[derive(Clone, Default)]
+struct BlackholeTransport {
+    blackholed: Arc<Vec<PeerAddr>>,
+}
+
+#[async_trait::async_trait]
+impl Transport for BlackholeTransport {
+    async fn listen(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportListener>> {
+        TcpTransport.listen(addr).await
+    }
+
+    async fn dial(&self, addr: &PeerAddr) -> io::Result<Box<dyn TransportStream>> {
+        if self.blackholed.contains(addr) {
+            std::future::pending().await
+        } else {
+            TcpTransport.dial(addr).await
+        }
+    }
+}
+
+#[tokio::test]
+async fn node_connect_timeout() {
+    let blackholed: PeerAddr = "127.0.0.1:1".parse().unwrap();
+    let transport = BlackholeTransport {
+        blackholed: Arc::new(vec![blackholed.clone()]),
+    };
+    let config = Config {
+        connect_timeout_ms: 50,
+        ..Default::default()
+    };
+    let node = Node::with_transport(Some(config), transport).await.unwrap();
+
+    let err = node.connect(&blackholed).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(node.num_connecting(), 0);
+}
+
+#[tokio::test]
+async fn node_connect_any() {
+    let blackholed: PeerAddr = "[::1]:1".parse().unwrap();
+    let transport = BlackholeTransport {
+        blackholed: Arc::new(vec![blackholed.clone()]),
+    };
+    let config = Config {
+        connect_timeout_ms: 200,
+        connection_attempt_delay_ms: 50,
+        ..Default::default()
+    };
+    let node = Node::with_transport(Some(config), transport).await.unwrap();
+    let peers = common::start_nodes(2, None).await;
+
+    // an unreachable address
+    let dead_peer = Node::new(None).await.unwrap();
+    let dead_addr = dead_peer.listening_addr().unwrap();
+    dead_peer.shut_down().await;
+
+    assert_eq!(
+        node.connect_any(&[]).await.unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert!(node
+        .connect_any(std::slice::from_ref(&dead_addr))
+        .await
+        .is_err());
+
+    // the blackholed address is attempted first, but the next one is attempted after the delay
+    let live_addr = peers[0].listening_addr().unwrap();
+    assert_eq!(
+        node.connect_any(&[blackholed, dead_addr, live_addr.clone()])
+            .await
+            .unwrap(),
+        live_addr
+    );
+    assert_eq!(node.num_connected(), 1);
+
+    // only a single connection is kept even if multiple candidates connect
+    node.disconnect(&live_addr).await;
+    let other_live_addr = peers[1].listening_addr().unwrap();
+    let connected_addr = node
+        .connect_any(&[live_addr.clone(), other_live_addr.clone()])
+        .await
+        .unwrap();
+    assert!(connected_addr == live_addr || connected_addr == other_live_addr);
+    wait_until!(1, node.num_connecting() == 0);
+    assert_eq!(node.connected_addrs(), vec![connected_addr]);
 }
@@ -0,0 +1,180 @@
+#![cfg(feature = "noise")]
+
+use bytes::Bytes;
+use tracing::*;
+
+mod common;
+use pea2pea::{
+    noise::{snow, NoiseConfig, NoiseHandshake},
+    protocols::{Handshake, Reading, Writing},
+    Config, Node, Pea2Pea, PeerAddr,
+};
+
+use std::io;
+
+const PSK_PATTERN: &str = "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s";
+
+#[derive(Clone)]
+struct NoiseNode {
+    node: Node,
+    noise_config: NoiseConfig,
+    trusted_key: Option<Vec<u8>>,
+}
+
+impl NoiseNode {
+    async fn new(noise_config: NoiseConfig) -> Self {
+        let config = Config {
+            read_buffer_size: 128 * 1024,
+            ..Default::default()
+        };
+
+        Self {
+            // leave room for messages exceeding the maximum size of a single Noise message
+            node: Node::new(Some(config)).await.unwrap(),
+            noise_config,
+            trusted_key: None,
+        }
+    }
+
+    async fn enable_protocols(&self) {
+        self.enable_handshake().await;
+        self.enable_reading().await;
+        self.enable_writing().await;
+    }
+}
+
+impl Pea2Pea for NoiseNode {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+impl NoiseHandshake for NoiseNode {
+    fn noise_config(&self) -> &NoiseConfig {
+        &self.noise_config
+    }
+
+    fn verify_remote_static_key(&self, _addr: &PeerAddr, key: Option<&[u8]>) -> io::Result<()> {
+        match self.trusted_key {
+            Some(ref trusted) if key != Some(trusted) => {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl_messaging!(NoiseNode);
+
+fn generate_keypair() -> snow::Keypair {
+    snow::Builder::new(NoiseConfig::default().pattern.parse().unwrap())
+        .generate_keypair()
+        .unwrap()
+}
+
+fn config_with_key(private_key: &[u8]) -> NoiseConfig {
+    NoiseConfig {
+        static_key: Some(private_key.to_vec()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn noise_handshake_and_messaging_work() {
+    let alice_keys = generate_keypair();
+    let bob_keys = generate_keypair();
+
+    let mut alice = NoiseNode::new(config_with_key(&alice_keys.private)).await;
+    alice.trusted_key = Some(bob_keys.public.clone());
+    let mut bob = NoiseNode::new(config_with_key(&bob_keys.private)).await;
+    bob.trusted_key = Some(alice_keys.public.clone());
+
+    alice.enable_protocols().await;
+    bob.enable_protocols().await;
+
+    let bob_addr = bob.node().listening_addr().unwrap();
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+
+    // the peers are identified by their static keys
+    assert_eq!(
+        alice.node().peer_id(&bob_addr),
+        Some(bob_keys.public.clone().into())
+    );
+
+    // the static keys remain available once the connections are established
+    assert_eq!(
+        alice
+            .node()
+            .connection_info(&bob_addr)
+            .unwrap()
+            .remote_static_key,
+        Some(bob_keys.public.clone())
+    );
+    let alice_addr = bob.node().connected_addrs().pop().unwrap();
+    assert_eq!(
+        bob.node()
+            .connection_info(&alice_addr)
+            .unwrap()
+            .remote_static_key,
+        Some(alice_keys.public.clone())
+    );
+
+    // a message exceeding the maximum size of a single Noise message
+    let large_msg = Bytes::from(vec![7u8; u16::MAX as usize]);
+
+    for msg in [Bytes::from_static(b"hello"), large_msg.clone()] {
+        assert!(alice
+            .send_direct_message(&bob_addr, msg)
+            .unwrap()
+            .await
+            .unwrap());
+    }
+    wait_until!(
+        1,
+        bob.node().stats().received() == (2, 2 + 5 + 2 + large_msg.len() as u64)
+    );
+}
+
+#[tokio::test]
+async fn noise_untrusted_static_key_is_rejected() {
+    let alice = NoiseNode::new(Default::default()).await;
+    let mut bob = NoiseNode::new(Default::default()).await;
+    bob.trusted_key = Some(generate_keypair().public);
+
+    alice.enable_protocols().await;
+    bob.enable_protocols().await;
+
+    let bob_addr = bob.node().listening_addr().unwrap();
+    let _ = alice.node().connect(&bob_addr).await;
+    wait_until!(1, bob.node().num_connecting() == 0);
+    assert_eq!(bob.node().num_connected(), 0);
+}
+
+#[tokio::test]
+async fn noise_psk_mismatch_fails() {
+    let psk_config = |psk: u8| NoiseConfig {
+        pattern: PSK_PATTERN.into(),
+        psks: vec![(3, vec![psk; 32])],
+        ..Default::default()
+    };
+
+    let alice = NoiseNode::new(psk_config(1)).await;
+    let bob = NoiseNode::new(psk_config(2)).await;
+    let charlie = NoiseNode::new(psk_config(1)).await;
+
+    for node in [&alice, &bob, &charlie] {
+        node.enable_protocols().await;
+    }
+
+    // with the `XXpsk3` pattern, the PSK mismatch is only detected by the responder
+    let bob_addr = bob.node().listening_addr().unwrap();
+    let _ = alice.node().connect(&bob_addr).await;
+    wait_until!(1, bob.node().num_connecting() == 0);
+    assert_eq!(bob.node().num_connected(), 0);
+    wait_until!(1, alice.node().num_connected() == 0);
+
+    let charlie_addr = charlie.node().listening_addr().unwrap();
+    alice.node().connect(&charlie_addr).await.unwrap();
+    wait_until!(1, charlie.node().num_connected() == 1);
+}
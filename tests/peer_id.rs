@@ -0,0 +1,404 @@
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Barrier,
+    time::sleep,
+};
+use tracing::*;
+
+mod common;
+use pea2pea::{
+    protocols::{Handshake, Reading, Writing},
+    Config, Connection, Node, Pea2Pea, PeerAddr, PeerId,
+};
+
+use std::{io, sync::Arc, time::Duration};
+
+// a node identified by its name; it is exchanged with peers during the handshake
+#[derive(Clone)]
+struct NamedNode(Node);
+
+impl NamedNode {
+    async fn new(name: &str) -> Self {
+        Self::with_config(Config {
+            name: Some(name.into()),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn with_config(config: Config) -> Self {
+        let node = Self(Node::new(Some(config)).await.unwrap());
+
+        node.enable_handshake().await;
+        node.enable_reading().await;
+        node.enable_writing().await;
+
+        node
+    }
+
+    fn peer_id(&self) -> PeerId {
+        self.node().name().as_bytes().into()
+    }
+}
+
+impl Pea2Pea for NamedNode {
+    fn node(&self) -> &Node {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl Handshake for NamedNode {
+    async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
+        let own_name = self.node().name().as_bytes();
+        let writer = conn.writer();
+        writer.write_u8(own_name.len() as u8).await?;
+        writer.write_all(own_name).await?;
+
+        let reader = conn.reader();
+        let len = reader.read_u8().await? as usize;
+        let mut peer_name = vec![0u8; len];
+        reader.read_exact(&mut peer_name).await?;
+
+        conn.peer_id = Some(peer_name.into());
+
+        Ok(conn)
+    }
+}
+
+impl_messaging!(NamedNode);
+
+#[tokio::test]
+async fn peer_id_addressing_works() {
+    let alice = NamedNode::new("alice").await;
+    let bob = NamedNode::new("bob").await;
+
+    let bob_addr = bob.node().listening_addr().unwrap();
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+
+    assert_eq!(alice.node().peer_id(&bob_addr), Some(bob.peer_id()));
+    assert_eq!(alice.node().peer_addr(&bob.peer_id()), Some(bob_addr));
+
+    alice
+        .send_to_peer(&bob.peer_id(), Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, bob.node().stats().received().0 == 1);
+
+    assert!(alice.node().disconnect_peer(&bob.peer_id()).await);
+    assert!(alice.node().peer_addr(&bob.peer_id()).is_none());
+    assert!(alice
+        .send_to_peer(&bob.peer_id(), Bytes::from_static(b"hello"))
+        .is_err());
+}
+
+#[tokio::test]
+async fn peer_id_stats_survive_reconnects() {
+    let alice = NamedNode::new("alice").await;
+    let bob = NamedNode::new("bob").await;
+    let bob_addr = bob.node().listening_addr().unwrap();
+
+    for i in 1..=3 {
+        alice.node().connect(&bob_addr).await.unwrap();
+        wait_until!(1, bob.node().num_connected() == 1);
+
+        // alice connects from a different port every time
+        let alice_addr = bob.node().connected_addrs()[0].clone();
+        assert_eq!(bob.node().peer_id(&alice_addr), Some(alice.peer_id()));
+
+        alice
+            .send_direct_message(&bob_addr, Bytes::from_static(b"hello"))
+            .unwrap()
+            .await
+            .unwrap();
+        wait_until!(
+            1,
+            bob.node()
+                .known_peers()
+                .get_by_id(&alice.peer_id())
+                .map(|stats| stats.received().0)
+                == Some(i)
+        );
+
+        assert!(bob.node().disconnect(&alice_addr).await);
+        wait_until!(1, alice.node().num_connected() == 0);
+        // the responder-side address entry is gone, but the stats remain available
+        assert!(bob.node().known_peers().get(&alice_addr).is_none());
+    }
+}
+
+#[tokio::test]
+async fn peer_id_duplicate_connection_is_rejected() {
+    let alice = NamedNode::new("alice").await;
+    let alice_impostor = NamedNode::new("alice").await;
+    let bob = NamedNode::new("bob").await;
+    let bob_addr = bob.node().listening_addr().unwrap();
+
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+
+    let _ = alice_impostor.node().connect(&bob_addr).await;
+    wait_until!(1, bob.node().num_connecting() == 0);
+    assert_eq!(bob.node().num_connected(), 1);
+    wait_until!(1, alice_impostor.node().num_connected() == 0);
+}
+
+#[tokio::test]
+async fn connection_ids_are_sequential() {
+    let alice = NamedNode::new("alice").await;
+    let bob = NamedNode::new("bob").await;
+    let carol = NamedNode::new("carol").await;
+
+    let bob_addr = bob.node().listening_addr().unwrap();
+    let carol_addr = carol.node().listening_addr().unwrap();
+    alice.node().connect(&bob_addr).await.unwrap();
+    alice.node().connect(&carol_addr).await.unwrap();
+
+    let bob_conn_id = alice.node().connection_id(&bob_addr).unwrap();
+    let carol_conn_id = alice.node().connection_id(&carol_addr).unwrap();
+    assert!(bob_conn_id < carol_conn_id);
+
+    // a new connection gets a new identifier
+    assert!(alice.node().disconnect(&bob_addr).await);
+    assert!(alice.node().connection_id(&bob_addr).is_none());
+    alice.node().connect(&bob_addr).await.unwrap();
+    assert!(alice.node().connection_id(&bob_addr).unwrap() > carol_conn_id);
+}
+
+async fn identified_node(name: &str, max_connections_per_peer: Option<u16>) -> NamedNode {
+    NamedNode::with_config(Config {
+        name: Some(name.into()),
+        local_peer_id: Some(name.as_bytes().into()),
+        max_connections_per_peer,
+        ..Default::default()
+    })
+    .await
+}
+
+#[tokio::test]
+async fn simultaneous_connections_are_tie_broken() {
+    for _ in 0..10 {
+        let alice = identified_node("alice", Some(1)).await;
+        let bob = identified_node("bob", Some(1)).await;
+        let alice_addr = alice.node().listening_addr().unwrap();
+        let bob_addr = bob.node().listening_addr().unwrap();
+
+        // both nodes dial each other at the same time
+        let _ = tokio::join!(
+            alice.node().connect(&bob_addr),
+            bob.node().connect(&alice_addr)
+        );
+
+        // both sides keep the connection initiated by alice, whose PeerId is lower
+        wait_until!(
+            1,
+            alice.node().num_connected() == 1
+                && bob.node().num_connected() == 1
+                && alice.node().is_connected(&bob_addr)
+                && !bob.node().is_connected(&alice_addr)
+                && alice.node().num_connecting() == 0
+                && bob.node().num_connecting() == 0
+        );
+        assert_eq!(bob.node().peer_addrs(&alice.peer_id()).len(), 1);
+
+        // the duplicate connection doesn't prevent messaging
+        alice
+            .send_to_peer(&bob.peer_id(), Bytes::from_static(b"hello"))
+            .unwrap()
+            .await
+            .unwrap();
+        wait_until!(1, bob.node().stats().received().0 == 1);
+    }
+}
+
+#[tokio::test]
+async fn multiple_connections_per_peer() {
+    let alice = identified_node("alice", None).await;
+    let bob = identified_node("bob", None).await;
+    let alice_addr = alice.node().listening_addr().unwrap();
+    let bob_addr = bob.node().listening_addr().unwrap();
+
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+    bob.node().connect(&alice_addr).await.unwrap();
+    wait_until!(1, alice.node().num_connected() == 2);
+
+    // both connections are kept, and the oldest one is used to address the peer
+    let addrs = alice.node().peer_addrs(&bob.peer_id());
+    assert_eq!(addrs.len(), 2);
+    assert_eq!(addrs[0], bob_addr);
+    assert_eq!(alice.node().peer_addr(&bob.peer_id()), Some(bob_addr));
+
+    // disconnecting from the peer closes all the connections
+    assert!(alice.node().disconnect_peer(&bob.peer_id()).await);
+    assert_eq!(alice.node().num_connected(), 0);
+    wait_until!(1, bob.node().num_connected() == 0);
+}
+
+#[tokio::test]
+async fn simultaneous_connections_are_tie_broken_by_addresses() {
+    for _ in 0..10 {
+        let alice = NamedNode::new("alice").await;
+        let bob = NamedNode::new("bob").await;
+        let alice_addr = alice.node().listening_addr().unwrap();
+        let bob_addr = bob.node().listening_addr().unwrap();
+
+        // both nodes dial each other at the same time; neither has a local PeerId
+        let _ = tokio::join!(
+            alice.node().connect(&bob_addr),
+            bob.node().connect(&alice_addr)
+        );
+
+        // both sides keep the same connection
+        wait_until!(
+            1,
+            alice.node().num_connected() == 1
+                && bob.node().num_connected() == 1
+                && alice.node().is_connected(&bob_addr) != bob.node().is_connected(&alice_addr)
+                && alice.node().num_connecting() == 0
+                && bob.node().num_connecting() == 0
+        );
+
+        alice
+            .send_to_peer(&bob.peer_id(), Bytes::from_static(b"hello"))
+            .unwrap()
+            .await
+            .unwrap();
+        wait_until!(1, bob.node().stats().received().0 == 1);
+    }
+}
+
+#[tokio::test]
+async fn multiple_connections_per_address() {
+    let config = |name: &str| Config {
+        name: Some(name.into()),
+        max_connections_per_peer: None,
+        ..Default::default()
+    };
+    let alice = NamedNode::with_config(config("alice")).await;
+    let bob = NamedNode::with_config(config("bob")).await;
+    let bob_addr = bob.node().listening_addr().unwrap();
+
+    alice.node().connect(&bob_addr).await.unwrap();
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 2);
+
+    // the connections are told apart by their identifiers
+    assert_eq!(alice.node().num_connected(), 2);
+    assert_eq!(alice.node().connected_addrs(), vec![bob_addr.clone()]);
+    let ids = alice.node().connection_ids(&bob_addr);
+    assert_eq!(ids.len(), 2);
+    assert_eq!(alice.node().connection_id(&bob_addr), Some(ids[0]));
+
+    alice
+        .send_to_connection(ids[1], Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, bob.node().stats().received().0 == 1);
+
+    // a single connection can be closed
+    assert!(alice.node().disconnect_connection(ids[0]).await);
+    assert_eq!(alice.node().connection_ids(&bob_addr), vec![ids[1]]);
+    wait_until!(1, bob.node().num_connected() == 1);
+    assert!(!alice.node().disconnect_connection(ids[0]).await);
+
+    // disconnecting from the address closes all of them
+    alice.node().connect(&bob_addr).await.unwrap();
+    assert_eq!(alice.node().connection_ids(&bob_addr).len(), 2);
+    assert!(alice.node().disconnect(&bob_addr).await);
+    assert_eq!(alice.node().num_connected(), 0);
+    wait_until!(1, bob.node().num_connected() == 0);
+}
+
+// a node that concludes pairs of handshakes at the same time; the peers identify themselves with a single byte
+#[derive(Clone)]
+struct SynchronizedNode(Node, Arc<Barrier>);
+
+impl Pea2Pea for SynchronizedNode {
+    fn node(&self) -> &Node {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl Handshake for SynchronizedNode {
+    async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
+        let peer_id = conn.reader().read_u8().await?;
+        conn.peer_id = Some(vec![peer_id].into());
+        self.1.wait().await;
+
+        Ok(conn)
+    }
+}
+
+impl_messaging!(SynchronizedNode);
+
+#[tokio::test]
+async fn concurrent_handshakes_respect_peer_limit() {
+    let node = SynchronizedNode(Node::new(None).await.unwrap(), Arc::new(Barrier::new(2)));
+    node.enable_handshake().await;
+    node.enable_reading().await;
+    node.enable_writing().await;
+    let node_addr = node.node().listening_addr().unwrap();
+
+    // two connections claiming to be the same peer conclude their handshakes simultaneously
+    let mut streams = Vec::new();
+    for _ in 0..2 {
+        let mut stream = TcpStream::connect(node_addr.socket_addr().unwrap())
+            .await
+            .unwrap();
+        stream.write_u8(7).await.unwrap();
+        streams.push(stream);
+    }
+
+    wait_until!(1, node.node().num_connecting() == 0);
+    assert_eq!(node.node().num_connected(), 1);
+    assert_eq!(node.node().peer_addrs(&vec![7].into()).len(), 1);
+}
+
+#[tokio::test]
+async fn lost_slot_race_leaves_no_tasks() {
+    let config = Config {
+        local_peer_id: Some(vec![5].into()),
+        ..Default::default()
+    };
+    let node = SynchronizedNode(
+        Node::new(Some(config)).await.unwrap(),
+        Arc::new(Barrier::new(2)),
+    );
+    node.enable_handshake().await;
+    node.enable_reading().await;
+    node.enable_writing().await;
+    let node_addr = node.node().listening_addr().unwrap();
+
+    // the peer (with a higher PeerId) is dialed by the node and dials it at the same time
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener_addr: PeerAddr = listener.local_addr().unwrap().into();
+    let node_clone = node.clone();
+    let target = listener_addr.clone();
+    let dialing = tokio::spawn(async move { node_clone.node().connect(&target).await });
+    let (mut outbound, _) = listener.accept().await.unwrap();
+    let mut inbound = TcpStream::connect(node_addr.socket_addr().unwrap())
+        .await
+        .unwrap();
+
+    // the handshake that concludes last reserves its slot first, so the connection that is not
+    // preferred (the inbound one) reserves it, only to have it taken over before it is registered
+    outbound.write_u8(7).await.unwrap();
+    wait_until!(1, node.node().num_connecting() == 2);
+    sleep(Duration::from_millis(50)).await;
+    inbound.write_u8(7).await.unwrap();
+
+    dialing.await.unwrap().unwrap();
+    wait_until!(1, node.node().num_connecting() == 0);
+    assert_eq!(node.node().connected_addrs(), vec![listener_addr]);
+
+    // none of the tasks of the connection that lost the race keep running
+    node.node().shut_down().await;
+    wait_until!(1, Arc::strong_count(&**node.node()) == 1);
+}
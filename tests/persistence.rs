@@ -0,0 +1,233 @@
+#![cfg(feature = "serde")]
+
+mod common;
+use pea2pea::{
+    protocols::{Reading, Writing},
+    BanTarget, Config, KnownPeers, Node, Pea2Pea, PeerAddr, PeerId, ReconnectPolicy,
+};
+
+use std::{
+    fs,
+    io::{self, Cursor},
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+fn temp_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pea2pea-{}-{}.peers", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    path
+}
+
+#[test]
+fn known_peers_save_and_load() {
+    let addr1: PeerAddr = "127.0.0.1:1".parse().unwrap();
+    let addr2: PeerAddr = "127.0.0.1:2".parse().unwrap();
+    let addr3: PeerAddr = "[::1]:3".parse().unwrap();
+    let peer_id = PeerId::from(vec![0xde, 0xad, 0xbe, 0xef]);
+    let banned_ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+    let known_peers = KnownPeers::default();
+    known_peers.add(&addr1);
+    known_peers.add(&addr2);
+    known_peers.add_peer_id(&addr2, peer_id.clone());
+    known_peers.add_peer_id(&addr3, peer_id.clone());
+    known_peers.register_sent_message(&addr1, 10);
+    known_peers.register_received_message(&addr1, 20);
+    known_peers.register_failure(&addr2);
+    known_peers.adjust_score(&addr1, 5.5);
+    known_peers.ban(&addr3, Duration::from_secs(60));
+    known_peers.ban(banned_ip, Duration::MAX);
+
+    let mut saved = Vec::new();
+    known_peers.save_to(&mut saved).unwrap();
+
+    let loaded = KnownPeers::default();
+    loaded.load_from(Cursor::new(&saved)).unwrap();
+
+    let stats1 = loaded.get(&addr1).unwrap();
+    assert_eq!(stats1.sent(), (1, 10));
+    assert_eq!(stats1.received(), (1, 20));
+    assert!(stats1.last_seen().is_some());
+    assert_eq!(loaded.score(&addr1), Some(5.5));
+
+    // the addresses of a peer with a PeerId still share its stats
+    let stats2 = loaded.get(&addr2).unwrap();
+    assert_eq!(stats2.failures(), 1);
+    assert!(Arc::ptr_eq(&stats2, &loaded.get(&addr3).unwrap()));
+    assert!(Arc::ptr_eq(&stats2, &loaded.get_by_id(&peer_id).unwrap()));
+
+    // the bans remain in place, including the permanent one
+    assert!(loaded.is_banned(&addr3));
+    assert!(loaded.is_banned(&SocketAddr::new(banned_ip, 1234).into()));
+    assert_eq!(loaded.bans().get(&BanTarget::Ip(banned_ip)), Some(&None));
+}
+
+#[test]
+fn known_peers_concurrent_saves() {
+    let path = temp_file("concurrent");
+    let known_peers = Arc::new(KnownPeers::default());
+    for port in 0..100 {
+        known_peers.add(&SocketAddr::from(([127, 0, 0, 1], port)).into());
+    }
+
+    // e.g. the periodic save and the one performed on shutdown
+    let savers = (0..8)
+        .map(|_| {
+            let known_peers = Arc::clone(&known_peers);
+            let path = path.clone();
+            std::thread::spawn(move || {
+                for _ in 0..20 {
+                    known_peers.save(&path).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for saver in savers {
+        saver.join().unwrap();
+    }
+
+    let loaded = KnownPeers::default();
+    loaded.load(&path).unwrap();
+    assert_eq!(loaded.snapshot().len(), 100);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn known_peers_load_malformed() {
+    let known_peers = KnownPeers::default();
+
+    for (contents, error) in [
+        ("", "EOF"),
+        ("not a known peers file", "expected"),
+        (
+            r#"{"version":2}"#,
+            "unsupported known peers file version: 2",
+        ),
+        (
+            r#"{"version":1,"addrs":[["127.0.0.1:1",0]]}"#,
+            "missing stats with index 0",
+        ),
+        (
+            r#"{"version":1,"stats":[{"msgs_sent":"x"}]}"#,
+            "invalid type",
+        ),
+        (
+            r#"{"version":1,"stats":[{}],"addrs":[["127.0.0.1:1",0]],"bans":[[{"Subnet":"10.0.0.0"},null]]}"#,
+            "unknown variant `Subnet`",
+        ),
+    ] {
+        let err = known_peers.load_from(Cursor::new(contents)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // the errors describe the problem
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+
+    // nothing was loaded from the partially valid file
+    assert!(known_peers.snapshot().is_empty());
+
+    // unknown entries and keys are ignored
+    known_peers
+        .load_from(Cursor::new(
+            r#"{"version":1,"stats":[{"msgs_sent":1,"future_key":1}],"future_entry":[],"addrs":[["127.0.0.1:1",0]]}"#,
+        ))
+        .unwrap();
+    let addr = "127.0.0.1:1".parse().unwrap();
+    assert_eq!(known_peers.get(&addr).unwrap().sent(), (1, 0));
+}
+
+#[tokio::test]
+async fn known_peers_file() {
+    let path = temp_file("known_peers_file");
+    let config = Config {
+        known_peers_file: Some(path.clone()),
+        known_peers_save_interval_ms: Some(10),
+        ..Default::default()
+    };
+
+    let peer = common::MessagingNode::new("peer").await;
+    peer.enable_reading().await;
+    peer.enable_writing().await;
+    let peer_addr = peer.node().listening_addr().unwrap();
+
+    let node = Node::new(Some(config.clone())).await.unwrap();
+    node.connect(&peer_addr).await.unwrap();
+
+    // the file is saved periodically
+    wait_until!(
+        1,
+        fs::read_to_string(&path)
+            .map(|contents| contents.contains(&peer_addr.to_string()))
+            .unwrap_or(false)
+    );
+    node.shut_down().await;
+
+    // a restarted node knows the peer immediately
+    let restarted = Node::new(Some(config)).await.unwrap();
+    let stats = restarted.known_peers().get(&peer_addr).unwrap();
+    assert!(stats.last_seen().is_some());
+    restarted.connect(&peer_addr).await.unwrap();
+
+    restarted.shut_down().await;
+    fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn known_peers_file_persistent_peers() {
+    let path = temp_file("persistent_peers");
+    let config = Config {
+        known_peers_file: Some(path.clone()),
+        ..Default::default()
+    };
+    let policy = ReconnectPolicy {
+        initial_backoff_ms: 10,
+        max_backoff_ms: 50,
+        ..Default::default()
+    };
+
+    let peer = common::MessagingNode::new("peer").await;
+    peer.enable_reading().await;
+    peer.enable_writing().await;
+    let peer_addr = peer.node().listening_addr().unwrap();
+
+    let node = Node::new(Some(config.clone())).await.unwrap();
+    node.add_persistent_peer(&peer_addr, policy);
+    wait_until!(1, node.is_connected(&peer_addr));
+    node.shut_down().await;
+    wait_until!(1, peer.node().num_connected() == 0);
+
+    // a restarted node remembers the persistent peer, but only redials it once asked to
+    let restarted = common::MessagingNode(Node::new(Some(config)).await.unwrap());
+    assert!(restarted.node().persistent_peers().is_empty());
+    assert_eq!(
+        restarted
+            .node()
+            .known_peers()
+            .persistent_peers()
+            .get(&peer_addr),
+        Some(&policy)
+    );
+    restarted.enable_reading().await;
+    restarted.enable_writing().await;
+    assert_eq!(restarted.node().restore_persistent_peers(), 1);
+    assert_eq!(restarted.node().persistent_peers(), vec![peer_addr.clone()]);
+    wait_until!(1, restarted.node().is_connected(&peer_addr));
+
+    // it is redialed when the connection is severed
+    let restarted_addr = peer.node().connected_addrs().pop().unwrap();
+    assert!(peer.node().disconnect(&restarted_addr).await);
+    wait_until!(1, peer.node().num_connected() == 1);
+
+    // once removed, it is no longer saved
+    assert!(restarted.node().remove_persistent_peer(&peer_addr));
+    restarted.node().shut_down().await;
+    let loaded = KnownPeers::default();
+    loaded.load(&path).unwrap();
+    assert!(loaded.persistent_peers().is_empty());
+    assert!(loaded.get(&peer_addr).is_some());
+
+    fs::remove_file(path).unwrap();
+}
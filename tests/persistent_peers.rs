@@ -0,0 +1,66 @@
+mod common;
+use pea2pea::{protocols::Reading, Pea2Pea, ReconnectPolicy};
+
+fn quick_policy(max_retries: Option<u32>) -> ReconnectPolicy {
+    ReconnectPolicy {
+        initial_backoff_ms: 10,
+        max_backoff_ms: 50,
+        max_retries,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn persistent_peer_is_redialed() {
+    let alice = common::MessagingNode::new("alice").await;
+    let bob = common::MessagingNode::new("bob").await;
+    for node in [&alice, &bob] {
+        node.enable_reading().await;
+    }
+    let bob_addr = bob.node().listening_addr().unwrap();
+
+    alice
+        .node()
+        .add_persistent_peer(&bob_addr, quick_policy(None));
+    assert_eq!(alice.node().persistent_peers(), vec![bob_addr.clone()]);
+    wait_until!(1, bob.node().num_connected() == 1);
+
+    // bob drops the connection; alice should redial
+    let alice_addr = bob.node().connected_addrs()[0].clone();
+    assert!(bob.node().disconnect(&alice_addr).await);
+    wait_until!(
+        1,
+        bob.node().num_connected() == 1 && bob.node().connected_addrs()[0].clone() != alice_addr
+    );
+    wait_until!(1, alice.node().is_connected(&bob_addr));
+
+    let stats = alice.node().known_peers().get(&bob_addr).unwrap();
+    assert_eq!(stats.connection_attempts(), 2);
+
+    // once removed, the peer is no longer redialed
+    assert!(alice.node().remove_persistent_peer(&bob_addr));
+    let alice_addr = bob.node().connected_addrs()[0].clone();
+    assert!(bob.node().disconnect(&alice_addr).await);
+    wait_until!(1, !alice.node().is_connected(&bob_addr));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(bob.node().num_connected(), 0);
+}
+
+#[tokio::test]
+async fn persistent_peer_retries_are_limited() {
+    let alice = common::MessagingNode::new("alice").await;
+    let bob = common::MessagingNode::new("bob").await;
+
+    // once bob is shut down, its address can no longer be connected to
+    let bob_addr = bob.node().listening_addr().unwrap();
+    bob.node().shut_down().await;
+
+    alice
+        .node()
+        .add_persistent_peer(&bob_addr, quick_policy(Some(2)));
+    wait_until!(1, alice.node().persistent_peers().is_empty());
+
+    let stats = alice.node().known_peers().get(&bob_addr).unwrap();
+    assert_eq!(stats.connection_attempts(), 3);
+    assert_eq!(stats.failures(), 3);
+}
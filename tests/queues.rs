@@ -0,0 +1,202 @@
+use bytes::Bytes;
+use tokio::time::sleep;
+
+mod common;
+use pea2pea::{
+    protocols::{Reading, Writing},
+    Config, Node, Pea2Pea, PeerAddr, QueuePolicy,
+};
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Arc,
+    },
+    time::Duration,
+};
+
+const NUM_MESSAGES: usize = 10;
+
+#[derive(Clone)]
+struct SlowReader {
+    node: Node,
+    processed: Arc<AtomicUsize>,
+}
+
+impl SlowReader {
+    async fn new(depth: usize, policy: QueuePolicy) -> Self {
+        let config = Config {
+            name: Some("slow_reader".into()),
+            inbound_queue_depth: depth,
+            inbound_queue_policy: policy,
+            ..Default::default()
+        };
+
+        Self {
+            node: Node::new(Some(config)).await.unwrap(),
+            processed: Default::default(),
+        }
+    }
+}
+
+impl Pea2Pea for SlowReader {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+#[async_trait::async_trait]
+impl Reading for SlowReader {
+    type Message = Vec<u8>;
+
+    fn read_message<R: io::Read>(
+        &self,
+        _source: &PeerAddr,
+        reader: &mut R,
+    ) -> io::Result<Option<Self::Message>> {
+        common::read_len_prefixed_message::<R, 2>(reader)
+    }
+
+    async fn process_message(&self, _source: PeerAddr, _message: Self::Message) -> io::Result<()> {
+        sleep(Duration::from_millis(10)).await;
+        self.processed.fetch_add(1, Relaxed);
+
+        Ok(())
+    }
+}
+
+async fn writer(depth: usize, policy: QueuePolicy) -> common::MessagingNode {
+    let config = Config {
+        name: Some("writer".into()),
+        outbound_queue_depth: depth,
+        outbound_queue_policy: policy,
+        ..Default::default()
+    };
+    let writer = common::MessagingNode(Node::new(Some(config)).await.unwrap());
+    writer.enable_writing().await;
+
+    writer
+}
+
+async fn spam_slow_reader(policy: QueuePolicy) -> SlowReader {
+    let reader = SlowReader::new(1, policy).await;
+    reader.enable_reading().await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+
+    let writer = writer(NUM_MESSAGES, QueuePolicy::DropNewest).await;
+    writer.node().connect(&reader_addr).await.unwrap();
+    wait_until!(1, reader.node().num_connected() == 1);
+
+    // the reader might disconnect before all the messages are sent
+    for _ in 0..NUM_MESSAGES {
+        if let Ok(delivery) = writer.send_direct_message(&reader_addr, Bytes::from_static(b"hello"))
+        {
+            let _ = delivery.await;
+        }
+    }
+
+    reader
+}
+
+#[tokio::test]
+async fn inbound_queue_wait_applies_backpressure() {
+    let reader = spam_slow_reader(QueuePolicy::Wait).await;
+
+    wait_until!(1, reader.processed.load(Relaxed) == NUM_MESSAGES);
+    assert_eq!(reader.node().stats().failures(), 0);
+}
+
+#[tokio::test]
+async fn inbound_queue_drop_newest_drops_messages() {
+    let reader = spam_slow_reader(QueuePolicy::DropNewest).await;
+
+    wait_until!(1, reader.node().stats().received().0 == NUM_MESSAGES as u64);
+    sleep(Duration::from_millis(100)).await;
+
+    let processed = reader.processed.load(Relaxed) as u64;
+    assert!(processed < NUM_MESSAGES as u64);
+    assert_eq!(
+        processed + reader.node().stats().failures(),
+        NUM_MESSAGES as u64
+    );
+}
+
+#[tokio::test]
+async fn inbound_queue_disconnect_drops_the_peer() {
+    let reader = spam_slow_reader(QueuePolicy::Disconnect).await;
+
+    wait_until!(1, reader.node().num_connected() == 0);
+    assert!(reader.processed.load(Relaxed) < NUM_MESSAGES);
+}
+
+#[tokio::test]
+async fn outbound_queue_drop_newest_rejects_messages() {
+    let reader = common::MessagingNode::new("reader").await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+
+    let writer = writer(2, QueuePolicy::DropNewest).await;
+    writer.node().connect(&reader_addr).await.unwrap();
+
+    // the writer task can't make any progress in between these calls, so the queue fills up
+    let msg = Bytes::from_static(b"hello");
+    assert!(writer
+        .send_direct_message(&reader_addr, msg.clone())
+        .is_ok());
+    assert!(writer
+        .send_direct_message(&reader_addr, msg.clone())
+        .is_ok());
+    assert_eq!(
+        writer
+            .send_direct_message(&reader_addr, msg)
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::Other
+    );
+    assert_eq!(writer.node().stats().failures(), 1);
+}
+
+#[tokio::test]
+async fn outbound_queue_wait_is_rejected() {
+    let config = Config {
+        outbound_queue_policy: QueuePolicy::Wait,
+        ..Default::default()
+    };
+    let err = Node::new(Some(config)).await.map(drop).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn outbound_queue_drop_oldest_evicts_messages() {
+    let reader = common::MessagingNode::new("reader").await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+
+    let writer = writer(1, QueuePolicy::DropOldest).await;
+    writer.node().connect(&reader_addr).await.unwrap();
+
+    let msg = Bytes::from_static(b"hello");
+    let oldest = writer
+        .send_direct_message(&reader_addr, msg.clone())
+        .unwrap();
+    let newest = writer.send_direct_message(&reader_addr, msg).unwrap();
+
+    assert!(!oldest.await.unwrap());
+    assert!(newest.await.unwrap());
+}
+
+#[tokio::test]
+async fn outbound_queue_disconnect_drops_the_peer() {
+    let reader = common::MessagingNode::new("reader").await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+
+    let writer = writer(1, QueuePolicy::Disconnect).await;
+    writer.node().connect(&reader_addr).await.unwrap();
+
+    let msg = Bytes::from_static(b"hello");
+    assert!(writer
+        .send_direct_message(&reader_addr, msg.clone())
+        .is_ok());
+    assert!(writer.send_direct_message(&reader_addr, msg).is_err());
+
+    wait_until!(1, writer.node().num_connected() == 0);
+}
@@ -0,0 +1,198 @@
+use bytes::Bytes;
+
+mod common;
+use pea2pea::{
+    protocols::{Reading, Writing},
+    Config, DisconnectReason, Node, NodeEvent, Pea2Pea, PeerAddr, ReputationPolicy,
+};
+
+use std::{io, time::Duration};
+
+// a node that rewards "bonus" messages on top of the automatic reward and considers any
+// message other than "ok" or "bonus" to be a failure
+#[derive(Clone)]
+struct ScoringNode(Node);
+
+impl Pea2Pea for ScoringNode {
+    fn node(&self) -> &Node {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl Reading for ScoringNode {
+    type Message = Bytes;
+
+    fn read_message<R: io::Read>(
+        &self,
+        _source: &PeerAddr,
+        reader: &mut R,
+    ) -> io::Result<Option<Self::Message>> {
+        let vec = common::read_len_prefixed_message::<R, 2>(reader)?;
+
+        Ok(vec.map(Bytes::from))
+    }
+
+    async fn process_message(&self, source: PeerAddr, message: Self::Message) -> io::Result<()> {
+        match &message[..] {
+            b"ok" => Ok(()),
+            b"bonus" => {
+                self.node().adjust_score(&source, 5.0);
+                Ok(())
+            }
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+impl Writing for ScoringNode {
+    type Message = Bytes;
+
+    fn write_message<W: io::Write>(
+        &self,
+        _target: &PeerAddr,
+        payload: &Self::Message,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        writer.write_all(&(payload.len() as u16).to_le_bytes())?;
+        writer.write_all(payload)
+    }
+}
+
+async fn scoring_node(policy: ReputationPolicy) -> ScoringNode {
+    let config = Config {
+        reputation_policy: Some(policy),
+        ..Default::default()
+    };
+    let node = ScoringNode(Node::new(Some(config)).await.unwrap());
+    node.enable_reading().await;
+    node.enable_writing().await;
+
+    node
+}
+
+async fn send_all(sender: &common::MessagingNode, target: &PeerAddr, msgs: &[&'static str]) {
+    for msg in msgs {
+        sender
+            .send_direct_message(target, Bytes::from(*msg))
+            .unwrap()
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn reputation_rewards_and_penalties() {
+    let scorer = scoring_node(ReputationPolicy {
+        message_reward: 1.0,
+        failure_penalty: 10.0,
+        half_life_ms: None,
+        ..Default::default()
+    })
+    .await;
+    let scorer_addr = scorer.node().listening_addr().unwrap();
+
+    // connect the peers one by one, so that their addresses can be told apart
+    let mut peers = Vec::new();
+    for name in ["good", "bad"] {
+        let peer = common::MessagingNode::new(name).await;
+        peer.enable_reading().await;
+        peer.enable_writing().await;
+        peer.node().connect(&scorer_addr).await.unwrap();
+        wait_until!(1, scorer.node().num_connected() == peers.len() + 1);
+        let addr = scorer
+            .node()
+            .connected_addrs()
+            .into_iter()
+            .find(|addr| !peers.iter().any(|(_, a)| a == addr))
+            .unwrap();
+        peers.push((peer, addr));
+    }
+    let (good, good_addr) = &peers[0];
+    let (bad, bad_addr) = &peers[1];
+
+    send_all(good, &scorer_addr, &["ok", "ok", "bonus"]).await;
+    send_all(bad, &scorer_addr, &["ok", "nope"]).await;
+
+    let known_peers = scorer.node().known_peers();
+    wait_until!(1, known_peers.score(good_addr) == Some(8.0));
+    wait_until!(1, known_peers.score(bad_addr) == Some(-9.0));
+
+    // the scores are sorted from the highest to the lowest
+    assert_eq!(
+        known_peers.scores(),
+        vec![(good_addr.clone(), 8.0), (bad_addr.clone(), -9.0)]
+    );
+}
+
+#[tokio::test]
+async fn reputation_thresholds() {
+    let scorer = scoring_node(ReputationPolicy {
+        failure_penalty: 10.0,
+        half_life_ms: None,
+        disconnect_threshold: Some(-15.0),
+        ban_threshold: Some(-25.0),
+        ..Default::default()
+    })
+    .await;
+    let mut events = scorer.node().subscribe_events();
+
+    let peer = common::MessagingNode::new("peer").await;
+    peer.enable_reading().await;
+    peer.enable_writing().await;
+    let peer_addr = peer.node().listening_addr().unwrap();
+
+    // the first failure keeps the score above the thresholds
+    scorer.node().connect(&peer_addr).await.unwrap();
+    scorer.node().register_failure(&peer_addr);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(scorer.node().is_connected(&peer_addr));
+
+    // the second one crosses the disconnect threshold
+    scorer.node().register_failure(&peer_addr);
+    loop {
+        if let NodeEvent::Disconnected { reason, .. } = events.recv().await.unwrap() {
+            assert_eq!(reason, DisconnectReason::LowReputation);
+            break;
+        }
+    }
+    assert!(!scorer.node().known_peers().is_banned(&peer_addr));
+
+    // the score of the peer is retained, as the node knows its listening address
+    scorer.node().connect(&peer_addr).await.unwrap();
+    assert_eq!(scorer.node().known_peers().score(&peer_addr), Some(-20.0));
+
+    // a further penalty crosses the ban threshold
+    assert_eq!(scorer.node().adjust_score(&peer_addr, -10.0), Some(-30.0));
+    wait_until!(1, !scorer.node().is_connected(&peer_addr));
+    assert!(scorer.node().known_peers().is_banned(&peer_addr));
+    assert!(scorer.node().connect(&peer_addr).await.is_err());
+}
+
+#[tokio::test]
+async fn reputation_decay() {
+    let scorer = scoring_node(ReputationPolicy {
+        half_life_ms: Some(100),
+        ..Default::default()
+    })
+    .await;
+
+    let peer_addr: PeerAddr = "127.0.0.1:1".parse().unwrap();
+    scorer.node().known_peers().add(&peer_addr);
+    assert_eq!(scorer.node().adjust_score(&peer_addr, 100.0), Some(100.0));
+
+    // roughly two half-lives later, the score is about a quarter of the original one
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let score = scorer.node().known_peers().score(&peer_addr).unwrap();
+    assert!(score > 10.0 && score < 30.0, "unexpected score: {}", score);
+
+    // adjustments apply to the decayed score
+    let adjusted = scorer.node().adjust_score(&peer_addr, -score).unwrap();
+    assert!(adjusted.abs() < 1.0, "unexpected score: {}", adjusted);
+
+    // unknown peers have no score
+    assert!(scorer
+        .node()
+        .adjust_score(&"127.0.0.1:2".parse().unwrap(), 1.0)
+        .is_none());
+}
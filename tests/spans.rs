@@ -0,0 +1,137 @@
+use bytes::Bytes;
+use parking_lot::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::*;
+use tracing_subscriber::util::SubscriberInitExt;
+
+mod common;
+use pea2pea::{
+    protocols::{Handshake, Reading, Writing},
+    Config, Connection, Node, Pea2Pea, PeerAddr,
+};
+
+use std::{io, sync::Arc};
+
+// a node that identifies itself with a single byte during the handshake
+#[derive(Clone)]
+struct IdentifiedNode(Node, u8);
+
+impl Pea2Pea for IdentifiedNode {
+    fn node(&self) -> &Node {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl Handshake for IdentifiedNode {
+    async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
+        conn.writer().write_u8(self.1).await?;
+        let peer_id = conn.reader().read_u8().await?;
+        conn.peer_id = Some(vec![peer_id].into());
+
+        Ok(conn)
+    }
+}
+
+impl_messaging!(IdentifiedNode);
+
+async fn identified_node(name: &str, id: u8) -> IdentifiedNode {
+    let config = Config {
+        name: Some(name.into()),
+        ..Default::default()
+    };
+    let node = IdentifiedNode(Node::new(Some(config)).await.unwrap(), id);
+    node.enable_handshake().await;
+    node.enable_reading().await;
+    node.enable_writing().await;
+
+    node
+}
+
+// collects the formatted logs
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn connection_spans() {
+    let logs = LogBuffer::default();
+    let logs_clone = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::TRACE)
+        .with_ansi(false)
+        .with_writer(move || logs_clone.clone())
+        .finish();
+    // the test runtime is single-threaded, so all the node tasks use this subscriber
+    let _guard = subscriber.set_default();
+
+    let alice = identified_node("alice", 0xaa).await;
+    let bob = identified_node("bob", 0xbb).await;
+    let bob_addr = bob.node().listening_addr().unwrap();
+
+    alice.node().connect(&bob_addr).await.unwrap();
+    alice
+        .send_direct_message(&bob_addr, Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, bob.node().stats().received().0 == 1);
+
+    // the spans are created at the most verbose enabled level
+    let conn_span = alice.node().connection_span(&bob_addr);
+    let metadata = conn_span.metadata().unwrap();
+    assert_eq!(metadata.name(), "conn");
+    assert_eq!(*metadata.level(), Level::TRACE);
+
+    // the node's span is used for addresses without a connection
+    let unknown_addr = "127.0.0.1:1".parse().unwrap();
+    assert_eq!(
+        alice.node().connection_span(&unknown_addr).id(),
+        alice.node().span().id()
+    );
+
+    let logs = String::from_utf8(logs.0.lock().clone()).unwrap();
+    let alice_conn = format!(
+        "node{{name=\"alice\"}}:conn{{addr={} side=Responder id=0",
+        bob_addr
+    );
+    let has_log = |span: &str, msg: &str| {
+        logs.lines()
+            .any(|line| line.contains(span) && line.contains(msg))
+    };
+
+    // the connection's span is used by the handshake, reader, processing and writer tasks
+    for msg in [
+        "shaking hands",
+        "spawned a task for processing messages",
+        "spawned a task for reading messages",
+        "spawned a task for writing messages",
+        "sent 7B",
+    ] {
+        assert!(
+            has_log(&alice_conn, msg),
+            "missing a log containing \"{}\":\n{}",
+            msg,
+            logs
+        );
+    }
+
+    // the PeerId is recorded once the handshake concludes
+    assert!(has_log(&format!("{} peer_id=bb}}", alice_conn), "sent 7B"));
+
+    // the side of the connection is the peer's side
+    assert!(has_log(
+        "node{name=\"bob\"}:conn{addr=",
+        "side=Initiator id=0 peer_id=aa}: pea2pea::protocols::reading: isolated 7B"
+    ));
+}
@@ -4,9 +4,11 @@ use rand::{rngs::SmallRng, Rng, SeedableRng};
 mod common;
 use pea2pea::{
     protocols::{Reading, Writing},
-    Pea2Pea,
+    Histogram, KnownPeers, KnownPeersSnapshot, Pea2Pea, PeerAddr, PeerId, Rates, StatsSnapshot,
 };
 
+use std::time::{Duration, SystemTime};
+
 #[tokio::test]
 async fn message_stats() {
     let mut rng = SmallRng::from_entropy();
@@ -18,7 +20,7 @@ async fn message_stats() {
     let writer = common::MessagingNode::new("writer").await;
     writer.enable_writing().await;
 
-    writer.node().connect(reader_addr).await.unwrap();
+    writer.node().connect(&reader_addr).await.unwrap();
 
     let sent_msgs_count = rng.gen_range(2..=64); // shouldn't exceed the outbound queue depth
     let mut msg = vec![0u8; rng.gen_range(1..=4096)];
@@ -28,7 +30,7 @@ async fn message_stats() {
 
     for _ in 0..sent_msgs_count {
         writer
-            .send_direct_message(reader_addr, msg.clone())
+            .send_direct_message(&reader_addr, msg.clone())
             .unwrap()
             .await
             .unwrap();
@@ -39,7 +41,7 @@ async fn message_stats() {
 
     assert!(writer.node().stats().sent() == (sent_msgs_count, expected_msgs_size));
     wait_until!(1, {
-        if let Some(stats) = writer.node().known_peers().get(reader_addr) {
+        if let Some(stats) = writer.node().known_peers().get(&reader_addr) {
             let (sent_msgs, sent_bytes) = stats.sent();
             sent_msgs == sent_msgs_count && sent_bytes == expected_msgs_size
         } else {
@@ -60,3 +62,277 @@ async fn message_stats() {
         }
     });
 }
+
+#[tokio::test]
+async fn connection_history_stats() {
+    let peer = common::MessagingNode::new("peer").await;
+    peer.enable_reading().await;
+    peer.enable_writing().await;
+    let peer_addr = peer.node().listening_addr().unwrap();
+
+    let node = common::MessagingNode::new("node").await;
+    node.enable_reading().await;
+    node.enable_writing().await;
+
+    let before = SystemTime::now() - Duration::from_millis(1);
+
+    node.node().connect(&peer_addr).await.unwrap();
+    let stats = node.node().known_peers().get(&peer_addr).unwrap();
+    assert_eq!(stats.connections(), 1);
+    assert_eq!(stats.reconnections(), 0);
+    let first_seen = stats.first_seen().unwrap();
+    assert!(first_seen >= before);
+    assert!(stats.connected_since().is_some());
+    assert!(stats.last_sent().is_none() && stats.last_received().is_none());
+
+    node.send_direct_message(&peer_addr, Bytes::from_static(b"hi"))
+        .unwrap()
+        .await
+        .unwrap();
+    assert!(stats.last_sent().unwrap() >= first_seen);
+    wait_until!(1, peer.node().num_connected() == 1);
+    let node_addr = peer.node().connected_addrs()[0].clone();
+    peer.send_direct_message(&node_addr, Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, stats.last_received().is_some());
+    assert!(stats.last_seen().unwrap() >= stats.last_received().unwrap());
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(node.node().disconnect(&peer_addr).await);
+    assert!(stats.connected_since().is_none());
+    let connected_duration = stats.connected_duration();
+    assert!(connected_duration >= Duration::from_millis(50));
+
+    // the cumulative duration only grows while connected
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(stats.connected_duration(), connected_duration);
+
+    wait_until!(1, peer.node().num_connected() == 0);
+    node.node().connect(&peer_addr).await.unwrap();
+    assert_eq!(stats.connections(), 2);
+    assert_eq!(stats.reconnections(), 1);
+    assert_eq!(stats.first_seen(), Some(first_seen));
+
+    // the peer was seen just now, so it isn't idle
+    let known_peers = node.node().known_peers();
+    assert!(known_peers.idle(Duration::from_secs(60)).is_empty());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(known_peers.idle(Duration::from_millis(10)), vec![peer_addr]);
+}
+
+#[tokio::test]
+async fn rate_and_histogram_stats() {
+    let reader = common::MessagingNode::new("reader").await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+    reader.enable_reading().await;
+
+    let writer = common::MessagingNode::new("writer").await;
+    writer.enable_writing().await;
+    writer.node().connect(&reader_addr).await.unwrap();
+
+    let sizes = [1usize, 10, 1000];
+    for size in sizes {
+        writer
+            .send_direct_message(&reader_addr, Bytes::from(vec![1u8; size]))
+            .unwrap()
+            .await
+            .unwrap();
+    }
+    wait_until!(1, reader.node().stats().received().0 == sizes.len() as u64);
+
+    let sent = writer.node().stats().snapshot();
+    let received = reader.node().stats().snapshot();
+
+    // the message sizes include the length prefixes
+    for histogram in [&sent.sent_sizes, &received.received_sizes] {
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.sum, 3 + 12 + 1002);
+        assert_eq!(histogram.buckets[Histogram::bucket_index(3)], 1);
+        assert_eq!(histogram.buckets[Histogram::bucket_index(12)], 1);
+        assert_eq!(histogram.buckets[Histogram::bucket_index(1002)], 1);
+        assert_eq!(histogram.quantile(0.5), Some(15));
+        assert_eq!(histogram.quantile(1.0), Some(1023));
+    }
+    assert_eq!(sent.received_sizes.count, 0);
+    assert_eq!(sent.write_latencies.count, 3);
+    assert_eq!(received.write_latencies.count, 0);
+
+    // the per-peer stats are tracked too
+    let peer_snapshot = writer
+        .node()
+        .known_peers()
+        .get(&reader_addr)
+        .unwrap()
+        .snapshot();
+    assert_eq!(peer_snapshot.sent_sizes, sent.sent_sizes);
+    assert_eq!(peer_snapshot.write_latencies.count, 3);
+
+    // the rates decay over time, the shorter average quicker than the longer one
+    assert!(sent.sent_rates.msgs_1s > 0.0 && sent.sent_rates.bytes_1m > 0.0);
+    assert!(received.received_rates.msgs_1m > 0.0);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let later = writer.node().stats().snapshot().sent_rates;
+    assert!(later.msgs_1s < sent.sent_rates.msgs_1s * 0.7);
+    assert!(later.msgs_1m > sent.sent_rates.msgs_1m * 0.95);
+}
+
+#[test]
+fn histogram_quantiles() {
+    let mut histogram = Histogram::default();
+    assert_eq!(histogram.quantile(0.5), None);
+    assert_eq!(histogram.mean(), None);
+
+    for value in [0u64, 1, 3, 100, u64::MAX] {
+        histogram.buckets[Histogram::bucket_index(value)] += 1;
+        histogram.count += 1;
+        histogram.sum = histogram.sum.saturating_add(value);
+    }
+
+    assert_eq!(histogram.quantile(0.0), Some(0));
+    assert_eq!(histogram.quantile(0.4), Some(1));
+    assert_eq!(histogram.quantile(0.6), Some(3));
+    assert_eq!(histogram.quantile(0.8), Some(127));
+    assert_eq!(histogram.quantile(1.0), Some(u64::MAX));
+}
+
+#[tokio::test]
+async fn stats_snapshot_diff() {
+    let reader = common::MessagingNode::new("reader").await;
+    let reader_addr = reader.node().listening_addr().unwrap();
+    reader.enable_reading().await;
+
+    let writer = common::MessagingNode::new("writer").await;
+    writer.enable_writing().await;
+    writer.node().connect(&reader_addr).await.unwrap();
+
+    writer
+        .send_direct_message(&reader_addr, Bytes::from_static(b"warm-up"))
+        .unwrap()
+        .await
+        .unwrap();
+    wait_until!(1, reader.node().stats().received().0 == 1);
+
+    let node_before = writer.node().stats().snapshot();
+    let peers_before = writer.node().known_peers().stats_snapshot();
+
+    for _ in 0..2 {
+        writer
+            .send_direct_message(&reader_addr, Bytes::from_static(b"hello"))
+            .unwrap()
+            .await
+            .unwrap();
+    }
+    wait_until!(1, reader.node().stats().received().0 == 3);
+
+    // the diffs contain the exact changes caused by the 2 messages
+    let node_diff = writer.node().stats().snapshot().diff(&node_before);
+    assert_eq!((node_diff.msgs_sent, node_diff.bytes_sent), (2, 14));
+    assert_eq!(node_diff.sent_sizes.count, 2);
+    assert_eq!(node_diff.sent_sizes.sum, 14);
+    assert_eq!(node_diff.sent_sizes.buckets[Histogram::bucket_index(7)], 2);
+    assert_eq!(node_diff.write_latencies.count, 2);
+    assert_eq!(node_diff.connections, 0);
+
+    let peers_diff = writer
+        .node()
+        .known_peers()
+        .stats_snapshot()
+        .diff(&peers_before);
+    let peer_diff = &peers_diff.addrs[&reader_addr];
+    assert_eq!((peer_diff.msgs_sent, peer_diff.bytes_sent), (2, 14));
+    assert_eq!(peer_diff.msgs_received, 0);
+    assert!(peer_diff.last_sent.is_some());
+
+    // snapshots don't change afterwards
+    assert_eq!(peers_before.addrs[&reader_addr].msgs_sent, 1);
+}
+
+#[test]
+fn stats_snapshot_merge() {
+    let first_seen = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+    let last_seen = SystemTime::UNIX_EPOCH + Duration::from_secs(20);
+
+    let mut merged = StatsSnapshot {
+        msgs_sent: 1,
+        bytes_sent: 10,
+        first_seen: Some(last_seen),
+        last_seen: Some(last_seen),
+        connected_duration: Duration::from_secs(1),
+        sent_rates: Rates {
+            msgs_1s: 1.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    merged.sent_sizes.buckets[Histogram::bucket_index(10)] += 1;
+    merged.sent_sizes.count += 1;
+    merged.sent_sizes.sum += 10;
+
+    let mut other = StatsSnapshot {
+        msgs_sent: 2,
+        bytes_sent: 20,
+        failures: 1,
+        first_seen: Some(first_seen),
+        last_seen: Some(first_seen),
+        connected_duration: Duration::from_secs(2),
+        sent_rates: Rates {
+            msgs_1s: 2.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    other.sent_sizes.buckets[Histogram::bucket_index(10)] += 2;
+    other.sent_sizes.count += 2;
+    other.sent_sizes.sum += 20;
+
+    merged.merge(&other);
+    assert_eq!((merged.msgs_sent, merged.bytes_sent), (3, 30));
+    assert_eq!(merged.failures, 1);
+    assert_eq!(merged.first_seen, Some(first_seen));
+    assert_eq!(merged.last_seen, Some(last_seen));
+    assert_eq!(merged.connected_duration, Duration::from_secs(3));
+    assert_eq!(merged.sent_rates.msgs_1s, 3.0);
+    assert_eq!(merged.sent_sizes.buckets[Histogram::bucket_index(10)], 3);
+    assert_eq!((merged.sent_sizes.count, merged.sent_sizes.sum), (3, 30));
+
+    // diffing reverses merging for the cumulative values
+    let diff = merged.diff(&other);
+    assert_eq!((diff.msgs_sent, diff.bytes_sent, diff.failures), (1, 10, 0));
+    assert_eq!(diff.connected_duration, Duration::from_secs(1));
+    assert_eq!(diff.sent_sizes.buckets[Histogram::bucket_index(10)], 1);
+}
+
+#[test]
+fn stats_snapshot_serde() {
+    let addr1: PeerAddr = "127.0.0.1:1".parse().unwrap();
+    let addr2: PeerAddr = "[::1]:2".parse().unwrap();
+    let peer_id = PeerId::from(vec![0xca, 0xfe]);
+
+    let known_peers = KnownPeers::default();
+    known_peers.add(&addr1);
+    known_peers.add_peer_id(&addr2, peer_id.clone());
+    known_peers.register_connection(&addr1);
+    known_peers.register_sent_message(&addr1, 10);
+    known_peers.register_received_message(&addr2, 20);
+    known_peers.adjust_score(&addr1, 2.5);
+
+    let mut snapshot = known_peers.stats_snapshot();
+    assert_eq!(snapshot.peer_ids[&peer_id].msgs_received, 1);
+
+    // the float parser of serde_json may be off by one ULP, so the rates are reset
+    for stats in snapshot
+        .addrs
+        .values_mut()
+        .chain(snapshot.peer_ids.values_mut())
+    {
+        stats.sent_rates = Default::default();
+        stats.received_rates = Default::default();
+    }
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let deserialized: KnownPeersSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, snapshot);
+    assert_eq!(deserialized.scores[&addr1], 2.5);
+}
@@ -0,0 +1,164 @@
+#![cfg(feature = "tls")]
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use tracing::*;
+
+mod common;
+use pea2pea::{
+    protocols::{Handshake, Reading, Writing},
+    transport::{
+        rustls::{
+            pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+            server::WebPkiClientVerifier,
+            ClientConfig, RootCertStore, ServerConfig,
+        },
+        TcpTransport, TlsTransport,
+    },
+    Connection, Node, Pea2Pea, PeerAddr,
+};
+
+use std::{io, sync::Arc};
+
+struct Identity {
+    cert: CertificateDer<'static>,
+    key: PrivateKeyDer<'static>,
+}
+
+impl Identity {
+    fn generate() -> Self {
+        let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".into()]).unwrap();
+
+        Self {
+            cert: certified.cert.der().clone(),
+            key: PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()).into(),
+        }
+    }
+
+    // requires the peers to present a certificate signed with `trusted` (mutual TLS)
+    fn transport(&self, trusted: &Identity) -> TlsTransport {
+        let mut roots = RootCertStore::empty();
+        roots.add(trusted.cert.clone()).unwrap();
+        let roots = Arc::new(roots);
+
+        let client_verifier = WebPkiClientVerifier::builder(Arc::clone(&roots))
+            .build()
+            .unwrap();
+        let server_config = ServerConfig::builder()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(vec![self.cert.clone()], self.key.clone_key())
+            .unwrap();
+
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(vec![self.cert.clone()], self.key.clone_key())
+            .unwrap();
+
+        TlsTransport::new(
+            TcpTransport,
+            Arc::new(server_config),
+            Arc::new(client_config),
+        )
+    }
+}
+
+#[derive(Clone)]
+struct TlsNode {
+    node: Node,
+    peer_certs: Arc<Mutex<Vec<CertificateDer<'static>>>>,
+}
+
+impl TlsNode {
+    async fn new(transport: TlsTransport) -> Self {
+        Self {
+            node: Node::with_transport(None, transport).await.unwrap(),
+            peer_certs: Default::default(),
+        }
+    }
+}
+
+impl Pea2Pea for TlsNode {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+}
+
+#[async_trait::async_trait]
+impl Handshake for TlsNode {
+    async fn perform_handshake(&self, conn: Connection) -> io::Result<Connection> {
+        // the TLS handshake has already been concluded at this point
+        let certs = conn
+            .peer_certificates()
+            .ok_or(io::ErrorKind::PermissionDenied)?;
+        self.peer_certs.lock().extend_from_slice(certs);
+
+        Ok(conn)
+    }
+}
+
+impl_messaging!(TlsNode);
+
+#[tokio::test]
+async fn tls_mutual_auth_works() {
+    let alice_id = Identity::generate();
+    let bob_id = Identity::generate();
+
+    let alice = TlsNode::new(alice_id.transport(&bob_id)).await;
+    let bob = TlsNode::new(bob_id.transport(&alice_id)).await;
+
+    for node in &[&alice, &bob] {
+        node.enable_handshake().await;
+        node.enable_reading().await;
+        node.enable_writing().await;
+    }
+
+    let bob_addr = bob.node().listening_addr().unwrap();
+    alice.node().connect(&bob_addr).await.unwrap();
+    wait_until!(1, bob.node().num_connected() == 1);
+
+    // both sides have seen each other's certificates
+    assert_eq!(*alice.peer_certs.lock(), vec![bob_id.cert.clone()]);
+    assert_eq!(*bob.peer_certs.lock(), vec![alice_id.cert.clone()]);
+
+    // the certificates remain available once the connections are established
+    assert_eq!(
+        alice
+            .node()
+            .connection_info(&bob_addr)
+            .unwrap()
+            .peer_certificates,
+        Some(vec![bob_id.cert.clone()])
+    );
+    let alice_addr = bob.node().connected_addrs().pop().unwrap();
+    assert_eq!(
+        bob.node()
+            .connection_info(&alice_addr)
+            .unwrap()
+            .peer_certificates,
+        Some(vec![alice_id.cert.clone()])
+    );
+
+    assert!(alice
+        .send_direct_message(&bob_addr, Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap());
+    wait_until!(1, bob.node().stats().received().0 == 1);
+}
+
+#[tokio::test]
+async fn tls_untrusted_peer_is_rejected() {
+    let alice_id = Identity::generate();
+    let bob_id = Identity::generate();
+    let mallory_id = Identity::generate();
+
+    // alice only trusts mallory, so bob's certificate is refused
+    let alice = TlsNode::new(alice_id.transport(&mallory_id)).await;
+    let bob = TlsNode::new(bob_id.transport(&alice_id)).await;
+
+    let bob_addr = bob.node().listening_addr().unwrap();
+    assert!(alice.node().connect(&bob_addr).await.is_err());
+    assert_eq!(alice.node().num_connected(), 0);
+    wait_until!(1, bob.node().num_connecting() == 0);
+    assert_eq!(bob.node().num_connected(), 0);
+}
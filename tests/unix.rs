@@ -0,0 +1,142 @@
+#![cfg(unix)]
+
+use bytes::Bytes;
+
+mod common;
+use pea2pea::{
+    protocols::{Reading, Writing},
+    transport::{Transport, UnixTransport},
+    Config, Node, Pea2Pea, PeerAddr,
+};
+
+use std::{env, io, os::unix::net::UnixListener, path::PathBuf, process};
+
+fn socket_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("pea2pea_{}_{}.sock", name, process::id()))
+}
+
+#[tokio::test]
+async fn unix_socket_messaging_works() {
+    let socket_path = socket_path("messaging");
+
+    let listener_transport = UnixTransport::with_listening_path(&socket_path);
+    let listener = common::MessagingNode(
+        Node::with_transport(None, listener_transport)
+            .await
+            .unwrap(),
+    );
+    listener.enable_reading().await;
+
+    let config = Config {
+        listener_ip: None,
+        ..Default::default()
+    };
+    let dialer_transport = UnixTransport::default();
+    let dialer = common::MessagingNode(
+        Node::with_transport(Some(config), dialer_transport)
+            .await
+            .unwrap(),
+    );
+    dialer.enable_writing().await;
+
+    let listener_addr = PeerAddr::Unix(socket_path.clone());
+    assert_eq!(listener.node().listening_addr().unwrap(), listener_addr);
+
+    dialer.node().connect(&listener_addr).await.unwrap();
+    wait_until!(1, listener.node().num_connected() == 1);
+    assert!(matches!(
+        listener.node().connected_addrs()[0],
+        PeerAddr::UnnamedUnix(_)
+    ));
+
+    assert!(dialer
+        .send_direct_message(&listener_addr, Bytes::from_static(b"hello"))
+        .unwrap()
+        .await
+        .unwrap());
+    wait_until!(1, listener.node().stats().received().0 == 1);
+
+    // the socket file is removed once the listener is shut down
+    listener.node().shut_down().await;
+    wait_until!(1, !socket_path.exists());
+}
+
+#[tokio::test]
+async fn unix_socket_stale_files() {
+    let socket_path = socket_path("stale");
+
+    // a socket file left behind by a listener that is gone is removed
+    drop(UnixListener::bind(&socket_path).unwrap());
+    assert!(socket_path.exists());
+    let node = Node::with_transport(None, UnixTransport::with_listening_path(&socket_path))
+        .await
+        .unwrap();
+
+    // a socket that is still in use is left alone
+    let err = Node::with_transport(None, UnixTransport::with_listening_path(&socket_path))
+        .await
+        .map(drop)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+    node.shut_down().await;
+    wait_until!(1, !socket_path.exists());
+}
+
+#[tokio::test]
+async fn unix_socket_unnamed_peers() {
+    let socket_path = socket_path("unnamed");
+    let listener = Node::with_transport(None, UnixTransport::with_listening_path(&socket_path))
+        .await
+        .unwrap();
+    let listener_addr = listener.listening_addr().unwrap();
+
+    let config = Config {
+        listener_ip: None,
+        ..Default::default()
+    };
+    let dialers =
+        common::start_nodes_with_transport(2, Some(config), UnixTransport::default()).await;
+    for dialer in &dialers {
+        dialer.connect(&listener_addr).await.unwrap();
+    }
+    wait_until!(1, listener.num_connected() == 2);
+
+    // the connections from unnamed sockets can be told apart
+    let addrs = listener.connected_addrs();
+    assert!(addrs
+        .iter()
+        .all(|addr| matches!(addr, PeerAddr::UnnamedUnix(_))));
+    assert_ne!(addrs[0], addrs[1]);
+
+    // they have no IP, so they are not subject to the IP-based features
+    assert!(addrs.iter().all(|addr| addr.ip().is_none()));
+
+    listener.shut_down().await;
+}
+
+#[tokio::test]
+async fn unix_socket_addrs() {
+    // IP addresses are never mistaken for socket paths, whatever their port
+    let ip_addr: PeerAddr = "[fd00::1]:0".parse().unwrap();
+    assert_eq!(ip_addr, PeerAddr::Ip("[fd00::1]:0".parse().unwrap()));
+    let err = UnixTransport::default()
+        .dial(&ip_addr)
+        .await
+        .map(drop)
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // the textual representation can be parsed back
+    for addr in [
+        ip_addr,
+        PeerAddr::Unix(socket_path("addrs")),
+        PeerAddr::UnnamedUnix(1),
+    ] {
+        assert_eq!(addr.to_string().parse::<PeerAddr>().unwrap(), addr);
+    }
+    assert_eq!(
+        "unix-unnamed:x".parse::<PeerAddr>().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+}